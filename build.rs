@@ -1,20 +1,18 @@
-use afm;
 use glob::glob;
-use pom;
 use proc_macro2::{Ident, Span};
 use quote::quote;
 
 use std::error::Error;
 use std::io::Write;
 use std::path::Path;
-use std::{char, u32};
+use std::char;
 use std::{env, fs};
 
 use inflector::cases::pascalcase::to_pascal_case;
 use inflector::cases::snakecase::to_snake_case;
 
-const GLYPH_LIST: &'static str = "resources/font/adobe_glyph_list/glyphlist.txt";
-const GLYPH_LIST_ZAPF_DINGBATS: &'static str = "resources/font/adobe_glyph_list/zapfdingbats.txt";
+const GLYPH_LIST: &str = "resources/font/adobe_glyph_list/glyphlist.txt";
+const GLYPH_LIST_ZAPF_DINGBATS: &str = "resources/font/adobe_glyph_list/zapfdingbats.txt";
 
 fn main() -> Result<(), Box<dyn Error>> {
     let out_dir = env::var("OUT_DIR")?;
@@ -110,7 +108,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let font_names_case_corrected = font_names
         .iter()
-        .map(|s| Ident::new(&to_pascal_case(&s), Span::call_site()));
+        .map(|s| Ident::new(&to_pascal_case(s), Span::call_site()));
     let font_names_case_corrected2 = font_names_case_corrected.clone();
 
     let token_stream = quote!(
@@ -35,7 +35,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let mut page = Page::new();
     page.add_content(&mut context, None, |page_context| {
-        let mut font = BuiltInFont::TimesItalic.font(&mut page_context.pdf_context)?;
+        let mut font = BuiltInFont::TimesItalic.font(page_context.pdf_context)?;
         font.encoding = Some(EncodingEntry::Predefined(
             PredefinedEncoding::WinAnsiEncoding,
         ));
@@ -50,11 +50,9 @@ fn main() -> Result<(), Box<dyn Error>> {
         Ok(())
     })?;
 
-    context.add_page(page);
+    context.add_page(page)?;
 
-    context
-        .document_info
-        .insert("Title".to_owned(), b"My Document".to_vec());
+    context.document_info.title = Some("My Document".to_owned());
 
     context.finish()?;
     Ok(())
@@ -0,0 +1,247 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! Writing documents to an asynchronous writer.
+
+use std::future::Future;
+use std::io::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::object::{IndirectReference, PdfFormat};
+use crate::pagetree::Page;
+use crate::{DocumentContext, Error, Version};
+
+/// The writer of the wrapped `DocumentContext`. It collects the output until it is passed on
+/// to the asynchronous writer.
+#[derive(Debug, Clone, Default)]
+struct PendingOutput(Arc<Mutex<Vec<u8>>>);
+
+impl PendingOutput {
+    fn lock(&self) -> MutexGuard<'_, Vec<u8>> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Write the collected output to `writer`. Only the bytes that have been written are
+    /// removed, so nothing is lost if the future is dropped before it completes.
+    async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> Result<(), Error> {
+        // nothing is added while the wrapping context is borrowed by the future
+        let bytes = self.lock().clone();
+        let mut written = 0;
+        while written < bytes.len() {
+            let count = writer.write(&bytes[written..]).await?;
+            if count == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into());
+            }
+            self.lock().drain(..count);
+            written += count;
+        }
+        Ok(())
+    }
+}
+
+impl Write for PendingOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A `DocumentContext` that writes to an `AsyncWrite`.
+///
+/// Objects are serialized synchronously into a buffer, which is written to the asynchronous
+/// writer whenever an object has been written, so the document is never held in memory as a
+/// whole and a slow writer slows down the producer. Everything that is not covered by the
+/// methods of this type, like adding page content or setting up encryption, is done on the
+/// wrapped `DocumentContext` obtained with `context`, followed by a call to `flush`.
+#[derive(Debug)]
+pub struct AsyncDocumentContext<'a, W> {
+    context: DocumentContext<'a>,
+    pending: PendingOutput,
+    writer: W,
+}
+
+impl<'a, W: AsyncWrite + Unpin> AsyncDocumentContext<'a, W> {
+    pub fn with_writer(writer: W, version: Version) -> Result<Self, Error> {
+        let pending = PendingOutput::default();
+        let context = DocumentContext::with_writer(pending.clone(), version)?;
+        Ok(AsyncDocumentContext {
+            context,
+            pending,
+            writer,
+        })
+    }
+
+    /// The wrapped context. Call `flush` after writing objects through it.
+    pub fn context(&mut self) -> &mut DocumentContext<'a> {
+        &mut self.context
+    }
+
+    /// Write everything the context has produced so far to the writer.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        self.pending.write_to(&mut self.writer).await
+    }
+
+    /// Write an indirect object and return an indirect reference to it.
+    #[track_caller]
+    pub fn write_object<'s, T: PdfFormat + 's>(
+        &'s mut self,
+        object: T,
+    ) -> impl Future<Output = Result<IndirectReference<T>, Error>> + 's {
+        // The object is serialized before the future is created, so that the future is `Send`
+        // even if the object is not.
+        let result = self.context.write_object(object);
+        self.write_pending(result)
+    }
+
+    /// Write `page` as the next page of the document.
+    pub fn add_page(
+        &mut self,
+        page: Page,
+    ) -> impl Future<Output = Result<IndirectReference<Page>, Error>> + '_ {
+        let result = self.context.add_page(page);
+        self.write_pending(result)
+    }
+
+    /// Write the output produced by the operation that returned `result`, also if it failed.
+    /// If the future is dropped, the output is written by the next operation.
+    fn write_pending<'s, T: 's>(
+        &'s mut self,
+        result: Result<T, Error>,
+    ) -> impl Future<Output = Result<T, Error>> + 's {
+        let pending = &self.pending;
+        let writer = &mut self.writer;
+        async move {
+            pending.write_to(writer).await?;
+            result
+        }
+    }
+
+    /// Finish the document, write the remaining output and flush the writer. The writer is
+    /// returned without being shut down.
+    pub async fn finish(self) -> Result<W, Error> {
+        let AsyncDocumentContext {
+            context,
+            pending,
+            mut writer,
+        } = self;
+        context.finish()?;
+        pending.write_to(&mut writer).await?;
+        writer.flush().await?;
+        Ok(writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    use super::*;
+    use crate::object::RawIndirectReference;
+    use crate::reader::ExistingDocument;
+    use crate::stream::{StreamEncoder, StreamFilter};
+    use crate::XRefMode;
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[test]
+    fn test_async_writer() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let future = async {
+            let mut context = AsyncDocumentContext::with_writer(Vec::new(), Version::Pdf1_7)?;
+            context.context().xref_mode = XRefMode::Stream;
+
+            let mut encoder = StreamEncoder::new(Some(StreamFilter::Deflate));
+            encoder.write_all(b"0 0 m 100 100 l S")?;
+            let stream = encoder.into_stream()?;
+            let content = context.write_object(stream).await?;
+            // the stream has been passed on to the writer
+            assert!(context.writer.ends_with(b"endobj\n"));
+
+            let mut page = Page::new();
+            page.contents.push(content);
+            context.add_page(page).await?;
+            context.finish().await
+        };
+        assert_send(&future);
+        let output = runtime.block_on(future).unwrap();
+        assert!(output.starts_with(b"%PDF-1.7\n"));
+        assert!(output.ends_with(b"%%EOF\n"));
+    }
+
+    /// Accepts a single byte at a time and is only ready on every second poll.
+    #[derive(Debug, Default)]
+    struct SlowWriter {
+        output: Vec<u8>,
+        ready: bool,
+    }
+
+    impl AsyncWrite for SlowWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            self.ready = !self.ready;
+            if self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.output.push(buf[0]);
+            Poll::Ready(Ok(1))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn test_dropped_future() {
+        let mut context =
+            AsyncDocumentContext::with_writer(SlowWriter::default(), Version::Pdf1_7).unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+        // dropped before it is polled
+        drop(context.write_object(vec![1i64, 2, 3]));
+        // dropped while the object is written
+        let mut future = Box::pin(context.write_object(vec![4i64, 5, 6]));
+        for _ in 0..10 {
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+        }
+        drop(future);
+        assert!(!context.writer.output.is_empty());
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let output = runtime.block_on(context.finish()).unwrap().output;
+        let document = ExistingDocument::parse(&output).unwrap();
+        let first = document.resolve(RawIndirectReference(1, 0)).unwrap();
+        assert_eq!(first.as_array().unwrap().len(), 3);
+        let second = document.resolve(RawIndirectReference(2, 0)).unwrap();
+        assert_eq!(second.as_array().unwrap()[2].as_integer(), Some(6));
+    }
+}
@@ -0,0 +1,234 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! Files that are embedded in the document, e.g. the XML of an electronic invoice.
+
+use std::io::Write;
+
+use crypto::digest::Digest;
+use crypto::md5::Md5;
+
+use crate as lemon_pdf;
+use lemon_pdf_derive::PdfFormat;
+
+use crate::date::PdfDate;
+use crate::object::{text_string, IndirectReference};
+use crate::stream::{Stream, StreamEncoder, StreamFilter};
+use crate::{DocumentContext, Result};
+
+/// How an associated file relates to the document. Written as `/AFRelationship` of the file
+/// specification.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PdfFormat)]
+pub enum AFRelationship {
+    /// The original source material of the document.
+    Source,
+    /// Data that is used to derive the content of the document, like a table for a chart.
+    Data,
+    /// An alternative representation of the content, e.g. the XML of an e-invoice.
+    Alternative,
+    /// A supplemental representation of the content, e.g. for easier processing.
+    Supplement,
+    EncryptedPayload,
+    FormData,
+    Schema,
+    Unspecified,
+}
+
+/// A file that is attached to the document with `DocumentContext::attach_file`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    /// The file name, e.g. `factur-x.xml`.
+    pub name: String,
+    pub contents: Vec<u8>,
+    /// The MIME type, e.g. `text/xml`. Required by PDF/A-3.
+    pub mime_type: Option<String>,
+    pub description: Option<String>,
+    pub creation_date: Option<PdfDate>,
+    /// The date of the last modification. Required by PDF/A-3.
+    pub mod_date: Option<PdfDate>,
+    /// Defaults to `AFRelationship::Unspecified`.
+    pub relationship: AFRelationship,
+    /// How the contents are compressed. Defaults to `StreamFilter::Deflate`.
+    pub filter: Option<StreamFilter>,
+}
+
+impl Attachment {
+    pub fn new(name: impl Into<String>, contents: impl Into<Vec<u8>>) -> Self {
+        Attachment {
+            name: name.into(),
+            contents: contents.into(),
+            mime_type: None,
+            description: None,
+            creation_date: None,
+            mod_date: None,
+            relationship: AFRelationship::Unspecified,
+            filter: Some(StreamFilter::Deflate),
+        }
+    }
+
+    /// Write the embedded file stream and return the file specification that refers to it.
+    pub(crate) fn write_to_context(
+        self,
+        context: &mut DocumentContext,
+    ) -> Result<FileSpecification> {
+        let mut checksum = [0; 16];
+        let mut md5 = Md5::new();
+        md5.input(&self.contents);
+        md5.result(&mut checksum);
+        let params = EmbeddedFileParams {
+            size: self.contents.len() as i64,
+            check_sum: checksum.to_vec(),
+            creation_date: self.creation_date,
+            mod_date: self.mod_date,
+        };
+
+        let mut encoder = StreamEncoder::new(self.filter);
+        encoder.write_all(&self.contents)?;
+        let mut stream = encoder.into_stream()?;
+        stream.add_key_value("Type".to_string(), Box::new("EmbeddedFile"));
+        if let Some(mime_type) = self.mime_type {
+            stream.add_key_value("Subtype".to_string(), Box::new(mime_type));
+        }
+        stream.add_key_value("Params".to_string(), Box::new(params));
+        let embedded_file = context.write_object(stream)?;
+
+        Ok(FileSpecification {
+            file: text_string(&self.name),
+            unicode_file: text_string(&self.name),
+            embedded_file: EmbeddedFiles {
+                file: embedded_file,
+                unicode_file: embedded_file,
+            },
+            desc: self.description.as_deref().map(text_string),
+            relationship: self.relationship,
+        })
+    }
+}
+
+/// The parameters of an embedded file stream.
+#[derive(Debug, PdfFormat)]
+#[omit_type(true)]
+struct EmbeddedFileParams {
+    size: i64,
+    /// The MD5 digest of the uncompressed contents.
+    check_sum: Vec<u8>,
+    #[skip_if("Option::is_none")]
+    creation_date: Option<PdfDate>,
+    #[skip_if("Option::is_none")]
+    mod_date: Option<PdfDate>,
+}
+
+/// The embedded file streams of a file specification.
+#[derive(Debug, PdfFormat)]
+#[omit_type(true)]
+struct EmbeddedFiles {
+    #[rename("F")]
+    file: IndirectReference<Stream>,
+    #[rename("UF")]
+    unicode_file: IndirectReference<Stream>,
+}
+
+/// A file specification dictionary of an embedded file.
+#[derive(Debug, PdfFormat)]
+#[rename("Filespec")]
+pub struct FileSpecification {
+    #[rename("F")]
+    file: Vec<u8>,
+    #[rename("UF")]
+    unicode_file: Vec<u8>,
+    #[rename("EF")]
+    embedded_file: EmbeddedFiles,
+    #[skip_if("Option::is_none")]
+    desc: Option<Vec<u8>>,
+    #[rename("AFRelationship")]
+    relationship: AFRelationship,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pdfa::PdfAConformance;
+    use crate::reader::ExistingDocument;
+    use crate::{Error, Version};
+
+    #[test]
+    fn test_attachment() {
+        let mut output = Vec::new();
+        let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_7).unwrap();
+        context
+            .set_pdfa_conformance(PdfAConformance::PdfA3b)
+            .unwrap();
+        let mut invoice = Attachment::new("factur-x.xml", &b"<rsm:CrossIndustryInvoice/>"[..]);
+        invoice.mime_type = Some("text/xml".to_string());
+        invoice.relationship = AFRelationship::Alternative;
+        invoice.filter = None;
+        let result = context.attach_file(invoice.clone());
+        assert!(matches!(result, Err(Error::Conformance { .. })));
+        invoice.mod_date = Some(PdfDate::new(2024, 2, 29));
+        context.attach_file(invoice.clone()).unwrap();
+        let result = context.attach_file(invoice);
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+        context.finish().unwrap();
+
+        let document = ExistingDocument::parse(&output).unwrap();
+        let catalog = document
+            .resolve_dictionary(document.trailer.get_reference("Root").unwrap())
+            .unwrap();
+        let associated_files = catalog.get("AF").unwrap().as_array().unwrap();
+        let names = catalog.get("Names").unwrap().as_dictionary().unwrap();
+        let embedded_files = document
+            .resolve_dictionary(names.get_reference("EmbeddedFiles").unwrap())
+            .unwrap();
+        let entries = embedded_files.get("Names").unwrap().as_array().unwrap();
+        assert_eq!(entries[0].0, b"(factur-x.xml)");
+        assert_eq!(
+            entries[1].as_reference(),
+            associated_files[0].as_reference()
+        );
+
+        let file_specification = document
+            .resolve_dictionary(entries[1].as_reference().unwrap())
+            .unwrap();
+        assert_eq!(file_specification.get("Type").unwrap().0, b"/Filespec");
+        assert_eq!(file_specification.get("UF").unwrap().0, b"(factur-x.xml)");
+        assert_eq!(
+            file_specification.get("AFRelationship").unwrap().0,
+            b"/Alternative"
+        );
+        let ef = file_specification
+            .get("EF")
+            .unwrap()
+            .as_dictionary()
+            .unwrap();
+        let (stream, contents) = document
+            .resolve_stream(ef.get_reference("F").unwrap())
+            .unwrap();
+        assert_eq!(contents, b"<rsm:CrossIndustryInvoice/>");
+        assert_eq!(stream.get("Type").unwrap().0, b"/EmbeddedFile");
+        assert_eq!(stream.get("Subtype").unwrap().0, b"/text#2Fxml");
+        let params = stream.get("Params").unwrap().as_dictionary().unwrap();
+        assert_eq!(params.get_integer("Size"), Some(27));
+        assert_eq!(params.get("ModDate").unwrap().0, b"(D:20240229000000Z)");
+
+        let mut context = DocumentContext::with_writer(Vec::new(), Version::Pdf1_7).unwrap();
+        context
+            .set_pdfa_conformance(PdfAConformance::PdfA2b)
+            .unwrap();
+        let mut data = Attachment::new("data.csv", &b"a,b\n1,2\n"[..]);
+        data.mime_type = Some("text/csv".to_string());
+        data.mod_date = Some(PdfDate::new(2024, 2, 29));
+        let result = context.attach_file(data);
+        assert!(matches!(result, Err(Error::Conformance { .. })));
+    }
+}
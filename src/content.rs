@@ -12,49 +12,178 @@
 //    See the License for the specific language governing permissions and
 //    limitations under the License.
 
-use std::collections::HashMap;
-use std::io::{Result, Write};
+use std::collections::BTreeMap;
+use std::io::Write;
 
 use lemon_pdf_derive::PdfFormat;
 use crate as lemon_pdf;
 
 use crate::font::Font;
+use crate::optional_content::OptionalContentGroup;
 use crate::object::{Formatter, IndirectReference, PdfFormat, WriteEscaped};
-use crate::pagetree::{Page, ResourceDictionary};
+use crate::pagetree::Page;
+use crate::pdfua;
 use crate::stream::StreamEncoder;
+use crate::structure_tree::StandardStructureType;
+use crate::Version;
 use crate::DocumentContext;
+use crate::{Error, Result};
 
-#[derive(Debug, Default, Copy, Clone, PartialEq, PdfFormat)]
+/// A length in PostScript points (1/72 inch), the unit of the default user space.
+///
+/// Other units like `units::Mm` convert into points with `Pt::from`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd, PdfFormat)]
 pub struct Pt(pub f64);
 
+impl std::ops::Add for Pt {
+    type Output = Pt;
+
+    fn add(self, other: Pt) -> Pt {
+        Pt(self.0 + other.0)
+    }
+}
+
+impl std::ops::Sub for Pt {
+    type Output = Pt;
+
+    fn sub(self, other: Pt) -> Pt {
+        Pt(self.0 - other.0)
+    }
+}
+
+impl std::ops::Neg for Pt {
+    type Output = Pt;
+
+    fn neg(self) -> Pt {
+        Pt(-self.0)
+    }
+}
+
+impl std::ops::Mul<f64> for Pt {
+    type Output = Pt;
+
+    fn mul(self, factor: f64) -> Pt {
+        Pt(self.0 * factor)
+    }
+}
+
+impl std::ops::Mul<Pt> for f64 {
+    type Output = Pt;
+
+    fn mul(self, length: Pt) -> Pt {
+        Pt(self * length.0)
+    }
+}
+
+impl std::ops::Div<f64> for Pt {
+    type Output = Pt;
+
+    fn div(self, divisor: f64) -> Pt {
+        Pt(self.0 / divisor)
+    }
+}
+
+/// The ratio of two lengths.
+impl std::ops::Div for Pt {
+    type Output = f64;
+
+    fn div(self, other: Pt) -> f64 {
+        self.0 / other.0
+    }
+}
+
+impl std::ops::AddAssign for Pt {
+    fn add_assign(&mut self, other: Pt) {
+        self.0 += other.0
+    }
+}
+
+impl std::ops::SubAssign for Pt {
+    fn sub_assign(&mut self, other: Pt) {
+        self.0 -= other.0
+    }
+}
+
+impl std::iter::Sum for Pt {
+    fn sum<I: Iterator<Item = Pt>>(iter: I) -> Pt {
+        Pt(iter.map(|length| length.0).sum())
+    }
+}
+
+/// A graphics state parameter dictionary.
+#[derive(Debug, Clone, PartialEq, PdfFormat)]
+#[rename("ExtGState")]
+pub struct ExtGState {
+    /// The constant alpha value for stroking operations.
+    #[rename("CA")]
+    #[skip_if("Option::is_none")]
+    pub stroke_alpha: Option<f64>,
+    /// The constant alpha value for all other painting operations.
+    #[rename("ca")]
+    #[skip_if("Option::is_none")]
+    pub fill_alpha: Option<f64>,
+}
+
 #[derive(Debug)]
 pub struct PageContext<'page, 'context, 'context_borrow> {
-    pub(crate) fonts: HashMap<IndirectReference<Font>, String>,
+    /// The resources of the page by name, including those of its previous content streams.
+    /// The shared resources of the document are only included if the page has resources of its
+    /// own.
+    pub(crate) fonts: BTreeMap<String, IndirectReference<Font>>,
+    pub(crate) ext_g_states: BTreeMap<String, ExtGState>,
+    pub(crate) optional_content_groups: BTreeMap<String, IndirectReference<OptionalContentGroup>>,
     pub(crate) content_stream: StreamEncoder,
+    /// For each marked-content sequence that has been begun but not ended, whether it is
+    /// tagged content or an artifact.
+    pub(crate) marked_content: Vec<bool>,
+    /// The marked-content identifier of the next tagged content. The identifiers are unique
+    /// within the page, so they continue from the previous content streams of the page.
+    pub(crate) next_mcid: i64,
     pub(crate) page: &'page mut Page,
     pub pdf_context: &'context_borrow mut DocumentContext<'context>,
 }
 
+/// Return the name of `resource` in the `shared` resources or the page resources `local`.
+/// A new resource is added to `local` with a name that is used by neither.
+fn resource_name<T: PartialEq>(
+    prefix: &str,
+    shared: &BTreeMap<String, T>,
+    local: &mut BTreeMap<String, T>,
+    resource: T,
+) -> String {
+    let existing = shared.iter().chain(local.iter()).find(|&(_, r)| *r == resource);
+    if let Some((name, _)) = existing {
+        return name.clone();
+    }
+    let name = (local.len()..)
+        .map(|index| format!("{}{}", prefix, index))
+        .find(|name| !shared.contains_key(name) && !local.contains_key(name))
+        .unwrap();
+    local.insert(name.clone(), resource);
+    name
+}
+
 impl<'page, 'context, 'context_borrow> PageContext<'page, 'context, 'context_borrow> {
+    /// Return the resource name of `font`, which is the name in the
+    /// `DocumentContext::shared_resources` if the font is shared.
     pub fn add_font(&mut self, font: IndirectReference<Font>) -> String {
-        let num_fonts = self.fonts.len();
-        let key = self
-            .fonts
-            .entry(font)
-            .or_insert_with(|| format!("F{}", num_fonts));
-        key.clone()
+        let shared = &self.pdf_context.shared_resources.font;
+        resource_name("F", shared, &mut self.fonts, font)
     }
 
     pub fn push_operand(&mut self, operand: impl PdfFormat) -> Result<()> {
-        let mut formatter = Formatter {
-            writer: &mut self.content_stream,
-        };
+        let mut formatter = Formatter::new(&mut self.content_stream);
         operand.write(&mut formatter)?;
-        write!(self.content_stream, " ")
+        write!(self.content_stream, " ")?;
+        Ok(())
     }
 
     pub fn apply_operator(&mut self, operator: &str) -> Result<()> {
-        write!(self.content_stream, "{} ", operator)
+        if self.pdf_context.pdfua_conformance().is_some() {
+            pdfua::check_operator(operator, self.marked_content.contains(&true))?;
+        }
+        write!(self.content_stream, "{} ", operator)?;
+        Ok(())
     }
 
     pub fn write_operation1(&mut self, arg1: impl PdfFormat, operator: &str) -> Result<()> {
@@ -127,6 +256,20 @@ impl<'page, 'context, 'context_borrow> PageContext<'page, 'context, 'context_bor
         self.apply_operator("cm")
     }
 
+    /// Set the opacity of filling and stroking operations, between 0.0 (transparent) and 1.0
+    /// (opaque). Requires PDF 1.4.
+    pub fn set_opacity(&mut self, fill: f64, stroke: f64) -> Result<()> {
+        self.pdf_context
+            .require_version(Version::Pdf1_4, "transparency")?;
+        let ext_g_state = ExtGState {
+            stroke_alpha: Some(stroke),
+            fill_alpha: Some(fill),
+        };
+        let shared = &self.pdf_context.shared_resources.ext_g_state;
+        let name = resource_name("GS", shared, &mut self.ext_g_states, ext_g_state);
+        self.write_operation1(name, "gs")
+    }
+
     pub fn line_width(&mut self, width: Pt) -> Result<()> {
         self.write_operation1(width, "w")
     }
@@ -188,6 +331,9 @@ impl<'page, 'context, 'context_borrow> PageContext<'page, 'context, 'context_bor
     }
 
     pub fn draw_cid_glyphs(&mut self, glyphs: impl IntoIterator<Item = u16>) -> Result<()> {
+        if self.pdf_context.pdfua_conformance().is_some() {
+            pdfua::check_operator("Tj", self.marked_content.contains(&true))?;
+        }
         write!(self.content_stream, "<")?;
         for glyph in glyphs {
             self.content_stream.write_hex_escaped(&glyph.to_be_bytes())?;
@@ -196,19 +342,84 @@ impl<'page, 'context, 'context_borrow> PageContext<'page, 'context, 'context_bor
         Ok(())
     }
 
+    /// Begin content that belongs to a structure element of the type `tag`. Returns the
+    /// marked-content identifier, with which the content is added to the element with
+    /// `StructureElement::add_marked_content`.
+    pub fn begin_tagged_content(&mut self, tag: StandardStructureType) -> Result<i64> {
+        let mcid = self.next_mcid;
+        self.push_operand(tag)?;
+        write!(self.content_stream, "<</MCID {}>> ", mcid)?;
+        self.apply_operator("BDC")?;
+        self.marked_content.push(true);
+        self.next_mcid += 1;
+        Ok(mcid)
+    }
+
+    /// Begin content that is not part of the logical structure, like page numbers or
+    /// decorations.
+    pub fn begin_artifact(&mut self) -> Result<()> {
+        self.write_operation1("Artifact", "BMC")?;
+        self.marked_content.push(true);
+        Ok(())
+    }
+
+    /// Begin content that is only visible if `group` is visible. The group is added to the
+    /// `/Properties` of the page resources.
+    pub fn begin_optional_content(
+        &mut self,
+        group: IndirectReference<OptionalContentGroup>,
+    ) -> Result<()> {
+        let shared = &self.pdf_context.shared_resources.properties;
+        let name = resource_name("OC", shared, &mut self.optional_content_groups, group);
+        self.write_operation2("OC", name, "BDC")?;
+        self.marked_content.push(false);
+        Ok(())
+    }
+
+    /// End the content begun by the last `begin_tagged_content`, `begin_artifact` or
+    /// `begin_optional_content`.
+    pub fn end_marked_content(&mut self) -> Result<()> {
+        if self.marked_content.pop().is_none() {
+            return Err(Error::InvalidInput(
+                "no marked content has been begun".to_string(),
+            ));
+        }
+        self.apply_operator("EMC")
+    }
+
     pub(crate) fn finish(self) -> Result<()> {
-        let mut font_dict = HashMap::new();
-        for (font_ref, font_key) in self.fonts {
-            font_dict.insert(font_key, font_ref);
+        if !self.marked_content.is_empty() {
+            return Err(Error::InvalidInput(
+                "the marked content has not been ended".to_string(),
+            ));
+        }
+        if self.next_mcid > 0 {
+            let key = self
+                .pdf_context
+                .marked_content
+                .set_mcid_count(self.page.struct_parents, self.next_mcid);
+            self.page.struct_parents = Some(key);
+        }
+        let shared = &self.pdf_context.shared_resources;
+        if self.fonts.is_empty()
+            && self.ext_g_states.is_empty()
+            && self.optional_content_groups.is_empty()
+            && !shared.is_empty()
+        {
+            // the page inherits the shared resources from the page tree
+            self.page.resources = None;
+        } else {
+            // resources of the page replace the inherited ones instead of extending them
+            let mut resources = shared.clone();
+            resources.font.extend(self.fonts);
+            resources.ext_g_state.extend(self.ext_g_states);
+            resources.properties.extend(self.optional_content_groups);
+            self.page.resources = Some(resources);
         }
-        let resources = ResourceDictionary { font: font_dict };
-        self.page.resources = Some(resources);
 
-        let content_stream = self.content_stream.into_stream();
+        let content_stream = self.content_stream.into_stream()?;
         let content_stream_ref = self.pdf_context.write_object(content_stream)?;
-        let mut array = Vec::new();
-        array.push(content_stream_ref);
-        self.page.contents = array;
+        self.page.contents.push(content_stream_ref);
 
         Ok(())
     }
@@ -12,39 +12,327 @@
 //    See the License for the specific language governing permissions and
 //    limitations under the License.
 
-use std::io::{Result, Write};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::Write;
 
-use crate::object::{Formatter, PdfFormat};
+use crate::object::{Formatter, PdfFormat, RawIndirectReference};
+use crate::{Error, Result};
 
-#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+/// An entry of the cross-reference table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum CrossRefEntry {
+    /// A free object. Only used for the head of the free list, object number 0.
+    Free { next: u32, generation: u32 },
+    /// An uncompressed object at the given byte offset.
+    InUse { offset: u64, generation: u32 },
+    /// An object that is stored at position `index` in the object stream with object number
+    /// `stream`.
+    Compressed { stream: u32, index: u32 },
+}
+
+impl CrossRefEntry {
+    /// The three fields of the entry as stored in a cross-reference stream.
+    fn fields(self) -> (u8, u64, u64) {
+        match self {
+            CrossRefEntry::Free { next, generation } => {
+                (0, u64::from(next), u64::from(generation))
+            }
+            CrossRefEntry::InUse { offset, generation } => (1, offset, u64::from(generation)),
+            CrossRefEntry::Compressed { stream, index } => {
+                (2, u64::from(stream), u64::from(index))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct CrossRef {
-    entries: Vec<(u64, u32)>,
+    /// The object number of the first entry in `entries`.
+    first_number: u32,
+    /// The highest object number.
+    last_number: u32,
+    entries: Vec<CrossRefEntry>,
+    /// Entries for objects of a previous revision that are replaced in an incremental update.
+    updated: BTreeMap<u32, CrossRefEntry>,
+}
+
+impl Default for CrossRef {
+    fn default() -> Self {
+        CrossRef::starting_at(1)
+    }
 }
 
 impl CrossRef {
-    /// Add a `PdfObject` to the `CrossRef` and get its object number.
-    pub fn add_entry(&mut self, offset: u64, generation: u32) -> u32 {
-        self.entries.push((offset, generation));
-        self.len()
+    /// Create a cross-reference section whose new objects are numbered starting at
+    /// `first_number`. Lower object numbers belong to a previous revision of the file.
+    pub fn starting_at(first_number: u32) -> Self {
+        // object number 0 is always free
+        let first_number = first_number.max(1);
+        CrossRef {
+            first_number,
+            last_number: first_number - 1,
+            entries: Vec::new(),
+            updated: BTreeMap::new(),
+        }
+    }
+
+    /// Add a `PdfObject` to the `CrossRef` and get its object number. Fails if the object
+    /// numbers are exhausted.
+    pub fn add_entry(&mut self, offset: u64, generation: u32) -> Result<u32> {
+        let num = self
+            .last_number
+            .checked_add(1)
+            .ok_or_else(|| Error::InvalidInput("too many objects".to_string()))?;
+        self.entries
+            .push(CrossRefEntry::InUse { offset, generation });
+        self.last_number = num;
+        Ok(num)
+    }
+
+    /// Returns `true` if object `num` belongs to a previous revision or has been added.
+    pub fn contains(&self, num: u32) -> bool {
+        num > 0 && num <= self.last_number
     }
 
-    pub fn get_entry_mut(&mut self, num: u32) -> &mut (u64, u32) {
-        &mut self.entries[(num-1) as usize]
+    fn get_entry_mut(&mut self, num: u32) -> Option<&mut CrossRefEntry> {
+        if num == 0 || num > self.last_number {
+            return None;
+        }
+        if num < self.first_number {
+            Some(self.updated.entry(num).or_insert(CrossRefEntry::InUse {
+                offset: 0,
+                generation: 0,
+            }))
+        } else {
+            let index = usize::try_from(num - self.first_number).ok()?;
+            self.entries.get_mut(index)
+        }
+    }
+
+    pub fn set_offset(&mut self, num: u32, generation: u32, offset: u64) -> Result<()> {
+        let entry = self.get_entry_mut(num).ok_or_else(|| {
+            Error::UnresolvedReference(RawIndirectReference(num.into(), generation.into()))
+        })?;
+        *entry = CrossRefEntry::InUse { offset, generation };
+        Ok(())
+    }
+
+    pub fn set_compressed(&mut self, num: u32, stream: u32, index: u32) -> Result<()> {
+        let entry = self
+            .get_entry_mut(num)
+            .ok_or_else(|| Error::UnresolvedReference(RawIndirectReference(num.into(), 0)))?;
+        *entry = CrossRefEntry::Compressed { stream, index };
+        Ok(())
+    }
+
+    /// Returns `true` if object `num` belongs to a previous revision of the file.
+    pub fn is_previous(&self, num: u32) -> bool {
+        num > 0 && num < self.first_number
+    }
+
+    /// Returns `true` if object `num` belongs to a previous revision or has been written.
+    /// Reserved object numbers that no object has been written for yet return `false`.
+    pub fn is_written(&self, num: u32) -> bool {
+        if num == 0 {
+            return false;
+        }
+        if num < self.first_number {
+            return true;
+        }
+        let index = usize::try_from(num - self.first_number).ok();
+        match index.and_then(|index| self.entries.get(index)) {
+            Some(CrossRefEntry::InUse { offset, .. }) => *offset > 0,
+            Some(CrossRefEntry::Compressed { .. }) => true,
+            _ => false,
+        }
     }
 
+    /// The highest object number.
     pub fn len(&self) -> u32 {
-        self.entries.len() as u32
+        self.last_number
+    }
+
+    /// Returns `true` if any object has been placed inside an object stream. Such a
+    /// cross-reference can only be written as a cross-reference stream.
+    pub fn has_compressed_entries(&self) -> bool {
+        self.entries
+            .iter()
+            .chain(self.updated.values())
+            .any(|entry| matches!(entry, CrossRefEntry::Compressed { .. }))
+    }
+
+    /// Group the entries into subsections of consecutive object numbers.
+    fn sections(&self) -> Vec<(u32, Vec<CrossRefEntry>)> {
+        let free_head = if self.first_number == 1 {
+            Some((
+                0,
+                CrossRefEntry::Free {
+                    next: 0,
+                    generation: 65535,
+                },
+            ))
+        } else {
+            None
+        };
+        let numbered = free_head
+            .into_iter()
+            .chain(self.updated.iter().map(|(&num, &entry)| (num, entry)))
+            .chain(
+                self.entries
+                    .iter()
+                    .enumerate()
+                    .map(|(index, &entry)| (self.first_number + index as u32, entry)),
+            );
+
+        let mut sections: Vec<(u32, Vec<CrossRefEntry>)> = Vec::new();
+        for (num, entry) in numbered {
+            match sections.last_mut() {
+                Some((start, entries)) if *start + entries.len() as u32 == num => {
+                    entries.push(entry)
+                }
+                _ => sections.push((num, vec![entry])),
+            }
+        }
+        sections
+    }
+
+    /// Encode the entries as the binary data of a cross-reference stream.
+    ///
+    /// Returns the field widths (the `/W` entry of the stream dictionary), the subsections
+    /// (the `/Index` entry) and the encoded rows.
+    pub fn stream_data(&self) -> ([usize; 3], Vec<u32>, Vec<u8>) {
+        let sections = self.sections();
+        let rows = sections
+            .iter()
+            .flat_map(|(_, entries)| entries.iter().map(|entry| entry.fields()))
+            .collect::<Vec<_>>();
+        let index = sections
+            .iter()
+            .flat_map(|(start, entries)| vec![*start, entries.len() as u32])
+            .collect();
+
+        let max_field2 = rows.iter().map(|row| row.1).max().unwrap_or(0);
+        let max_field3 = rows.iter().map(|row| row.2).max().unwrap_or(0);
+        let widths = [1, byte_width(max_field2), byte_width(max_field3)];
+
+        let mut data = Vec::with_capacity(rows.len() * widths.iter().sum::<usize>());
+        for (field1, field2, field3) in rows {
+            data.push(field1);
+            data.extend_from_slice(&field2.to_be_bytes()[8 - widths[1]..]);
+            data.extend_from_slice(&field3.to_be_bytes()[8 - widths[2]..]);
+        }
+        (widths, index, data)
     }
 }
 
+/// The number of bytes needed to store `value` in big-endian order.
+fn byte_width(value: u64) -> usize {
+    let bits = 64 - value.leading_zeros() as usize;
+    bits.div_ceil(8).max(1)
+}
+
 impl PdfFormat for CrossRef {
-    fn write(&self, f: &mut Formatter) -> Result<()> {
+    fn write(&self, f: &mut Formatter) -> std::io::Result<()> {
         writeln!(f, "xref")?;
-        writeln!(f, "0 {}", self.entries.len()+1)?;
-        write!(f, "{:0>10} {:0>5} f\r\n", 0, 65535)?;
-        for entry in &self.entries {
-            write!(f, "{:0>10} {:0>5} n\r\n", entry.0, entry.1)?;
+        for (start, entries) in self.sections() {
+            writeln!(f, "{} {}", start, entries.len())?;
+            for entry in entries {
+                match entry {
+                    CrossRefEntry::Free { next, generation } => {
+                        write!(f, "{:0>10} {:0>5} f\r\n", next, generation)?
+                    }
+                    CrossRefEntry::InUse { offset, generation } => {
+                        // the offsets in a table are limited to ten digits
+                        if offset > 9_999_999_999 {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidData,
+                                "offset too large for a cross-reference table",
+                            ));
+                        }
+                        write!(f, "{:0>10} {:0>5} n\r\n", offset, generation)?
+                    }
+                    // Compressed objects cannot be referenced from a classic cross-reference
+                    // table. The context switches to a cross-reference stream in that case, so
+                    // this is never reached for well-formed output.
+                    CrossRefEntry::Compressed { .. } => {
+                        write!(f, "{:0>10} {:0>5} f\r\n", 0, 0)?
+                    }
+                }
+            }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stream_data() {
+        let mut crossref = CrossRef::default();
+        crossref.add_entry(15, 0).unwrap();
+        crossref.add_entry(0, 0).unwrap();
+        crossref.set_compressed(2, 3, 1).unwrap();
+        crossref.add_entry(300, 0).unwrap();
+
+        let (widths, index, data) = crossref.stream_data();
+        assert_eq!(widths, [1, 2, 2]);
+        assert_eq!(index, vec![0, 4]);
+        assert_eq!(
+            data,
+            vec![
+                0, 0, 0, 0xff, 0xff, //
+                1, 0, 15, 0, 0, //
+                2, 0, 3, 0, 1, //
+                1, 1, 44, 0, 0,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_incremental_sections() {
+        let mut crossref = CrossRef::starting_at(10);
+        crossref.add_entry(1000, 0).unwrap();
+        crossref.set_offset(3, 0, 900).unwrap();
+
+        let mut output = Vec::new();
+        crossref
+            .write(&mut Formatter::new(&mut output))
+            .unwrap();
+        assert_eq!(
+            std::str::from_utf8(&output).unwrap(),
+            "xref\n3 1\n0000000900 00000 n\r\n10 1\n0000001000 00000 n\r\n"
+        );
+        assert_eq!(crossref.len(), 10);
+
+        // offsets beyond 4 GiB are kept, but the table only has room for ten digits
+        crossref.set_offset(3, 0, 5_000_000_000).unwrap();
+        let mut output = Vec::new();
+        crossref
+            .write(&mut Formatter::new(&mut output))
+            .unwrap();
+        assert!(std::str::from_utf8(&output)
+            .unwrap()
+            .contains("5000000000 00000 n"));
+        crossref.set_offset(3, 0, 10_000_000_000).unwrap();
+        assert!(crossref.write(&mut Formatter::new(&mut Vec::new())).is_err());
+    }
+
+    #[test]
+    fn test_invalid_numbers() {
+        let mut crossref = CrossRef::starting_at(3);
+        assert_eq!(crossref.add_entry(0, 0).unwrap(), 3);
+        assert!(crossref.set_offset(2, 0, 100).is_ok());
+        assert!(crossref.set_offset(0, 0, 100).is_err());
+        assert!(crossref.set_offset(4, 0, 100).is_err());
+        assert!(crossref.set_compressed(u32::MAX, 5, 0).is_err());
+        assert_eq!(crossref.len(), 3);
+
+        let mut crossref = CrossRef::starting_at(u32::MAX);
+        assert_eq!(crossref.add_entry(0, 0).unwrap(), u32::MAX);
+        assert!(crossref.add_entry(0, 0).is_err());
+        assert_eq!(crossref.len(), u32::MAX);
+    }
+}
@@ -0,0 +1,159 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! Dates in the format used by PDF, `D:YYYYMMDDHHmmSSOHH'mm'`, and by XMP.
+
+use std::fmt;
+use std::io::Result;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::object::{Formatter, PdfFormat};
+
+/// A date and time, written as a PDF date string.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PdfDate {
+    pub year: u16,
+    /// The month from 1 to 12.
+    pub month: u8,
+    /// The day of the month from 1 to 31.
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// The offset of the local time from UTC in minutes. If `None`, the relation of the time
+    /// to UTC is unknown.
+    pub utc_offset: Option<i16>,
+}
+
+impl PdfDate {
+    /// Midnight of the given day, in UTC.
+    pub fn new(year: u16, month: u8, day: u8) -> Self {
+        PdfDate {
+            year,
+            month,
+            day,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            utc_offset: Some(0),
+        }
+    }
+
+    /// The UTC date of `seconds` since the Unix epoch.
+    pub fn from_unix_timestamp(seconds: i64) -> Self {
+        let days = seconds.div_euclid(86400);
+        let seconds_of_day = seconds.rem_euclid(86400);
+        // the civil date of the day count, see Howard Hinnant's `civil_from_days`
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        PdfDate {
+            year: year.clamp(0, 9999) as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (seconds_of_day / 3600) as u8,
+            minute: (seconds_of_day / 60 % 60) as u8,
+            second: (seconds_of_day % 60) as u8,
+            utc_offset: Some(0),
+        }
+    }
+
+    /// The date in ISO 8601 format as used by XMP, e.g. `2024-02-29T12:34:56+01:00`.
+    pub fn to_iso_8601(&self) -> String {
+        let mut string = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        );
+        match self.utc_offset {
+            None => {}
+            Some(0) => string.push('Z'),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.unsigned_abs();
+                string.push_str(&format!("{}{:02}:{:02}", sign, offset / 60, offset % 60));
+            }
+        }
+        string
+    }
+
+    /// The current time in UTC.
+    pub fn now() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs() as i64);
+        PdfDate::from_unix_timestamp(seconds)
+    }
+}
+
+impl fmt::Display for PdfDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "D:{:04}{:02}{:02}{:02}{:02}{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )?;
+        match self.utc_offset {
+            None => Ok(()),
+            Some(0) => write!(f, "Z"),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.unsigned_abs();
+                write!(f, "{}{:02}'{:02}'", sign, offset / 60, offset % 60)
+            }
+        }
+    }
+}
+
+impl PdfFormat for PdfDate {
+    fn write(&self, f: &mut Formatter) -> Result<()> {
+        self.to_string().into_bytes().write(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_date() {
+        assert_eq!(
+            PdfDate::from_unix_timestamp(0).to_string(),
+            "D:19700101000000Z"
+        );
+        let date = PdfDate::from_unix_timestamp(1_709_210_096);
+        assert_eq!(date.to_string(), "D:20240229123456Z");
+        let date = PdfDate {
+            utc_offset: Some(-(5 * 60 + 30)),
+            ..date
+        };
+        assert_eq!(date.to_string(), "D:20240229123456-05'30'");
+        assert_eq!(date.to_iso_8601(), "2024-02-29T12:34:56-05:30");
+        let date = PdfDate {
+            utc_offset: None,
+            ..PdfDate::new(1999, 12, 31)
+        };
+        assert_eq!(date.to_string(), "D:19991231000000");
+    }
+}
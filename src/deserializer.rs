@@ -11,12 +11,6 @@ use nom::sequence::{pair, tuple};
 use nom::IResult;
 use nom::InputTakeAtPosition;
 
-#[derive(Copy, Clone, Debug)]
-enum IndirectReferenceDe {
-    Both(i64, i64),
-    One(i64),
-}
-
 #[derive(Debug)]
 enum Token<'bytes> {
     Name(&'bytes [u8]),
@@ -25,18 +19,10 @@ enum Token<'bytes> {
     StartArray,
     EndArray,
     IndirectReference(i64, i64),
-    PartialIndirectReference(i64),
 }
 
 fn is_space(chr: u8) -> bool {
-    match chr {
-        0 | 9 | 10 | 12 | 13 | 32 => true,
-        _ => false,
-    }
-}
-
-fn multispace0(input: &[u8]) -> IResult<&[u8], &[u8]> {
-    input.split_at_position_complete(|item| !is_space(item))
+    matches!(chr, 0 | 9 | 10 | 12 | 13 | 32)
 }
 
 fn multispace1(input: &[u8]) -> IResult<&[u8], &[u8]> {
@@ -45,7 +31,7 @@ fn multispace1(input: &[u8]) -> IResult<&[u8], &[u8]> {
 
 fn int_num(i: &[u8]) -> IResult<&[u8], i64> {
     map_res(recognize(pair(opt(tag(b"-")), digit1)), |s: &[u8]| {
-        std::str::from_utf8(s).unwrap().parse::<i64>()
+        String::from_utf8_lossy(s).parse::<i64>()
     })(i)
 }
 
@@ -82,26 +68,26 @@ impl<'bytes> PdfDeserializer<'bytes> {
     }
 
     fn take_ws(&mut self) -> &'bytes [u8] {
-        let mut result: &[u8] = &[];
-        for sublice in self.input.split(|&chr| !chr.is_ascii_whitespace()) {
-            result = sublice;
-            break;
-        }
+        let result = self
+            .input
+            .split(|&chr| !chr.is_ascii_whitespace())
+            .next()
+            .unwrap_or(&[]);
         self.take(result.len());
         result
     }
 
     fn take_until_ws(&mut self) -> &'bytes [u8] {
-        let mut result: &[u8] = &[];
-        for sublice in self.input.split(|&chr| chr.is_ascii_whitespace()) {
-            result = sublice;
-            break;
-        }
+        let result = self
+            .input
+            .split(|&chr| chr.is_ascii_whitespace())
+            .next()
+            .unwrap_or(&[]);
         self.take(result.len());
         result
     }
 
-    fn next_token(&mut self) -> Result<Token, Error> {
+    fn next_token(&mut self) -> Result<Token<'bytes>, Error> {
         self.take_ws();
 
         if self.input.starts_with(b"/") {
@@ -123,7 +109,7 @@ impl<'bytes> PdfDeserializer<'bytes> {
             self.input = input;
             Ok(Token::IndirectReference(num, gen))
         } else {
-            panic!()
+            Err(Error::Message("unexpected token".to_string()))
         }
     }
 }
@@ -143,14 +129,14 @@ impl<'de> Deserializer<'de> for &mut PdfDeserializer<'de> {
                 self.state = Some(State::None);
                 visitor.visit_i64(gen)
             }
-            Some(State::None) => visitor.visit_str(&"R"),
+            Some(State::None) => visitor.visit_str("R"),
             None => match self.next_token()? {
                 Token::Name(text) => visitor.visit_str(std::str::from_utf8(text)?),
                 Token::IndirectReference(num, gen) => {
                     self.state = Some(State::Both(num, gen));
                     visitor.visit_seq(self)
                 }
-                _ => todo!(),
+                token => Err(Error::Message(format!("unexpected token {:?}", token))),
             },
         }
     }
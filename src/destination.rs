@@ -0,0 +1,148 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! Destinations, which specify a page and how it is displayed.
+
+use std::io::Result;
+
+use crate::object::{Formatter, IndirectReference, PdfFormat};
+use crate::{Page, Pt};
+
+/// A page of the document and the view of it that is displayed when jumping to the
+/// destination. Coordinates that are `None` are left unchanged by the viewer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Destination {
+    /// Display the page with the coordinates `left` and `top` at the upper-left corner of the
+    /// window, magnified by `zoom`.
+    Xyz {
+        page: IndirectReference<Page>,
+        left: Option<Pt>,
+        top: Option<Pt>,
+        zoom: Option<f64>,
+    },
+    /// Fit the entire page into the window.
+    Fit(IndirectReference<Page>),
+    /// Fit the width of the page into the window, with `top` at the top edge of the window.
+    FitH {
+        page: IndirectReference<Page>,
+        top: Option<Pt>,
+    },
+    /// Fit the height of the page into the window, with `left` at the left edge of the
+    /// window.
+    FitV {
+        page: IndirectReference<Page>,
+        left: Option<Pt>,
+    },
+    /// Fit the given rectangle into the window.
+    FitR {
+        page: IndirectReference<Page>,
+        left: Pt,
+        bottom: Pt,
+        right: Pt,
+        top: Pt,
+    },
+    /// Fit the bounding box of the page contents into the window.
+    FitB(IndirectReference<Page>),
+    /// Fit the width of the bounding box into the window, with `top` at the top edge of the
+    /// window.
+    FitBH {
+        page: IndirectReference<Page>,
+        top: Option<Pt>,
+    },
+    /// Fit the height of the bounding box into the window, with `left` at the left edge of the
+    /// window.
+    FitBV {
+        page: IndirectReference<Page>,
+        left: Option<Pt>,
+    },
+    /// A destination of the document's named destinations, see
+    /// `DocumentContext::named_destinations`.
+    Named(Vec<u8>),
+}
+
+impl Destination {
+    /// The page the destination points at. `None` for named destinations.
+    pub fn page(&self) -> Option<IndirectReference<Page>> {
+        let page = match *self {
+            Destination::Xyz { page, .. }
+            | Destination::Fit(page)
+            | Destination::FitH { page, .. }
+            | Destination::FitV { page, .. }
+            | Destination::FitR { page, .. }
+            | Destination::FitB(page)
+            | Destination::FitBH { page, .. }
+            | Destination::FitBV { page, .. } => page,
+            Destination::Named(_) => return None,
+        };
+        Some(page)
+    }
+}
+
+impl PdfFormat for Destination {
+    fn write(&self, f: &mut Formatter) -> Result<()> {
+        if let Destination::Named(name) = self {
+            return name.write(f);
+        }
+        let array = f.format_array().value(&self.page());
+        match self {
+            Destination::Xyz {
+                left, top, zoom, ..
+            } => array.value(&"XYZ").value(left).value(top).value(zoom),
+            Destination::Fit(_) => array.value(&"Fit"),
+            Destination::FitH { top, .. } => array.value(&"FitH").value(top),
+            Destination::FitV { left, .. } => array.value(&"FitV").value(left),
+            Destination::FitR {
+                left,
+                bottom,
+                right,
+                top,
+                ..
+            } => array
+                .value(&"FitR")
+                .value(left)
+                .value(bottom)
+                .value(right)
+                .value(top),
+            Destination::FitB(_) => array.value(&"FitB"),
+            Destination::FitBH { top, .. } => array.value(&"FitBH").value(top),
+            Destination::FitBV { left, .. } => array.value(&"FitBV").value(left),
+            Destination::Named(_) => array,
+        }
+        .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_destination() {
+        let page = IndirectReference::new(3, 0);
+        let format = |destination: Destination| {
+            let mut output = Vec::new();
+            destination.write(&mut Formatter::new(&mut output)).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(format(Destination::Fit(page)), "[ 3 0 R /Fit ]");
+        let xyz = Destination::Xyz {
+            page,
+            left: None,
+            top: Some(Pt(700.0)),
+            zoom: Some(1.5),
+        };
+        assert_eq!(format(xyz), "[ 3 0 R /XYZ null 700.00 1.50 ]");
+        assert_eq!(format(Destination::Named(b"toc".to_vec())), "(toc)");
+    }
+}
@@ -12,28 +12,40 @@
 //    See the License for the specific language governing permissions and
 //    limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::hash::Hash;
 use std::io::Result;
 use std::ops::Deref;
 
 use crate::object::{Formatter, PdfFormat};
 
-pub type Dictionary = HashMap<String, Box<dyn PdfFormat>>;
+/// A dictionary whose entries are written sorted by key.
+pub type Dictionary = BTreeMap<String, Box<dyn PdfFormat>>;
 
-trait Helper {
-    fn as_trait(&self) -> &dyn PdfFormat;
-}
-
-impl<T: PdfFormat + Sized> Helper for T {
-    fn as_trait(&self) -> &dyn PdfFormat {
-        self as &dyn PdfFormat
+impl<T, U> PdfFormat for BTreeMap<T, U>
+where
+    T: PdfFormat + Ord,
+    U: PdfFormat,
+{
+    fn write(&self, f: &mut Formatter) -> Result<()> {
+        let mut dict_fmt = f.format_dictionary();
+        for (key, value) in self.iter() {
+            dict_fmt = dict_fmt.key_value(key, value);
+        }
+        dict_fmt.finish()
     }
 }
 
-impl Helper for &dyn PdfFormat {
-    fn as_trait(&self) -> &dyn PdfFormat {
-        *self
+impl<T> PdfFormat for BTreeMap<T, Box<dyn PdfFormat>>
+where
+    T: PdfFormat + Ord,
+{
+    fn write(&self, f: &mut Formatter) -> Result<()> {
+        let mut dict_fmt = f.format_dictionary();
+        for (key, value) in self.iter() {
+            dict_fmt = dict_fmt.key_value(key, value.deref());
+        }
+        dict_fmt.finish()
     }
 }
 
@@ -12,41 +12,178 @@
 //    See the License for the specific language governing permissions and
 //    limitations under the License.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::io::prelude::*;
-use std::io::Error;
-
-use serde::Serialize;
+use std::panic::Location;
 
+use crypto::digest::Digest;
+use crypto::md5::Md5;
+use crypto::sha2::Sha256;
 use lemon_pdf_derive::PdfFormat;
 use crate as lemon_pdf;
 
+use crate::attachment::{Attachment, FileSpecification};
 use crate::crossref::CrossRef;
-use crate::object::{Formatter, IndirectReference, PdfFormat, RawIndirectReference, Object};
-use crate::pagetree::{Page, Pages};
+use crate::destination::Destination;
+use crate::error::{DanglingReference, Error};
+use crate::font::Font;
+use crate::info::DocumentInfo;
+use crate::encryption::{Encryption, EncryptionMethod, ObjectCipher, SecurityHandler};
+use crate::name_tree::{NameDictionary, NameTree};
+use crate::number_tree::{NumberTree, NumberTreeNode};
+use crate::object::{
+    text_string, Formatter, IndirectReference, PdfFormat, RawIndirectReference, Object,
+};
+use crate::object_stream::ObjectStreamBuilder;
+use crate::optional_content::{
+    OptionalContentConfiguration, OptionalContentGroup, OptionalContentProperties, OrderEntry,
+    OrderItem,
+};
+use crate::outline::{write_outline, OutlineItem, Outlines};
+use crate::page_label::PageLabel;
+use crate::pagetree::{Page, PageTreeWriter, Pages, ResourceDictionary};
+use crate::pdfa::{self, OutputIntent, PdfAConformance};
+use crate::pdfua::{self, PdfUAConformance, StructureChecker};
+use crate::reader::{ExistingDocument, RawDictionary, RawObject};
 use crate::serializer::PdfSerializer;
-use crate::{structure_tree::StructTreeRoot, trailer::Trailer};
-use log::error;
-
-pub type DocumentInfo = HashMap<String, Vec<u8>>;
+use crate::signature::{
+    AcroForm, Signature, SignatureDictionary, SignatureField, SignaturePlaceholder,
+};
+use crate::stream::Stream;
+use crate::structure_tree::{
+    write_structure_tree, MarkInfo, MarkedContentPages, StructTreeRoot, StructureElement,
+};
+use crate::trailer::{get_digest, Trailer};
+use crate::viewer::{self, GoToAction, PageLayout, PageMode, ViewerPreferences};
+use crate::xmp::XmpMetadata;
+use log::warn;
 
-#[derive(Debug, Copy, Clone)]
+/// The version of the PDF specification a document conforms to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Version {
+    Pdf1_3,
+    Pdf1_4,
+    Pdf1_5,
+    Pdf1_6,
     Pdf1_7,
+    Pdf2_0,
+}
+
+/// Determines what happens if a feature requires a newer PDF version than the one the
+/// document was created with.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum VersionPolicy {
+    /// Raise the version of the document using the `/Version` entry of the document catalog.
+    #[default]
+    Upgrade,
+    /// Use the feature anyway and log a warning.
+    Warn,
+    /// Refuse to use the feature and return an error. Features that are deprecated in the
+    /// document's version are refused as well.
+    Strict,
+}
+
+/// Determines how indirect objects and the cross-reference information are laid out in the
+/// file.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum XRefMode {
+    /// Every object is written uncompressed and a classic `xref` table is used.
+    #[default]
+    Table,
+    /// Non-stream objects are packed into compressed object streams (`/Type /ObjStm`) and
+    /// the cross-reference information is written as a cross-reference stream
+    /// (`/Type /XRef`). Requires PDF 1.5 or later.
+    Stream,
+}
+
+/// Determines the file identifier, the `/ID` entry of the trailer.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum FileIdentifier {
+    /// Derived from the current time and the file size, so every run produces a different
+    /// identifier.
+    #[default]
+    Timestamp,
+    /// The MD5 hash of everything written before the cross-reference section. Together with
+    /// the stable order of dictionary entries, writing the same content twice produces
+    /// identical files. Cannot be used with encryption, since the identifier is needed to
+    /// encrypt the content; use `Fixed` instead.
+    ContentHash,
+    /// An identifier supplied by the caller. Encrypted documents are only reproducible with
+    /// `EncryptionMethod::Rc4_128` and `EncryptionMethod::Aes128`: AES-256 draws a random file
+    /// key, salts and initialization vectors for every document.
+    Fixed([u8; 16]),
 }
 
 impl Version {
-    fn header(self) -> &'static str {
+    /// The version number, e.g. `"1.7"`.
+    pub fn number(self) -> &'static str {
         match self {
-            Version::Pdf1_7 => "%PDF-1.7",
+            Version::Pdf1_3 => "1.3",
+            Version::Pdf1_4 => "1.4",
+            Version::Pdf1_5 => "1.5",
+            Version::Pdf1_6 => "1.6",
+            Version::Pdf1_7 => "1.7",
+            Version::Pdf2_0 => "2.0",
         }
     }
+
+    /// Parse a version number like `1.7`.
+    fn from_number(number: &[u8]) -> Option<Self> {
+        [
+            Version::Pdf1_3,
+            Version::Pdf1_4,
+            Version::Pdf1_5,
+            Version::Pdf1_6,
+            Version::Pdf1_7,
+            Version::Pdf2_0,
+        ]
+        .iter()
+        .copied()
+        .find(|version| version.number().as_bytes() == number)
+    }
+
+    /// The version of an existing file, taking the `/Version` entry of its document catalog
+    /// into account.
+    fn of_document(data: &[u8], catalog: &RawDictionary) -> Option<Self> {
+        let header = data
+            .strip_prefix(b"%PDF-")
+            .and_then(|rest| rest.get(..3))
+            .and_then(Version::from_number);
+        let catalog_version = catalog
+            .get("Version")
+            .and_then(|version| version.0.strip_prefix(b"/"))
+            .and_then(Version::from_number);
+        header.max(catalog_version)
+    }
+
+    fn header(self) -> String {
+        format!("%PDF-{}", self.number())
+    }
+}
+
+impl PdfFormat for Version {
+    fn write(&self, f: &mut Formatter) -> std::io::Result<()> {
+        self.number().write(f)
+    }
 }
 
-#[derive(Debug)]
 pub(crate) struct OffsetTrackingWriter<T> {
     offset: u64,
     inner: T,
+    /// If set, the output is collected here instead of being passed to `inner`.
+    buffer: Option<Vec<u8>>,
+    /// The hash of everything written so far.
+    digest: Md5,
+}
+
+impl<T> std::fmt::Debug for OffsetTrackingWriter<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OffsetTrackingWriter")
+            .field("offset", &self.offset)
+            .field("buffer", &self.buffer.as_ref().map(Vec::len))
+            .finish()
+    }
 }
 
 impl<T> OffsetTrackingWriter<T> {
@@ -54,6 +191,18 @@ impl<T> OffsetTrackingWriter<T> {
         OffsetTrackingWriter {
             inner: writer,
             offset: 0,
+            buffer: None,
+            digest: Md5::new(),
+        }
+    }
+
+    /// Create a writer whose output is appended to `offset` existing bytes.
+    pub fn with_offset(writer: T, offset: u64) -> Self {
+        OffsetTrackingWriter {
+            inner: writer,
+            offset,
+            buffer: None,
+            digest: Md5::new(),
         }
     }
 
@@ -61,6 +210,23 @@ impl<T> OffsetTrackingWriter<T> {
         self.offset
     }
 
+    /// The MD5 hash of everything written so far.
+    pub fn content_digest(&self) -> [u8; 16] {
+        let mut digest = [0; 16];
+        self.digest.clone().result(&mut digest);
+        digest
+    }
+
+    /// Hold back all further output until `take_buffer` is called.
+    pub fn start_buffering(&mut self) {
+        self.buffer.get_or_insert_with(Vec::new);
+    }
+
+    /// Return the held back output. Further output is passed to the inner writer again.
+    pub fn take_buffer(&mut self) -> Vec<u8> {
+        self.buffer.take().unwrap_or_default()
+    }
+
     #[allow(unused)]
     pub fn into_inner(self) -> T {
         self.inner
@@ -71,24 +237,36 @@ impl<T> Write for OffsetTrackingWriter<T>
 where
     T: Write,
 {
-    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(buffer) = &mut self.buffer {
+            buffer.extend_from_slice(buf);
+            self.offset += buf.len() as u64;
+            self.digest.input(buf);
+            return Ok(buf.len());
+        }
         match self.inner.write(buf) {
             Ok(size) => {
                 self.offset += size as u64;
+                self.digest.input(&buf[..size]);
                 Ok(size)
             }
             Err(err) => Err(err),
         }
     }
 
-    fn flush(&mut self) -> Result<(), Error> {
+    fn flush(&mut self) -> std::io::Result<()> {
         self.inner.flush()
     }
 
-    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
-        self.inner
-            .write_all(buf)
-            .map(|_| self.offset += buf.len() as u64)
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        if let Some(buffer) = &mut self.buffer {
+            buffer.extend_from_slice(buf);
+        } else {
+            self.inner.write_all(buf)?;
+        }
+        self.offset += buf.len() as u64;
+        self.digest.input(buf);
+        Ok(())
     }
 }
 
@@ -96,68 +274,589 @@ where
 #[rename("Catalog")]
 pub(crate) struct DocumentCatalog {
     pages: Option<IndirectReference<Pages>>,
-    struct_tree_root: Option<Object<StructTreeRoot>>
+    #[skip_if("Option::is_none")]
+    struct_tree_root: Option<Object<StructTreeRoot>>,
+    #[skip_if("Option::is_none")]
+    mark_info: Option<MarkInfo>,
+    #[skip_if("Option::is_none")]
+    lang: Option<Vec<u8>>,
+    #[skip_if("Option::is_none")]
+    acro_form: Option<AcroForm>,
+    #[skip_if("Option::is_none")]
+    names: Option<NameDictionary>,
+    /// The associated files of the document.
+    #[rename("AF")]
+    #[skip_if("Option::is_none")]
+    af: Option<Vec<IndirectReference<FileSpecification>>>,
+    #[skip_if("Option::is_none")]
+    metadata: Option<IndirectReference<Stream>>,
+    #[skip_if("Option::is_none")]
+    output_intents: Option<Vec<OutputIntent>>,
+    #[skip_if("Option::is_none")]
+    page_labels: Option<IndirectReference<NumberTreeNode<PageLabel>>>,
+    #[skip_if("Option::is_none")]
+    page_layout: Option<PageLayout>,
+    #[skip_if("Option::is_none")]
+    page_mode: Option<PageMode>,
+    #[skip_if("Option::is_none")]
+    outlines: Option<IndirectReference<Outlines>>,
+    #[skip_if("Option::is_none")]
+    viewer_preferences: Option<ViewerPreferences>,
+    #[skip_if("Option::is_none")]
+    open_action: Option<GoToAction>,
+    #[rename("OCProperties")]
+    #[skip_if("Option::is_none")]
+    oc_properties: Option<OptionalContentProperties>,
+    /// Set if features of a newer version than the one in the header are used.
+    #[skip_if("Option::is_none")]
+    version: Option<Version>,
+}
+
+/// The parts of a previous revision of the document that are needed to append an incremental
+/// update.
+#[derive(Debug)]
+struct PreviousRevision {
+    /// The offset of the last cross-reference section.
+    startxref: u64,
+    root: RawIndirectReference,
+    catalog: RawDictionary,
+    page_tree_root: RawIndirectReference,
+    page_tree: RawDictionary,
+    info: Option<RawIndirectReference>,
+    id: Option<RawObject>,
+}
+
+impl PreviousRevision {
+    fn read(document: &ExistingDocument) -> Result<Self, Error> {
+        let invalid = |msg: &str| Error::InvalidObjectGraph(msg.to_string());
+        if document.trailer.get("Encrypt").is_some() {
+            return Err(Error::InvalidInput(
+                "cannot update an encrypted document".to_string(),
+            ));
+        }
+        let root = document
+            .trailer
+            .get_reference("Root")
+            .ok_or_else(|| invalid("trailer has no /Root entry"))?;
+        let catalog = document.resolve_dictionary(root)?;
+        let page_tree_root = catalog
+            .get_reference("Pages")
+            .ok_or_else(|| invalid("document catalog has no /Pages entry"))?;
+        let page_tree = document.resolve_dictionary(page_tree_root)?;
+        let id = document
+            .trailer
+            .get("ID")
+            .and_then(RawObject::as_array)
+            .and_then(|id| id.into_iter().next());
+        Ok(PreviousRevision {
+            startxref: document.startxref,
+            root,
+            catalog,
+            page_tree_root,
+            page_tree,
+            info: document.trailer.get_reference("Info"),
+            id,
+        })
+    }
+}
+
+/// Serialize `object`. Returns the bytes and whether the object is a stream.
+fn format_object(
+    object: &impl PdfFormat,
+    cipher: Option<&ObjectCipher>,
+    references: Option<&mut Vec<RawIndirectReference>>,
+) -> Result<(Vec<u8>, bool), Error> {
+    let mut bytes = Vec::new();
+    let mut formatter = Formatter::with_cipher(&mut bytes, cipher);
+    formatter.references = references;
+    object.write(&mut formatter)?;
+    let is_stream = formatter.wrote_stream;
+    Ok((bytes, is_stream))
+}
+
+/// A signature whose dictionary and field have not been written yet.
+struct PendingSignature<'a> {
+    signature: Signature<'a>,
+    dictionary: IndirectReference<SignatureDictionary>,
+    field: IndirectReference<SignatureField>,
 }
 
 pub struct DocumentContext<'a> {
-    pub(crate) output: OffsetTrackingWriter<Box<dyn Write + 'a>>,
+    pub(crate) output: OffsetTrackingWriter<Box<dyn Write + Send + 'a>>,
+    /// The version in the file header.
     pub version: Version,
+    /// What to do when a feature requires a newer version than `version`. Defaults to
+    /// `VersionPolicy::Upgrade`.
+    pub version_policy: VersionPolicy,
+    /// The version required by the features used so far.
+    required_version: Version,
     pub(crate) crossref: CrossRef,
-    document_catalog: Option<DocumentCatalog>,
-    page_tree: Option<Pages>,
+    /// How objects and the cross-reference information are written. Defaults to
+    /// `XRefMode::Table`.
+    pub xref_mode: XRefMode,
+    /// How the file identifier is chosen. Defaults to `FileIdentifier::Timestamp`.
+    pub file_identifier: FileIdentifier,
+    object_stream: ObjectStreamBuilder,
+    document_catalog: DocumentCatalog,
+    page_tree: PageTreeWriter,
     /// If this hash map is not empty, a document information dictionary with the corresponding
     /// entries will be created.
     pub document_info: DocumentInfo,
-    dangling_references: HashSet<RawIndirectReference>,
+    /// If set, an XMP metadata stream with the entries of `document_info` is written.
+    pub xmp_metadata: Option<XmpMetadata>,
+    /// The top-level items of the document outline. No outline is written if this is empty.
+    pub outline: Vec<OutlineItem>,
+    /// Destinations that can be referred to by name with `Destination::Named`, from within
+    /// the document or from other documents.
+    pub named_destinations: NameTree<Destination>,
+    /// The files attached with `attach_file`, by name.
+    embedded_files: NameTree<IndirectReference<FileSpecification>>,
+    /// The page labels by the zero-based index of the first page they apply to. Viewers
+    /// display the page numbers if this is empty.
+    pub page_labels: NumberTree<PageLabel>,
+    /// What is shown next to the document when it is opened. Viewers default to
+    /// `PageMode::UseNone`.
+    pub page_mode: Option<PageMode>,
+    /// How pages are arranged when the document is opened.
+    pub page_layout: Option<PageLayout>,
+    pub viewer_preferences: Option<ViewerPreferences>,
+    /// The destination that is displayed when the document is opened, e.g. a page at a
+    /// certain zoom level.
+    pub open_action: Option<Destination>,
+    /// The natural language of the text of the document, e.g. `en-US`.
+    pub language: Option<String>,
+    /// The top-level elements of the logical structure of the document. If there are any,
+    /// the document is written as tagged PDF. The content of the pages is added to the
+    /// elements with `PageContext::begin_tagged_content`.
+    pub structure_tree: Vec<StructureElement>,
+    pub(crate) marked_content: MarkedContentPages,
+    /// The optional content groups added with `add_optional_content_group` and whether they
+    /// are visible initially.
+    optional_content_groups: Vec<(IndirectReference<OptionalContentGroup>, bool)>,
+    /// How the optional content groups are listed by viewers. If this is empty, they are
+    /// listed without nesting in the order they were added.
+    pub optional_content_order: Vec<OrderItem>,
+    /// Resources that every page inherits from the root of the page tree. They are written
+    /// once instead of with each page, and `PageContext` reuses their keys, so fonts used on
+    /// most pages are best added here with `add_shared_font`. They are written when the first
+    /// page is added, so adding further pages or finishing the document fails if they are
+    /// changed afterwards.
+    pub shared_resources: ResourceDictionary,
+    /// If set, `finish` also fails if an indirect reference written into any object points
+    /// to an object that was never written. Defaults to `false`.
+    pub check_references: bool,
+    /// If set, `write_object` returns the reference of a previously written object instead of
+    /// writing the object again if both serialize to the same bytes. Defaults to `false`.
+    ///
+    /// Pages are never shared. Other objects that must not be shared, like annotations, can
+    /// be written with `write_object_fn`, which does not deduplicate.
+    pub deduplicate_objects: bool,
+    /// The SHA-256 hashes of the objects written with `write_object`, if
+    /// `deduplicate_objects` is set.
+    object_hashes: HashMap<[u8; 32], RawIndirectReference>,
+    /// References whose objects have not been written yet, with the location they were
+    /// created at.
+    dangling_references: BTreeMap<RawIndirectReference, &'static Location<'static>>,
+    /// The objects that contain references to each object, if `check_references` is set.
+    references: BTreeMap<RawIndirectReference, Vec<RawIndirectReference>>,
+    previous_revision: Option<PreviousRevision>,
+    security_handler: Option<SecurityHandler>,
+    pdfa: Option<PdfAConformance>,
+    pdfua: Option<PdfUAConformance>,
+    /// The file identifier, if it has to be known before the trailer is written.
+    file_id: Option<[u8; 16]>,
+    signature: Option<PendingSignature<'a>>,
 }
 
 impl<'a> std::fmt::Debug for DocumentContext<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("DocumentContext")
             .field("version", &self.version)
+            .field("version_policy", &self.version_policy)
+            .field("required_version", &self.required_version)
             .field("crossref", &self.crossref)
+            .field("xref_mode", &self.xref_mode)
+            .field("file_identifier", &self.file_identifier)
             .field("document_catalog", &self.document_catalog)
             .field("page_tree", &self.page_tree)
             .field("document_info", &self.document_info)
+            .field("xmp_metadata", &self.xmp_metadata)
+            .field("outline", &self.outline)
+            .field("named_destinations", &self.named_destinations)
+            .field("embedded_files", &self.embedded_files)
+            .field("page_labels", &self.page_labels)
+            .field("page_mode", &self.page_mode)
+            .field("page_layout", &self.page_layout)
+            .field("viewer_preferences", &self.viewer_preferences)
+            .field("open_action", &self.open_action)
+            .field("language", &self.language)
+            .field("structure_tree", &self.structure_tree)
+            .field("marked_content", &self.marked_content)
+            .field("optional_content_groups", &self.optional_content_groups)
+            .field("optional_content_order", &self.optional_content_order)
+            .field("shared_resources", &self.shared_resources)
+            .field("check_references", &self.check_references)
+            .field("deduplicate_objects", &self.deduplicate_objects)
+            .field("previous_revision", &self.previous_revision)
+            .field("security_handler", &self.security_handler)
+            .field("pdfa", &self.pdfa)
+            .field("pdfua", &self.pdfua)
+            .field("file_id", &self.file_id)
+            .field(
+                "signature",
+                &self.signature.as_ref().map(|pending| &pending.signature),
+            )
             .finish()
     }
 }
 
 impl<'a> DocumentContext<'a> {
-    pub fn with_writer<W: 'a + Write>(writer: W, version: Version) -> Result<Self, Error> {
+    /// Start a new document that is written to `writer`. The writer has to be `Send`, so that
+    /// the context can be moved to another thread, e.g. by an `AsyncDocumentContext` in a
+    /// spawned task.
+    pub fn with_writer<W: 'a + Write + Send>(writer: W, version: Version) -> Result<Self, Error> {
         let mut context = DocumentContext {
             output: OffsetTrackingWriter::new(Box::new(writer)),
             version,
+            version_policy: Default::default(),
+            required_version: version,
             crossref: Default::default(),
-            document_catalog: Some(DocumentCatalog::default()),
-            page_tree: Some(Pages::new()),
+            xref_mode: Default::default(),
+            file_identifier: Default::default(),
+            object_stream: Default::default(),
+            document_catalog: Default::default(),
+            page_tree: Default::default(),
             document_info: Default::default(),
+            xmp_metadata: None,
+            outline: Vec::new(),
+            named_destinations: NameTree::new(),
+            embedded_files: NameTree::new(),
+            page_labels: NumberTree::new(),
+            page_mode: None,
+            page_layout: None,
+            viewer_preferences: None,
+            open_action: None,
+            language: None,
+            structure_tree: Vec::new(),
+            marked_content: Default::default(),
+            optional_content_groups: Vec::new(),
+            optional_content_order: Vec::new(),
+            shared_resources: Default::default(),
+            check_references: false,
+            deduplicate_objects: false,
+            object_hashes: Default::default(),
             dangling_references: Default::default(),
+            references: Default::default(),
+            previous_revision: None,
+            security_handler: None,
+            pdfa: None,
+            pdfua: None,
+            file_id: None,
+            signature: None,
         };
         context.start_pdf()?;
         Ok(context)
     }
 
+    /// Start an incremental update of the existing PDF file `existing`.
+    ///
+    /// Everything written to `writer` is meant to be appended to the end of `existing`, e.g. by
+    /// opening the file in append mode. The previous revision is left untouched: new objects,
+    /// a new cross-reference section and a trailer pointing to the previous section via `/Prev`
+    /// are added. Pages added with `add_page` are appended to the existing page tree.
+    pub fn incremental_update<W: 'a + Write + Send>(
+        existing: &[u8],
+        writer: W,
+    ) -> Result<Self, Error> {
+        let document = ExistingDocument::parse(existing)?;
+        let previous_revision = PreviousRevision::read(&document)?;
+        let version = Version::of_document(existing, &previous_revision.catalog)
+            .unwrap_or(Version::Pdf1_7);
+        let xref_mode = if document.uses_xref_stream {
+            XRefMode::Stream
+        } else {
+            XRefMode::Table
+        };
+
+        let page_tree_root = IndirectReference::new(
+            previous_revision.page_tree_root.0,
+            previous_revision.page_tree_root.1,
+        );
+        let mut context = DocumentContext {
+            output: OffsetTrackingWriter::with_offset(Box::new(writer), existing.len() as u64),
+            version,
+            version_policy: Default::default(),
+            required_version: version,
+            crossref: CrossRef::starting_at(document.size()?),
+            xref_mode,
+            file_identifier: Default::default(),
+            object_stream: Default::default(),
+            document_catalog: Default::default(),
+            page_tree: PageTreeWriter::with_parent(page_tree_root),
+            document_info: Default::default(),
+            xmp_metadata: None,
+            outline: Vec::new(),
+            named_destinations: NameTree::new(),
+            embedded_files: NameTree::new(),
+            page_labels: NumberTree::new(),
+            page_mode: None,
+            page_layout: None,
+            viewer_preferences: None,
+            open_action: None,
+            language: None,
+            structure_tree: Vec::new(),
+            marked_content: Default::default(),
+            optional_content_groups: Vec::new(),
+            optional_content_order: Vec::new(),
+            shared_resources: Default::default(),
+            check_references: false,
+            deduplicate_objects: false,
+            object_hashes: Default::default(),
+            dangling_references: Default::default(),
+            references: Default::default(),
+            previous_revision: Some(previous_revision),
+            security_handler: None,
+            pdfa: None,
+            pdfua: None,
+            file_id: None,
+            signature: None,
+        };
+        if !existing.ends_with(b"\n") && !existing.ends_with(b"\r") {
+            writeln!(context.output)?;
+        }
+        Ok(context)
+    }
+
+    /// The version of the document, including upgrades required by the features used so far.
+    pub fn effective_version(&self) -> Version {
+        self.version.max(self.required_version)
+    }
+
+    /// Check that `feature` is available in the document's version, applying the version
+    /// policy if it is not.
+    pub(crate) fn require_version(&mut self, required: Version, feature: &str) -> Result<(), Error> {
+        if self.effective_version() >= required {
+            return Ok(());
+        }
+        match self.version_policy {
+            VersionPolicy::Upgrade => self.required_version = required,
+            VersionPolicy::Warn => warn!(
+                "{} requires PDF {}, but the document has version {}",
+                feature,
+                required.number(),
+                self.version.number()
+            ),
+            VersionPolicy::Strict => {
+                return Err(Error::InvalidInput(format!(
+                    "{} requires PDF {}, but the document has version {}",
+                    feature,
+                    required.number(),
+                    self.version.number()
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Report the use of `feature`, which is deprecated in PDF 2.0.
+    pub(crate) fn deprecated_in_2_0(&self, feature: &str) -> Result<(), Error> {
+        if self.effective_version() < Version::Pdf2_0 {
+            return Ok(());
+        }
+        if self.version_policy == VersionPolicy::Strict {
+            return Err(Error::InvalidInput(format!(
+                "{} is deprecated in PDF 2.0",
+                feature
+            )));
+        }
+        warn!("{} is deprecated in PDF 2.0", feature);
+        Ok(())
+    }
+
+    /// The file header, followed by a comment with binary characters.
+    fn header_bytes(&self) -> Vec<u8> {
+        let mut header = format!("{}\n", self.version.header()).into_bytes();
+        header.extend_from_slice(b"%\xff\xff\xff\xff\n");
+        header
+    }
+
     fn start_pdf(&mut self) -> Result<(), Error> {
-        writeln!(self.output, "{}", self.version.header())?;
-        self.output.write_all(b"%\xff\xff\xff\xff\n")?;
+        let header = self.header_bytes();
+        self.output.write_all(&header)?;
+        Ok(())
+    }
+
+    /// Claim conformance to PDF/A. Every object written afterwards is checked for features
+    /// that PDF/A forbids, and `finish` adds the sRGB output intent and the XMP metadata that
+    /// identifies the conformance level.
+    ///
+    /// This has to be called before any object is written. Fonts have to be embedded, so
+    /// `BuiltInFont::font` cannot be used, and the document cannot be encrypted.
+    pub fn set_pdfa_conformance(&mut self, conformance: PdfAConformance) -> Result<(), Error> {
+        if self.previous_revision.is_some() {
+            return Err(Error::InvalidInput(
+                "PDF/A conformance cannot be claimed in incremental updates".to_string(),
+            ));
+        }
+        if self.crossref.len() > 0 {
+            return Err(Error::InvalidInput(
+                "PDF/A conformance has to be set up before any object is written".to_string(),
+            ));
+        }
+        if self.security_handler.is_some() {
+            return Err(Error::Conformance {
+                object: None,
+                message: "PDF/A forbids encryption".to_string(),
+            });
+        }
+        pdfa::check_version(self.version)?;
+        self.pdfa = Some(conformance);
         Ok(())
     }
 
+    /// The PDF/A conformance level claimed with `set_pdfa_conformance`.
+    pub fn pdfa_conformance(&self) -> Option<PdfAConformance> {
+        self.pdfa
+    }
+
+    /// Claim conformance to PDF/UA. Content that is painted outside of
+    /// `PageContext::begin_tagged_content` and `PageContext::begin_artifact` is rejected, and
+    /// `finish` checks the `structure_tree` and sets `ViewerPreferences::display_doc_title`.
+    ///
+    /// This has to be called before any object is written. The document needs a `language`
+    /// and a title in `document_info`.
+    pub fn set_pdfua_conformance(&mut self, conformance: PdfUAConformance) -> Result<(), Error> {
+        if self.previous_revision.is_some() {
+            return Err(Error::InvalidInput(
+                "PDF/UA conformance cannot be claimed in incremental updates".to_string(),
+            ));
+        }
+        if self.crossref.len() > 0 {
+            return Err(Error::InvalidInput(
+                "PDF/UA conformance has to be set up before any object is written".to_string(),
+            ));
+        }
+        pdfua::check_version(self.version)?;
+        self.pdfua = Some(conformance);
+        Ok(())
+    }
+
+    /// The PDF/UA conformance level claimed with `set_pdfua_conformance`.
+    pub fn pdfua_conformance(&self) -> Option<PdfUAConformance> {
+        self.pdfua
+    }
+
+    /// Protect the document with a password.
+    ///
+    /// This has to be called before any object is written, since all strings and streams of
+    /// the document are encrypted.
+    pub fn set_encryption(&mut self, encryption: Encryption) -> Result<(), Error> {
+        if self.pdfa.is_some() {
+            return Err(Error::Conformance {
+                object: None,
+                message: "PDF/A forbids encryption".to_string(),
+            });
+        }
+        if self.previous_revision.is_some() {
+            return Err(Error::InvalidInput(
+                "encryption is not supported for incremental updates".to_string(),
+            ));
+        }
+        if self.crossref.len() > 0 {
+            return Err(Error::InvalidInput(
+                "encryption has to be set up before any object is written".to_string(),
+            ));
+        }
+        match encryption.method {
+            EncryptionMethod::Rc4_128 => {
+                self.require_version(Version::Pdf1_4, "RC4 encryption with 128-bit keys")?;
+                self.deprecated_in_2_0("RC4 encryption")?;
+            }
+            EncryptionMethod::Aes128 => {
+                self.require_version(Version::Pdf1_6, "AES encryption")?;
+                self.deprecated_in_2_0("AES encryption with 128-bit keys")?;
+            }
+            EncryptionMethod::Aes256 => {
+                self.require_version(Version::Pdf2_0, "AES encryption with 256-bit keys")?
+            }
+        }
+        let file_id = match self.file_identifier {
+            FileIdentifier::Timestamp => get_digest(self.current_offset()),
+            FileIdentifier::Fixed(file_id) => file_id,
+            FileIdentifier::ContentHash => {
+                return Err(Error::InvalidInput(
+                    "a content derived file identifier cannot be used with encryption".to_string(),
+                ))
+            }
+        };
+        self.security_handler = Some(SecurityHandler::new(&encryption, &file_id));
+        self.file_id = Some(file_id);
+        Ok(())
+    }
+
+    /// Digitally sign the document.
+    ///
+    /// This has to be called before any object is written. The returned signature field should
+    /// be added to the annotations of a page. Since the signature covers the complete file, the
+    /// output is held back in memory until `finish` computes the signature.
+    #[track_caller]
+    pub fn sign(
+        &mut self,
+        signature: Signature<'a>,
+    ) -> Result<IndirectReference<SignatureField>, Error> {
+        if self.previous_revision.is_some() {
+            return Err(Error::InvalidInput(
+                "signing is not supported for incremental updates".to_string(),
+            ));
+        }
+        if self.signature.is_some() {
+            return Err(Error::InvalidInput("the document is already signed".to_string()));
+        }
+        if self.current_offset() != self.header_bytes().len() as u64 {
+            return Err(Error::InvalidInput(
+                "signing has to be set up before any object is written".to_string(),
+            ));
+        }
+        let dictionary = self.reserve_reference()?;
+        let field = self.reserve_reference()?;
+        self.dangling_references
+            .insert(dictionary.raw(), Location::caller());
+        self.dangling_references.insert(field.raw(), Location::caller());
+        self.signature = Some(PendingSignature {
+            signature,
+            dictionary,
+            field,
+        });
+        self.output.start_buffering();
+        Ok(field)
+    }
+
     pub fn write_object1<T: serde::Serialize>(
         &mut self,
         object: T,
     ) -> Result<IndirectReference<T>, Error> {
-        let reference = self.create_reference();
+        let reference = self.create_reference()?;
         self.write_referenced_object(reference, object)?;
         Ok(reference)
     }
 
-    pub fn create_reference<T: serde::Serialize>(&mut self) -> IndirectReference<T> {
-        let num = self.crossref.add_entry(0, 0);
+    /// Reserve an object number for an object that is written later on.
+    pub(crate) fn reserve_reference<T>(&mut self) -> Result<IndirectReference<T>, Error> {
+        let num = self.crossref.add_entry(0, 0)?;
+        Ok(IndirectReference::new(i64::from(num), 0))
+    }
+
+    /// Reserve an object number for an object that is written later on with
+    /// `write_referenced_object`. `finish` fails if the object is never written.
+    #[track_caller]
+    pub fn create_reference<T: serde::Serialize>(
+        &mut self,
+    ) -> Result<IndirectReference<T>, Error> {
+        let num = self.crossref.add_entry(0, 0)?;
         let reference = IndirectReference::new(i64::from(num), 0);
-        self.dangling_references.insert(reference.raw());
-        reference
+        self.dangling_references
+            .insert(reference.raw(), Location::caller());
+        Ok(reference)
     }
 
     pub fn write_referenced_object<T: serde::Serialize>(
@@ -165,122 +864,872 @@ impl<'a> DocumentContext<'a> {
         reference: IndirectReference<T>,
         object: T,
     ) -> Result<(), Error> {
-        self.dangling_references.remove(&reference.raw());
-        // update placeholder entry in crossref
-        self.crossref.get_entry_mut(reference.number() as u32).0 = self.output.offset();
-
-        let mut serializer = PdfSerializer {
-            output: &mut self.output,
+        let reference = reference.raw();
+        let serialize = |cipher: Option<ObjectCipher>| {
+            let mut bytes = Vec::new();
+            let mut serializer = PdfSerializer {
+                output: &mut bytes,
+                cipher,
+            };
+            object.serialize(&mut serializer)?;
+            Ok::<_, Error>(bytes)
         };
-
-        #[derive(Serialize)]
-        #[serde(rename = "obj")]
-        struct Obj(i64, i64);
-
-        Obj(reference.number(), reference.generation())
-            .serialize(&mut serializer)
-            .unwrap();
-        object.serialize(&mut serializer).unwrap();
-        write!(self.output, "\nendobj\n")?;
+        let bytes = serialize(None)?;
+        if self.check_references {
+            let references = RawObject(bytes.clone()).references();
+            self.record_references(reference, references);
+        }
+        if self.pdfa.is_some() {
+            pdfa::check_object(reference, &bytes)?;
+        }
+        // the serializer does not produce streams
+        if let Some(bytes) = self.compress_object(reference, bytes, false)? {
+            let bytes = match &self.security_handler {
+                Some(handler) => serialize(Some(handler.object_cipher(reference)))?,
+                None => bytes,
+            };
+            self.write_object_bytes(reference, &bytes)?;
+        }
+        self.dangling_references.remove(&reference);
         Ok(())
     }
 
     /// Write an indirect object to the context and return an indirect reference to it.
+    #[track_caller]
     pub fn write_object<T: PdfFormat>(&mut self, object: T) -> Result<IndirectReference<T>, Error> {
-        self.write_object_fn(|_, _| Ok(object))
+        if !self.deduplicate_objects {
+            return self.write_object_fn(|_, _| Ok(object));
+        }
+        let mut references = Vec::new();
+        let (bytes, is_stream) = format_object(&object, None, Some(&mut references))?;
+        let mut hash = [0; 32];
+        let mut hasher = Sha256::new();
+        hasher.input(&bytes);
+        hasher.result(&mut hash);
+        if let Some(&existing) = self.object_hashes.get(&hash) {
+            return Ok(IndirectReference::new(existing.0, existing.1));
+        }
+
+        let reference = self.reserve_reference()?;
+        self.record_references(reference.raw(), references);
+        self.write_formatted_object(&object, reference.raw(), bytes, is_stream)?;
+        self.object_hashes.insert(hash, reference.raw());
+        Ok(reference)
     }
 
+    /// Replace an object of the previous revision in an incremental update.
+    ///
+    /// The new object is written with the same object number and generation, so every
+    /// reference to the old object now refers to `object`. Objects of the current revision
+    /// cannot be replaced.
+    pub fn update_object<T: PdfFormat>(
+        &mut self,
+        reference: RawIndirectReference,
+        object: T,
+    ) -> Result<IndirectReference<T>, Error> {
+        let number = u32::try_from(reference.0).ok();
+        if !number.is_some_and(|number| self.crossref.is_previous(number)) {
+            return Err(Error::UnresolvedReference(reference));
+        }
+        self.write_indirect_object(object, reference)?;
+        Ok(IndirectReference::new(reference.0, reference.1))
+    }
+
+    /// Write the object returned by `fun`, which is passed the reference of the object, so
+    /// it can already be used by objects that `fun` writes. If `fun` fails, the reference is
+    /// left dangling and `finish` fails.
+    #[track_caller]
     pub fn write_object_fn<T: PdfFormat>(
         &mut self,
         fun: impl FnOnce(&mut Self, IndirectReference<T>) -> Result<T, Error>,
     ) -> Result<IndirectReference<T>, Error> {
         // add placeholder entry to crossref
-        let num = self.crossref.add_entry(0, 0);
+        let num = self.crossref.add_entry(0, 0)?;
         let reference = IndirectReference::new(i64::from(num), 0);
+        self.dangling_references
+            .insert(reference.raw(), Location::caller());
         let object = fun(self, reference)?;
 
         self.write_indirect_object(object, reference.raw())?;
+        self.dangling_references.remove(&reference.raw());
         Ok(reference)
     }
 
-    fn write_indirect_object(
+    pub(crate) fn write_indirect_object(
         &mut self,
         object: impl PdfFormat,
         reference: RawIndirectReference,
     ) -> Result<(), Error> {
+        let (bytes, is_stream) = self.format_checked(&object, reference)?;
+        self.write_formatted_object(&object, reference, bytes, is_stream)
+    }
+
+    /// Write `object`, whose unencrypted serialization is `bytes`.
+    fn write_formatted_object(
+        &mut self,
+        object: &impl PdfFormat,
+        reference: RawIndirectReference,
+        bytes: Vec<u8>,
+        is_stream: bool,
+    ) -> Result<(), Error> {
+        if self.pdfa.is_some() {
+            pdfa::check_object(reference, &bytes)?;
+        }
+        // Objects inside of object streams are not encrypted individually, the object stream
+        // as a whole is.
+        if let Some(bytes) = self.compress_object(reference, bytes, is_stream)? {
+            let bytes = match &self.security_handler {
+                Some(handler) => {
+                    format_object(object, Some(&handler.object_cipher(reference)), None)?.0
+                }
+                None => bytes,
+            };
+            self.write_object_bytes(reference, &bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Write an indirect object that must neither be encrypted nor be compressed into an object
+    /// stream, like the encryption dictionary or a cross-reference stream.
+    pub(crate) fn write_unencrypted_object(
+        &mut self,
+        object: impl PdfFormat,
+        reference: RawIndirectReference,
+    ) -> Result<(), Error> {
+        let (bytes, _) = format_object(&object, None, None)?;
+        self.write_object_bytes(reference, &bytes)?;
+        Ok(())
+    }
+
+    /// Serialize `object` without encryption. If `check_references` is set, the references it
+    /// contains are recorded as references from the object `reference`.
+    fn format_checked(
+        &mut self,
+        object: &impl PdfFormat,
+        reference: RawIndirectReference,
+    ) -> Result<(Vec<u8>, bool), Error> {
+        if !self.check_references {
+            return format_object(object, None, None);
+        }
+        let mut references = Vec::new();
+        let formatted = format_object(object, None, Some(&mut references))?;
+        self.record_references(reference, references);
+        Ok(formatted)
+    }
+
+    /// Record that the object `reference` contains `references`, if `check_references` is
+    /// set.
+    fn record_references(
+        &mut self,
+        reference: RawIndirectReference,
+        references: Vec<RawIndirectReference>,
+    ) {
+        if !self.check_references {
+            return;
+        }
+        for target in references {
+            let referenced_by = self.references.entry(target).or_default();
+            if referenced_by.last() != Some(&reference) {
+                referenced_by.push(reference);
+            }
+        }
+    }
+
+    /// Fail if an object was never written, although a reference to it was created or, with
+    /// `check_references`, written into another object.
+    fn check_dangling_references(&mut self) -> Result<(), Error> {
+        let mut dangling: BTreeMap<_, _> = std::mem::take(&mut self.dangling_references)
+            .into_iter()
+            .map(|(reference, created_at)| {
+                let dangling = DanglingReference {
+                    reference,
+                    created_at: Some(created_at),
+                    referenced_by: Vec::new(),
+                };
+                (reference, dangling)
+            })
+            .collect();
+        for (target, referenced_by) in std::mem::take(&mut self.references) {
+            let written = u32::try_from(target.0).is_ok_and(|num| self.crossref.is_written(num));
+            if !written {
+                dangling
+                    .entry(target)
+                    .or_insert_with(|| DanglingReference {
+                        reference: target,
+                        created_at: None,
+                        referenced_by: Vec::new(),
+                    })
+                    .referenced_by = referenced_by;
+            }
+        }
+        if dangling.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::DanglingReferences(dangling.into_values().collect()))
+        }
+    }
+
+    /// When using `XRefMode::Stream`, put the serialized object into the current object stream.
+    /// Returns the bytes again if the object has to be written directly instead, e.g. because
+    /// it is a stream, which may not be stored in an object stream.
+    fn compress_object(
+        &mut self,
+        reference: RawIndirectReference,
+        bytes: Vec<u8>,
+        is_stream: bool,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        if self.xref_mode == XRefMode::Stream && reference.1 == 0 && !is_stream {
+            self.require_version(Version::Pdf1_5, "compressed object streams")?;
+            let number = u32::try_from(reference.0)
+                .ok()
+                .filter(|&number| self.crossref.contains(number))
+                .ok_or(Error::UnresolvedReference(reference))?;
+            self.object_stream.push(number, bytes);
+            if self.object_stream.len() >= ObjectStreamBuilder::MAX_OBJECTS {
+                self.flush_object_stream()?;
+            }
+            return Ok(None);
+        }
+        Ok(Some(bytes))
+    }
+
+    /// Write a serialized object directly to the output. Returns the offset of `bytes` in the
+    /// file.
+    fn write_object_bytes(
+        &mut self,
+        reference: RawIndirectReference,
+        bytes: &[u8],
+    ) -> Result<u64, Error> {
         // update placeholder entry in crossref
-        self.crossref.get_entry_mut(reference.0 as u32).0 = self.output.offset();
-        writeln!(self.output, "{} {} obj", reference.0, reference.1)?;
-        let mut formatter = Formatter {
-            writer: &mut self.output,
+        let (number, generation) = match (u32::try_from(reference.0), u32::try_from(reference.1)) {
+            (Ok(number), Ok(generation)) => (number, generation),
+            _ => return Err(Error::UnresolvedReference(reference)),
         };
-        object.write(&mut formatter)?;
+        self.crossref
+            .set_offset(number, generation, self.output.offset())?;
+        writeln!(self.output, "{} {} obj", reference.0, reference.1)?;
+        let offset = self.output.offset();
+        self.output.write_all(bytes)?;
         write!(self.output, "\nendobj\n")?;
+        Ok(offset)
+    }
+
+    /// Write all objects that are waiting to be compressed into a new object stream.
+    fn flush_object_stream(&mut self) -> Result<(), Error> {
+        if self.object_stream.is_empty() {
+            return Ok(());
+        }
+        let (numbers, stream) = self.object_stream.finish()?;
+        let stream_ref = self.write_object(stream)?;
+        let stream_number = u32::try_from(stream_ref.number())
+            .map_err(|_| Error::UnresolvedReference(stream_ref.raw()))?;
+        for (index, number) in (0..).zip(numbers) {
+            self.crossref
+                .set_compressed(number, stream_number, index)?;
+        }
         Ok(())
     }
 
-    pub fn add_page(&mut self, page: Page) {
-        if let Some(p) = self.page_tree.as_mut() {
-            p.add_page(page)
+    /// Embed `attachment` in the document and add it to the associated files of the document.
+    ///
+    /// PDF/A-2 only allows embedded PDF files, which have to conform to PDF/A themselves.
+    /// PDF/A-3 allows any file, but requires its MIME type and modification date.
+    pub fn attach_file(
+        &mut self,
+        attachment: Attachment,
+    ) -> Result<IndirectReference<FileSpecification>, Error> {
+        let violation = match self.pdfa {
+            Some(PdfAConformance::PdfA2b) if !attachment.contents.starts_with(b"%PDF-") => {
+                Some("PDF/A-2 only allows embedded PDF files")
+            }
+            Some(PdfAConformance::PdfA3b)
+                if attachment.mime_type.is_none() || attachment.mod_date.is_none() =>
+            {
+                Some("PDF/A-3 requires the MIME type and modification date of embedded files")
+            }
+            _ => None,
+        };
+        if let Some(message) = violation {
+            return Err(Error::Conformance {
+                object: None,
+                message: format!("cannot attach {}: {}", attachment.name, message),
+            });
+        }
+        let name = text_string(&attachment.name);
+        if self.embedded_files.get(&name).is_some() {
+            return Err(Error::InvalidInput(format!(
+                "a file named {} is already attached",
+                attachment.name
+            )));
         }
+        let file_specification = attachment.write_to_context(self)?;
+        let reference = self.write_object(file_specification)?;
+        self.embedded_files.insert(name, reference);
+        Ok(reference)
+    }
+
+    /// Add an optional content group, a layer of content that can be shown or hidden by the
+    /// user. Content is added to the group with `PageContext::begin_optional_content`.
+    ///
+    /// Requires PDF 1.5.
+    pub fn add_optional_content_group(
+        &mut self,
+        name: impl Into<String>,
+        visible: bool,
+    ) -> Result<IndirectReference<OptionalContentGroup>, Error> {
+        self.require_version(Version::Pdf1_5, "optional content")?;
+        let group = OptionalContentGroup {
+            name: text_string(&name.into()),
+        };
+        // groups with the same name are still distinct layers
+        let reference = self.write_object_fn(|_, _| Ok(group))?;
+        self.optional_content_groups.push((reference, visible));
+        Ok(reference)
+    }
+
+    /// Add `font` to the `shared_resources` of all pages and return its resource name. The
+    /// name of the font is returned if it has already been added. Fails for new fonts once
+    /// pages have been added.
+    pub fn add_shared_font(&mut self, font: IndirectReference<Font>) -> Result<String, Error> {
+        let fonts = &mut self.shared_resources.font;
+        if let Some((key, _)) = fonts.iter().find(|&(_, &shared)| shared == font) {
+            return Ok(key.clone());
+        }
+        if self.page_tree.page_count() > 0 {
+            return Err(Error::InvalidInput(
+                "shared fonts cannot be added once pages have been added".to_string(),
+            ));
+        }
+        let key = (fonts.len()..)
+            .map(|index| format!("F{}", index))
+            .find(|key| !fonts.contains_key(key))
+            .unwrap();
+        fonts.insert(key.clone(), font);
+        Ok(key)
+    }
+
+    /// Write `page` as the next page of the document. Fails if a page box does not lie inside
+    /// the media box.
+    pub fn add_page(&mut self, page: Page) -> Result<IndirectReference<Page>, Error> {
+        page.check_boxes()?;
+        if page.user_unit.is_some() {
+            self.require_version(Version::Pdf1_6, "UserUnit")?;
+        }
+        let struct_parents = page.struct_parents;
+        let mut page_tree = std::mem::take(&mut self.page_tree);
+        let result = page_tree.add_page(self, page);
+        self.page_tree = page_tree;
+        if let (Ok(reference), Some(key)) = (&result, struct_parents) {
+            self.marked_content.add_page(reference.raw(), key);
+        }
+        result
     }
 
     fn write_trailer(
         &mut self,
-        crossref_offset: u32,
+        crossref_offset: u64,
         document_catalog: IndirectReference<DocumentCatalog>,
         document_info: Option<IndirectReference<DocumentInfo>>,
+        encrypt: Option<RawIndirectReference>,
     ) -> Result<(), Error> {
+        let file_id = match (self.file_id, self.file_identifier) {
+            (Some(file_id), _) | (None, FileIdentifier::Fixed(file_id)) => file_id,
+            (None, FileIdentifier::ContentHash) => self.output.content_digest(),
+            (None, FileIdentifier::Timestamp) => get_digest(self.current_offset()),
+        };
         let mut trailer = Trailer {
             crossref_offset,
             document_catalog,
             document_info,
+            encrypt,
+            file_id,
+            prev: self.previous_revision.as_ref().map(|prev| prev.startxref),
+            original_id: self
+                .previous_revision
+                .as_ref()
+                .and_then(|prev| prev.id.clone()),
         };
-        trailer.write(self)?;
+        if self.xref_mode == XRefMode::Stream || self.crossref.has_compressed_entries() {
+            trailer.write_xref_stream(self)?;
+        } else {
+            let mut formatter = Formatter::new(&mut self.output);
+            self.crossref.write(&mut formatter)?;
+            trailer.write(self)?;
+        }
+        Ok(())
+    }
+
+    fn write_encryption_dictionary(&mut self) -> Result<Option<RawIndirectReference>, Error> {
+        match self.security_handler.take() {
+            Some(handler) => {
+                let num = self.crossref.add_entry(0, 0)?;
+                let reference = RawIndirectReference(i64::from(num), 0);
+                self.write_unencrypted_object(handler.dictionary(), reference)?;
+                Ok(Some(reference))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Write the signature dictionary with its placeholders and the signature field.
+    fn write_signature(&mut self) -> Result<Option<SignaturePlaceholder<'a>>, Error> {
+        let pending = match self.signature.take() {
+            Some(pending) => pending,
+            None => return Ok(None),
+        };
+        let dictionary = pending.dictionary.raw();
+        let field = pending.field.raw();
+        self.dangling_references.remove(&dictionary);
+        self.dangling_references.remove(&field);
+
+        // The signature dictionary must not be compressed into an object stream, since the
+        // placeholders are patched in the file.
+        let cipher = self
+            .security_handler
+            .as_ref()
+            .map(|handler| handler.object_cipher(dictionary));
+        let (bytes, _) = format_object(
+            &SignatureDictionary::new(&pending.signature),
+            cipher.as_ref(),
+            None,
+        )?;
+        let offset = self.write_object_bytes(dictionary, &bytes)?;
+        let placeholder = SignaturePlaceholder::find(pending.signature.signer, &bytes, offset)?;
+
+        self.write_indirect_object(
+            SignatureField::new(&pending.signature.field_name, pending.dictionary),
+            field,
+        )?;
+        self.document_catalog.acro_form = Some(AcroForm::with_signature(pending.field));
+        Ok(Some(placeholder))
+    }
+
+    /// Compute the signature of the held back output, patch it into the signature dictionary
+    /// and write the output.
+    fn apply_signature(&mut self, placeholder: SignaturePlaceholder) -> Result<(), Error> {
+        let mut data = self.header_bytes();
+        let header_length = data.len();
+        data.extend(self.output.take_buffer());
+        placeholder.apply(&mut data)?;
+        self.output.write_all(&data[header_length..])?;
         Ok(())
     }
 
     fn write_document_catalog(&mut self) -> Result<IndirectReference<DocumentCatalog>, Error> {
-        let page_tree = self
-            .page_tree
-            .take()
-            .expect("Internal Error: context has no pagetree.");
-        let page_tree = page_tree.write_to_context(self)?;
-        let mut catalog = self.document_catalog.take().expect("No document catalog");
-        catalog.pages = Some(page_tree);
+        let page_tree = std::mem::take(&mut self.page_tree);
+        let mut catalog = std::mem::take(&mut self.document_catalog);
+        if let Some(conformance) = self.pdfua {
+            let missing = if self.language.is_none() {
+                Some("the natural language of the document")
+            } else if self.document_info.title.is_none() {
+                Some("a document title")
+            } else if self.structure_tree.is_empty() {
+                Some("a structure tree")
+            } else {
+                None
+            };
+            if let Some(missing) = missing {
+                return Err(Error::Conformance {
+                    object: None,
+                    message: format!("PDF/UA requires {}", missing),
+                });
+            }
+            pdfua::check_version(self.required_version)?;
+            let viewer_preferences = self.viewer_preferences.get_or_insert_with(Default::default);
+            viewer_preferences.display_doc_title = Some(true);
+            let xmp_metadata = self.xmp_metadata.get_or_insert_with(Default::default);
+            xmp_metadata.pdfua_part = Some(conformance.part());
+        }
+        let required_versions = viewer::required_versions(
+            self.page_mode,
+            self.page_layout,
+            self.viewer_preferences.as_ref(),
+        );
+        for (version, feature) in required_versions {
+            self.require_version(version, feature)?;
+        }
+        catalog.page_mode = self.page_mode;
+        catalog.page_layout = self.page_layout;
+        catalog.viewer_preferences = self.viewer_preferences.take();
+        catalog.open_action = self.open_action.take().map(GoToAction::new);
+        if let Some(conformance) = self.pdfa {
+            pdfa::check_version(self.required_version)?;
+            catalog.output_intents = Some(vec![OutputIntent::srgb(self)?]);
+            let xmp_metadata = self.xmp_metadata.get_or_insert_with(Default::default);
+            xmp_metadata.pdfa_identification = Some(conformance.identification());
+        }
+        if let Some(xmp_metadata) = self.xmp_metadata.take() {
+            let stream = xmp_metadata.to_stream(&self.document_info)?;
+            catalog.metadata = Some(self.write_object(stream)?);
+        }
+        if self.required_version > self.version {
+            catalog.version = Some(self.required_version);
+        }
+        let optional_content_groups = std::mem::take(&mut self.optional_content_groups);
+        if !optional_content_groups.is_empty() {
+            let mut order = std::mem::take(&mut self.optional_content_order);
+            if order.is_empty() {
+                order = optional_content_groups
+                    .iter()
+                    .map(|&(group, _)| OrderItem::Group(group, Vec::new()))
+                    .collect();
+            }
+            let (on, off): (Vec<_>, Vec<_>) = optional_content_groups
+                .iter()
+                .partition(|&&(_, visible)| visible);
+            catalog.oc_properties = Some(OptionalContentProperties {
+                groups: optional_content_groups.iter().map(|&(group, _)| group).collect(),
+                default_configuration: OptionalContentConfiguration {
+                    name: text_string("Default"),
+                    on: on.into_iter().map(|&(group, _)| group).collect(),
+                    off: off.into_iter().map(|&(group, _)| group).collect(),
+                    order: OrderEntry::from_items(order),
+                },
+            });
+        }
+        catalog.lang = self.language.take().map(|language| text_string(&language));
+        let structure_tree = std::mem::take(&mut self.structure_tree);
+        let marked_content = std::mem::take(&mut self.marked_content);
+        let checker = self.pdfua.map(|_| StructureChecker::default());
+        if let Some(root) = write_structure_tree(self, structure_tree, &marked_content, checker)? {
+            catalog.struct_tree_root = Some(root.into());
+            catalog.mark_info = Some(MarkInfo { marked: true });
+        }
+        let outline = std::mem::take(&mut self.outline);
+        catalog.outlines = write_outline(self, outline)?;
+        let mut names = NameDictionary::default();
+        let named_destinations = std::mem::take(&mut self.named_destinations);
+        if !named_destinations.is_empty() {
+            names.dests = Some(named_destinations.write_to_context(self)?);
+        }
+        let embedded_files = std::mem::take(&mut self.embedded_files);
+        if !embedded_files.is_empty() {
+            let files = embedded_files.iter().map(|(_, &file)| file).collect();
+            catalog.af = Some(files);
+            names.embedded_files = Some(embedded_files.write_to_context(self)?);
+        }
+        if names.dests.is_some() || names.embedded_files.is_some() {
+            catalog.names = Some(names);
+        }
+        let page_labels = std::mem::take(&mut self.page_labels);
+        if !page_labels.is_empty() {
+            catalog.page_labels = Some(page_labels.write_to_context(self)?);
+        }
+        if let Some(previous) = self.previous_revision.take() {
+            let result = self.update_document_catalog(&previous, page_tree, catalog);
+            self.previous_revision = Some(previous);
+            return result;
+        }
+        catalog.pages = Some(page_tree.finish(self)?);
         self.write_object(catalog)
     }
 
+    /// Append new pages to the page tree of the previous revision and merge the entries of
+    /// `catalog` into the previous document catalog.
+    fn update_document_catalog(
+        &mut self,
+        previous: &PreviousRevision,
+        page_tree: PageTreeWriter,
+        catalog: DocumentCatalog,
+    ) -> Result<IndirectReference<DocumentCatalog>, Error> {
+        let invalid = |msg: &str| Error::InvalidObjectGraph(msg.to_string());
+        let page_tree_root = previous.page_tree_root;
+        let mut page_tree_dict = previous.page_tree.clone();
+        let mut catalog_dict = previous.catalog.clone();
+
+        let page_count = page_tree.page_count();
+        if page_count > 0 {
+            let new_pages = page_tree.finish(self)?;
+            let mut kids = page_tree_dict
+                .get("Kids")
+                .and_then(RawObject::as_array)
+                .ok_or_else(|| invalid("page tree root has no direct /Kids array"))?;
+            let count = page_tree_dict
+                .get_integer("Count")
+                .ok_or_else(|| invalid("page tree root has no /Count"))?;
+            kids.push(RawObject(
+                format!("{} {} R", new_pages.number(), new_pages.generation()).into_bytes(),
+            ));
+            let mut kids_bytes = Vec::new();
+            kids.write(&mut Formatter::new(&mut kids_bytes))?;
+            page_tree_dict.set("Kids", RawObject(kids_bytes));
+            page_tree_dict.set("Count", RawObject((count + page_count).to_string().into_bytes()));
+            self.write_indirect_object(page_tree_dict, page_tree_root)?;
+        }
+
+        // Merge the new catalog entries. The previous catalog is only replaced if something
+        // changed.
+        let (catalog_bytes, _) = self.format_checked(&catalog, previous.root)?;
+        let new_entries = RawObject(catalog_bytes)
+            .as_dictionary()
+            .ok_or_else(|| invalid("could not merge document catalog"))?;
+        let mut changed = false;
+        for (key, value) in new_entries.iter() {
+            if key != b"Type" && key != b"Pages" && !value.is_null() {
+                catalog_dict.set(key, value.clone());
+                changed = true;
+            }
+        }
+        if changed {
+            self.write_indirect_object(catalog_dict, previous.root)?;
+        }
+        Ok(IndirectReference::new(previous.root.0, previous.root.1))
+    }
+
     fn write_document_info(&mut self) -> Result<IndirectReference<DocumentInfo>, Error> {
-        let doc_info = std::mem::replace(&mut self.document_info, HashMap::default());
-        self.write_object1(doc_info)
+        if self.document_info.has_deprecated_entries() {
+            self.deprecated_in_2_0("the document information dictionary")?;
+        }
+        let doc_info = std::mem::take(&mut self.document_info);
+        self.write_object(doc_info)
     }
     /// Finish the `Context` and flush all remaining writes.
+    ///
+    /// Fails with `Error::DanglingReferences` if an object whose reference was created has
+    /// never been written. With `check_references`, every reference written into an object
+    /// is checked as well.
     pub fn finish(mut self) -> Result<(), Error> {
-        for dangling_ref in &self.dangling_references {
-            error!(
-                "Created indirect reference {:?} but never assigned an object to it!",
-                dangling_ref
-            )
+        if self.xref_mode == XRefMode::Stream {
+            self.require_version(Version::Pdf1_5, "cross-reference streams")?;
         }
-
+        let signature = self.write_signature()?;
         let document_catalog = self.write_document_catalog()?;
         let document_info = if !self.document_info.is_empty() {
             Some(self.write_document_info()?)
         } else {
-            None
+            self.previous_revision
+                .as_ref()
+                .and_then(|prev| prev.info)
+                .map(|info| IndirectReference::new(info.0, info.1))
         };
 
+        self.flush_object_stream()?;
+        self.check_dangling_references()?;
+        let encrypt = self.write_encryption_dictionary()?;
+
         let offset = self.output.offset();
-        let mut formatter = Formatter {
-            writer: &mut self.output,
-        };
-        self.crossref.write(&mut formatter)?;
-        self.write_trailer(offset as u32, document_catalog, document_info)?;
-        self.output.flush()
+        self.write_trailer(offset, document_catalog, document_info, encrypt)?;
+        if let Some(signature) = signature {
+            self.apply_signature(signature)?;
+        }
+        self.output.flush()?;
+        Ok(())
     }
 
     pub(crate) fn current_offset(&self) -> u64 {
         self.output.offset()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::font::builtin::BuiltInFont;
+    use crate::font::Font;
+    use crate::object::Value;
+    use crate::stream::StreamEncoder;
+    use crate::Pt;
+
+    fn write_document(xref_mode: XRefMode, file_identifier: FileIdentifier) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_7).unwrap();
+        context.xref_mode = xref_mode;
+        context.file_identifier = file_identifier;
+        context.document_info = DocumentInfo {
+            title: Some("Title".to_string()),
+            author: Some("Author".to_string()),
+            subject: Some("Subject".to_string()),
+            keywords: Some("Keywords".to_string()),
+            ..Default::default()
+        };
+        let mut page = Page::new();
+        page.add_content(&mut context, None, |page_context| {
+            for font in &[BuiltInFont::TimesRoman, BuiltInFont::Helvetica, BuiltInFont::Courier] {
+                let font = font.font(page_context.pdf_context)?;
+                let font_ref = page_context.pdf_context.write_object(Font::Simple(font))?;
+                page_context.add_font(font_ref);
+            }
+            Ok(())
+        })
+        .unwrap();
+        context.add_page(page).unwrap();
+        context.finish().unwrap();
+        output
+    }
+
+    #[test]
+    fn test_deterministic_output() {
+        for &xref_mode in &[XRefMode::Table, XRefMode::Stream] {
+            let first = write_document(xref_mode, FileIdentifier::ContentHash);
+            let second = write_document(xref_mode, FileIdentifier::ContentHash);
+            assert!(first == second);
+        }
+
+        let output = write_document(XRefMode::Table, FileIdentifier::Fixed([0xAB; 16]));
+        let id = format!("<{}>", "AB".repeat(16));
+        assert!(String::from_utf8_lossy(&output).contains(&id));
+    }
+
+    #[test]
+    fn test_object_streams() {
+        let mut output = Vec::new();
+        let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_7).unwrap();
+        context.xref_mode = XRefMode::Stream;
+        let mut encoder = StreamEncoder::new(None);
+        encoder.write_all(b"0 0 m 10 10 l S").unwrap();
+        let stream = encoder.into_stream().unwrap();
+        // streams are detected by their kind, also when wrapped in another type
+        context.write_object(Value::Stream(stream)).unwrap();
+        context.write_object(Value::Name("Packed".to_string())).unwrap();
+        context.finish().unwrap();
+
+        let output = String::from_utf8_lossy(&output);
+        assert!(output.contains("\nstream\n0 0 m 10 10 l S\nendstream"));
+        assert!(output.contains("/Type /ObjStm"));
+        assert!(!output.contains("/Packed"));
+    }
+
+    #[test]
+    fn test_version_policy() {
+        let write = |version, version_policy| {
+            let mut output = Vec::new();
+            let mut context = DocumentContext::with_writer(&mut output, version)?;
+            context.version_policy = version_policy;
+            let mut page = Page::new();
+            page.add_content(&mut context, None, |page_context| {
+                page_context.set_opacity(0.5, 1.0)
+            })?;
+            context.add_page(page)?;
+            context.finish()?;
+            Ok::<_, Error>(String::from_utf8_lossy(&output).into_owned())
+        };
+
+        let output = write(Version::Pdf1_3, VersionPolicy::Upgrade).unwrap();
+        assert!(output.starts_with("%PDF-1.3\n"));
+        assert!(output.contains("/Version /1.4"));
+
+        let output = write(Version::Pdf2_0, VersionPolicy::Upgrade).unwrap();
+        assert!(output.starts_with("%PDF-2.0\n"));
+        assert!(!output.contains("/Version"));
+
+        assert!(write(Version::Pdf1_3, VersionPolicy::Strict).is_err());
+        let output = write(Version::Pdf1_3, VersionPolicy::Warn).unwrap();
+        assert!(!output.contains("/Version"));
+
+        let mut context = DocumentContext::with_writer(Vec::new(), Version::Pdf1_4).unwrap();
+        context.version_policy = VersionPolicy::Strict;
+        context.xref_mode = XRefMode::Stream;
+        assert!(context.finish().is_err());
+    }
+
+    #[test]
+    fn test_deduplicate_objects() {
+        let write = |deduplicate_objects| {
+            let mut output = Vec::new();
+            let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_7).unwrap();
+            context.deduplicate_objects = deduplicate_objects;
+            for _ in 0..3 {
+                let mut page = Page::new();
+                page.add_content(&mut context, None, |page_context| {
+                    let font = BuiltInFont::Helvetica.font(page_context.pdf_context)?;
+                    let font_ref = page_context.pdf_context.write_object(Font::Simple(font))?;
+                    let font_key = page_context.add_font(font_ref);
+                    page_context.begin_text()?;
+                    page_context.set_font(&font_key, Pt(12.0))?;
+                    page_context.end_text()
+                })
+                .unwrap();
+                context.add_page(page).unwrap();
+            }
+            context.finish().unwrap();
+            String::from_utf8_lossy(&output).into_owned()
+        };
+
+        let output = write(false);
+        assert_eq!(output.matches("/Type /FontDescriptor").count(), 3);
+        let output = write(true);
+        assert_eq!(output.matches("/Type /FontDescriptor").count(), 1);
+        assert_eq!(output.matches("/Type /Page\n").count(), 3);
+    }
+
+    #[test]
+    fn test_dangling_references() {
+        let mut context = DocumentContext::with_writer(Vec::new(), Version::Pdf1_7).unwrap();
+        let reference = context.create_reference::<BTreeMap<String, Vec<u8>>>().unwrap();
+        let line = line!() - 1;
+        let result = context.write_object_fn(|_, _| -> Result<BTreeMap<String, Vec<u8>>, Error> {
+            Err(Error::InvalidInput("failed".to_string()))
+        });
+        assert!(result.is_err());
+        let dangling = match context.finish() {
+            Err(Error::DanglingReferences(dangling)) => dangling,
+            other => panic!("unexpected result {:?}", other),
+        };
+        assert_eq!(dangling.len(), 2);
+        assert_eq!(dangling[0].reference, reference.raw());
+        let created_at = dangling[0].created_at.unwrap();
+        assert_eq!((created_at.file(), created_at.line()), (file!(), line));
+
+        let write = |check_references| {
+            let mut context = DocumentContext::with_writer(Vec::new(), Version::Pdf1_7)?;
+            context.check_references = check_references;
+            let mut page = Page::new();
+            page.contents.push(IndirectReference::new(42, 0));
+            let page = context.add_page(page)?;
+            context.finish().map(|_| page)
+        };
+        assert!(write(false).is_ok());
+        match write(true) {
+            Err(Error::DanglingReferences(dangling)) => {
+                assert_eq!(dangling.len(), 1);
+                assert_eq!(dangling[0].reference, RawIndirectReference(42, 0));
+                assert_eq!(dangling[0].created_at, None);
+                assert_eq!(dangling[0].referenced_by.len(), 1);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // references in objects written through serde are checked as well
+        #[derive(serde::Serialize)]
+        struct Holder {
+            targets: Vec<RawIndirectReference>,
+        }
+        let mut context = DocumentContext::with_writer(Vec::new(), Version::Pdf1_7).unwrap();
+        context.check_references = true;
+        let holder = Holder {
+            targets: vec![RawIndirectReference(42, 0)],
+        };
+        let holder = context.write_object1(holder).unwrap();
+        match context.finish() {
+            Err(Error::DanglingReferences(dangling)) => {
+                assert_eq!(dangling.len(), 1);
+                assert_eq!(dangling[0].reference, RawIndirectReference(42, 0));
+                assert_eq!(dangling[0].referenced_by, vec![holder.raw()]);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_update_object() {
+        let previous = write_document(XRefMode::Table, FileIdentifier::ContentHash);
+        let document = ExistingDocument::parse(&previous).unwrap();
+        let info = document.trailer.get_reference("Info").unwrap();
+
+        let mut output = Vec::new();
+        let mut context = DocumentContext::incremental_update(&previous, &mut output).unwrap();
+        let title = DocumentInfo {
+            title: Some("Updated".to_string()),
+            ..Default::default()
+        };
+        context.update_object(info, title.clone()).unwrap();
+        let new = context.write_object(title.clone()).unwrap();
+        for &reference in &[new.raw(), RawIndirectReference(0, 0), RawIndirectReference(1000, 0)] {
+            assert!(matches!(
+                context.update_object(reference, title.clone()),
+                Err(Error::UnresolvedReference(_))
+            ));
+        }
+        context.finish().unwrap();
+    }
+}
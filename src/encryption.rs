@@ -0,0 +1,608 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! Password protection using the standard security handler.
+
+use std::cell::Cell;
+
+use bitflags::bitflags;
+use crypto::aessafe::{AesSafe128Encryptor, AesSafe256Encryptor};
+use crypto::digest::Digest;
+use crypto::md5::Md5;
+use crypto::rc4::Rc4;
+use crypto::sha2::{Sha256, Sha384, Sha512};
+use crypto::symmetriccipher::{BlockEncryptor, SynchronousStreamCipher};
+use rand::Rng;
+
+use crate as lemon_pdf;
+use crate::object::{HexString, RawIndirectReference};
+use lemon_pdf_derive::PdfFormat;
+
+bitflags! {
+    /// The operations a user who opened the document with the user password may perform.
+    pub struct Permissions: u32 {
+        const PRINT = 1 << 2;
+        const MODIFY = 1 << 3;
+        const COPY = 1 << 4;
+        const ANNOTATE = 1 << 5;
+        const FILL_FORMS = 1 << 8;
+        const EXTRACT_FOR_ACCESSIBILITY = 1 << 9;
+        const ASSEMBLE = 1 << 10;
+        const PRINT_HIGH_QUALITY = 1 << 11;
+    }
+}
+
+impl Permissions {
+    /// The value of the `/P` entry. All reserved bits that the specification requires to be
+    /// set are set.
+    fn p_value(self) -> i64 {
+        i64::from((self.bits() | 0xFFFF_F0C0) as i32)
+    }
+}
+
+/// The algorithm used to encrypt strings and streams.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EncryptionMethod {
+    /// 128-bit RC4 (security handler revision 3, PDF 1.4).
+    Rc4_128,
+    /// 128-bit AES in CBC mode using the `AESV2` crypt filter (security handler revision 4,
+    /// PDF 1.6).
+    Aes128,
+    /// 256-bit AES in CBC mode using the `AESV3` crypt filter (security handler revision 6,
+    /// PDF 2.0).
+    Aes256,
+}
+
+/// The settings for encrypting a document.
+#[derive(Debug, Clone)]
+pub struct Encryption {
+    /// The password needed to open the document. May be empty.
+    pub user_password: String,
+    /// The password that grants full access. If empty, the user password is used.
+    pub owner_password: String,
+    pub permissions: Permissions,
+    pub method: EncryptionMethod,
+    /// Whether metadata streams (`/Type /Metadata`) are encrypted as well. Leaving them
+    /// unencrypted allows indexing tools to read the metadata without a password. Ignored for
+    /// RC4 encryption, which always encrypts metadata.
+    pub encrypt_metadata: bool,
+}
+
+impl Encryption {
+    pub fn new(
+        user_password: impl Into<String>,
+        owner_password: impl Into<String>,
+        permissions: Permissions,
+        method: EncryptionMethod,
+    ) -> Self {
+        Encryption {
+            user_password: user_password.into(),
+            owner_password: owner_password.into(),
+            permissions,
+            method,
+            encrypt_metadata: true,
+        }
+    }
+}
+
+const PASSWORD_PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// The length of the file encryption key in bytes for revisions 3 and 4.
+const KEY_LENGTH: usize = 16;
+
+/// The length of the file encryption key in bytes for revision 6.
+const KEY_LENGTH_256: usize = 32;
+
+/// Encode a password as PDFDocEncoding (approximated by Latin-1) and pad or truncate it to
+/// 32 bytes.
+fn pad_password(password: &str) -> [u8; 32] {
+    let mut padded = [0; 32];
+    let bytes = password
+        .chars()
+        .map(|chr| if (chr as u32) < 256 { chr as u8 } else { b'?' })
+        .chain(PASSWORD_PADDING.iter().copied());
+    for (target, byte) in padded.iter_mut().zip(bytes) {
+        *target = byte;
+    }
+    padded
+}
+
+pub(crate) fn md5(parts: &[&[u8]]) -> [u8; 16] {
+    let mut md5 = Md5::new();
+    for part in parts {
+        md5.input(part);
+    }
+    let mut result = [0; 16];
+    md5.result(&mut result);
+    result
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0; data.len()];
+    Rc4::new(key).process(data, &mut output);
+    output
+}
+
+/// Apply RC4 20 times, the i-th time with every byte of the key XORed with i (revision 3+).
+fn rc4_iterated(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut data = rc4(key, data);
+    for i in 1..=19 {
+        let round_key: Vec<u8> = key.iter().map(|byte| byte ^ i).collect();
+        data = rc4(&round_key, &data);
+    }
+    data
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+/// Encrypt `data`, whose length must be a multiple of 16, with AES in CBC mode without
+/// padding.
+fn aes_cbc(cipher: &dyn BlockEncryptor, iv: [u8; 16], data: &[u8]) -> Vec<u8> {
+    debug_assert_eq!(data.len() % 16, 0);
+    let mut output = Vec::with_capacity(data.len());
+    let mut previous = iv;
+    for block in data.chunks(16) {
+        let mut input = [0; 16];
+        for (i, byte) in block.iter().enumerate() {
+            input[i] = byte ^ previous[i];
+        }
+        cipher.encrypt_block(&input, &mut previous);
+        output.extend_from_slice(&previous);
+    }
+    output
+}
+
+/// Encrypt `data` with AES in CBC mode with PKCS#7 padding, as done for strings and streams.
+/// The initialization vector is prepended to the result.
+fn aes_cbc_padded(cipher: &dyn BlockEncryptor, iv: [u8; 16], data: &[u8]) -> Vec<u8> {
+    let padding = 16 - data.len() % 16;
+    let mut padded = data.to_vec();
+    padded.extend(std::iter::repeat_n(padding as u8, padding));
+
+    let mut output = iv.to_vec();
+    output.extend(aes_cbc(cipher, iv, &padded));
+    output
+}
+
+/// Encode a password for revision 6: UTF-8, truncated to 127 bytes.
+///
+/// The SASLprep profile is not applied, so passwords should be restricted to characters
+/// that it leaves unchanged.
+fn password_bytes(password: &str) -> &[u8] {
+    let mut end = password.len().min(127);
+    while !password.is_char_boundary(end) {
+        end -= 1;
+    }
+    &password.as_bytes()[..end]
+}
+
+/// The hash function of revision 6 (Algorithm 2.B of ISO 32000-2). `user_key` is the 48 byte
+/// `/U` entry when computing owner values and empty otherwise.
+fn hash_r6(password: &[u8], salt: &[u8], user_key: &[u8]) -> [u8; 32] {
+    let mut sha256 = Sha256::new();
+    sha256.input(password);
+    sha256.input(salt);
+    sha256.input(user_key);
+    let mut k = vec![0; 32];
+    sha256.result(&mut k);
+
+    let mut round = 0;
+    loop {
+        let mut k1 = Vec::with_capacity(64 * (password.len() + k.len() + user_key.len()));
+        for _ in 0..64 {
+            k1.extend_from_slice(password);
+            k1.extend_from_slice(&k);
+            k1.extend_from_slice(user_key);
+        }
+        let mut iv = [0; 16];
+        iv.copy_from_slice(&k[16..32]);
+        let e = aes_cbc(&AesSafe128Encryptor::new(&k[..16]), iv, &k1);
+
+        let remainder = e[..16].iter().map(|&byte| u32::from(byte)).sum::<u32>() % 3;
+        let mut digest: Box<dyn Digest> = match remainder {
+            0 => Box::new(Sha256::new()),
+            1 => Box::new(Sha384::new()),
+            _ => Box::new(Sha512::new()),
+        };
+        digest.input(&e);
+        k = vec![0; digest.output_bytes()];
+        digest.result(&mut k);
+
+        round += 1;
+        if round >= 64 && u32::from(e[e.len() - 1]) <= round - 32 {
+            break;
+        }
+    }
+
+    let mut result = [0; 32];
+    result.copy_from_slice(&k[..32]);
+    result
+}
+
+/// The encryption dictionary that is referenced from the trailer.
+#[derive(Debug, Clone, PdfFormat)]
+#[omit_type(true)]
+pub(crate) struct EncryptionDictionary {
+    filter: &'static str,
+    #[rename("V")]
+    v: u32,
+    #[rename("R")]
+    r: u32,
+    length: u32,
+    #[rename("CF")]
+    #[skip_if("Option::is_none")]
+    cf: Option<CryptFilters>,
+    #[rename("StmF")]
+    #[skip_if("Option::is_none")]
+    stm_f: Option<&'static str>,
+    #[rename("StrF")]
+    #[skip_if("Option::is_none")]
+    str_f: Option<&'static str>,
+    #[rename("O")]
+    o: HexString,
+    #[rename("U")]
+    u: HexString,
+    #[rename("OE")]
+    #[skip_if("Option::is_none")]
+    oe: Option<HexString>,
+    #[rename("UE")]
+    #[skip_if("Option::is_none")]
+    ue: Option<HexString>,
+    #[rename("P")]
+    p: i64,
+    #[skip_if("Option::is_none")]
+    perms: Option<HexString>,
+    #[skip_if("Option::is_none")]
+    encrypt_metadata: Option<bool>,
+}
+
+#[derive(Debug, Clone, PdfFormat)]
+#[omit_type(true)]
+struct CryptFilters {
+    #[rename("StdCF")]
+    std_cf: CryptFilter,
+}
+
+impl CryptFilters {
+    fn new(cfm: &'static str, key_length: usize) -> Self {
+        CryptFilters {
+            std_cf: CryptFilter {
+                cfm,
+                auth_event: "DocOpen",
+                length: key_length as u32,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PdfFormat)]
+struct CryptFilter {
+    #[rename("CFM")]
+    cfm: &'static str,
+    auth_event: &'static str,
+    length: u32,
+}
+
+/// Computes the file encryption key and encrypts the strings and streams of indirect objects.
+#[derive(Debug, Clone)]
+pub(crate) struct SecurityHandler {
+    method: EncryptionMethod,
+    key: Vec<u8>,
+    encrypt_metadata: bool,
+    dictionary: EncryptionDictionary,
+}
+
+impl SecurityHandler {
+    /// Set up the security handler for the first element of the file identifier `file_id`.
+    pub fn new(encryption: &Encryption, file_id: &[u8]) -> Self {
+        let owner_password = if encryption.owner_password.is_empty() {
+            &encryption.user_password
+        } else {
+            &encryption.owner_password
+        };
+        let encrypt_metadata =
+            encryption.encrypt_metadata || encryption.method == EncryptionMethod::Rc4_128;
+        let p = encryption.permissions.p_value();
+
+        let (key, dictionary) = match encryption.method {
+            EncryptionMethod::Rc4_128 | EncryptionMethod::Aes128 => {
+                Self::revision_4(encryption, owner_password, p, encrypt_metadata, file_id)
+            }
+            EncryptionMethod::Aes256 => {
+                Self::revision_6(encryption, owner_password, p, encrypt_metadata)
+            }
+        };
+
+        SecurityHandler {
+            method: encryption.method,
+            key,
+            encrypt_metadata,
+            dictionary,
+        }
+    }
+
+    /// The key and encryption dictionary for RC4 (revision 3) and AES-128 (revision 4).
+    fn revision_4(
+        encryption: &Encryption,
+        owner_password: &str,
+        p: i64,
+        encrypt_metadata: bool,
+        file_id: &[u8],
+    ) -> (Vec<u8>, EncryptionDictionary) {
+        let user_password = pad_password(&encryption.user_password);
+
+        // Algorithm 3: the owner password entry
+        let mut hash = md5(&[&pad_password(owner_password)]);
+        for _ in 0..50 {
+            hash = md5(&[&hash[..KEY_LENGTH]]);
+        }
+        let o = rc4_iterated(&hash[..KEY_LENGTH], &user_password);
+
+        // Algorithm 2: the file encryption key
+        let metadata_marker: &[u8] = if encrypt_metadata { &[] } else { &[0xFF; 4] };
+        let mut key = md5(&[
+            &user_password,
+            &o,
+            &(p as i32).to_le_bytes(),
+            file_id,
+            metadata_marker,
+        ]);
+        for _ in 0..50 {
+            key = md5(&[&key[..KEY_LENGTH]]);
+        }
+        let key = key[..KEY_LENGTH].to_vec();
+
+        // Algorithm 5: the user password entry
+        let mut u = rc4_iterated(&key, &md5(&[&PASSWORD_PADDING, file_id]));
+        u.extend_from_slice(&[0; 16]);
+
+        let (v, r, cf, filter_name) = match encryption.method {
+            EncryptionMethod::Rc4_128 => (2, 3, None, None),
+            _ => (
+                4,
+                4,
+                Some(CryptFilters::new("AESV2", KEY_LENGTH)),
+                Some("StdCF"),
+            ),
+        };
+
+        let dictionary = EncryptionDictionary {
+            filter: "Standard",
+            v,
+            r,
+            length: 8 * KEY_LENGTH as u32,
+            cf,
+            stm_f: filter_name,
+            str_f: filter_name,
+            o: HexString(o),
+            u: HexString(u),
+            oe: None,
+            ue: None,
+            p,
+            perms: None,
+            encrypt_metadata: if encrypt_metadata { None } else { Some(false) },
+        };
+        (key, dictionary)
+    }
+
+    /// The key and encryption dictionary for AES-256 (revision 6).
+    fn revision_6(
+        encryption: &Encryption,
+        owner_password: &str,
+        p: i64,
+        encrypt_metadata: bool,
+    ) -> (Vec<u8>, EncryptionDictionary) {
+        let key: [u8; KEY_LENGTH_256] = random_bytes();
+        let no_iv = [0; 16];
+
+        // Algorithm 8: the user password entries
+        let user_password = password_bytes(&encryption.user_password);
+        let salts: [u8; 16] = random_bytes();
+        let (validation_salt, key_salt) = salts.split_at(8);
+        let mut u = hash_r6(user_password, validation_salt, &[]).to_vec();
+        u.extend_from_slice(&salts);
+        let intermediate = hash_r6(user_password, key_salt, &[]);
+        let ue = aes_cbc(&AesSafe256Encryptor::new(&intermediate), no_iv, &key);
+
+        // Algorithm 9: the owner password entries
+        let owner_password = password_bytes(owner_password);
+        let salts: [u8; 16] = random_bytes();
+        let (validation_salt, key_salt) = salts.split_at(8);
+        let mut o = hash_r6(owner_password, validation_salt, &u).to_vec();
+        o.extend_from_slice(&salts);
+        let intermediate = hash_r6(owner_password, key_salt, &u);
+        let oe = aes_cbc(&AesSafe256Encryptor::new(&intermediate), no_iv, &key);
+
+        // Algorithm 10: the permissions entry
+        let mut perms = [0; 16];
+        perms[..8].copy_from_slice(&p.to_le_bytes());
+        perms[8] = if encrypt_metadata { b'T' } else { b'F' };
+        perms[9..12].copy_from_slice(b"adb");
+        perms[12..].copy_from_slice(&random_bytes::<4>());
+        let perms = aes_cbc(&AesSafe256Encryptor::new(&key), no_iv, &perms);
+
+        let dictionary = EncryptionDictionary {
+            filter: "Standard",
+            v: 5,
+            r: 6,
+            length: 8 * KEY_LENGTH_256 as u32,
+            cf: Some(CryptFilters::new("AESV3", KEY_LENGTH_256)),
+            stm_f: Some("StdCF"),
+            str_f: Some("StdCF"),
+            o: HexString(o),
+            u: HexString(u),
+            oe: Some(HexString(oe)),
+            ue: Some(HexString(ue)),
+            p,
+            perms: Some(HexString(perms)),
+            encrypt_metadata: Some(encrypt_metadata),
+        };
+        (key.to_vec(), dictionary)
+    }
+
+    pub fn dictionary(&self) -> &EncryptionDictionary {
+        &self.dictionary
+    }
+
+    /// The cipher for the strings and streams of the indirect object `reference` (Algorithm 1).
+    pub fn object_cipher(&self, reference: RawIndirectReference) -> ObjectCipher {
+        let key = match self.method {
+            // Revision 6 uses the file encryption key directly (Algorithm 1.A).
+            EncryptionMethod::Aes256 => self.key.clone(),
+            EncryptionMethod::Rc4_128 | EncryptionMethod::Aes128 => {
+                let number = (reference.0 as u32).to_le_bytes();
+                let generation = (reference.1 as u32).to_le_bytes();
+                let salt: &[u8] = match self.method {
+                    EncryptionMethod::Aes128 => b"sAlT",
+                    _ => b"",
+                };
+                let hash = md5(&[&self.key, &number[..3], &generation[..2], salt]);
+                let key_len = (self.key.len() + 5).min(16);
+                hash[..key_len].to_vec()
+            }
+        };
+        ObjectCipher {
+            method: self.method,
+            key,
+            encrypt_metadata: self.encrypt_metadata,
+            counter: Cell::new(0),
+        }
+    }
+}
+
+/// Encrypts the strings and streams of a single indirect object.
+#[derive(Debug, Clone)]
+pub(crate) struct ObjectCipher {
+    method: EncryptionMethod,
+    key: Vec<u8>,
+    encrypt_metadata: bool,
+    /// Used to derive a distinct initialization vector for every encrypted string or stream.
+    counter: Cell<u64>,
+}
+
+impl ObjectCipher {
+    pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
+        match self.method {
+            EncryptionMethod::Rc4_128 => rc4(&self.key, data),
+            EncryptionMethod::Aes128 => {
+                let counter = self.counter.get();
+                self.counter.set(counter + 1);
+                let iv = md5(&[&self.key, &counter.to_le_bytes()]);
+                aes_cbc_padded(&AesSafe128Encryptor::new(&self.key), iv, data)
+            }
+            EncryptionMethod::Aes256 => {
+                aes_cbc_padded(&AesSafe256Encryptor::new(&self.key), random_bytes(), data)
+            }
+        }
+    }
+
+    /// Whether streams of type `/Metadata` are to be encrypted.
+    pub fn encrypts_metadata(&self) -> bool {
+        self.encrypt_metadata
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pad_password() {
+        let padded = pad_password("abc");
+        assert_eq!(&padded[..3], b"abc");
+        assert_eq!(&padded[3..], &PASSWORD_PADDING[..29]);
+    }
+
+    #[test]
+    fn test_aes_cbc_padded() {
+        // NIST SP 800-38A, F.2.1 (first block)
+        let key = [
+            0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf,
+            0x4f, 0x3c,
+        ];
+        let iv = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+            0x0e, 0x0f,
+        ];
+        let plaintext = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93,
+            0x17, 0x2a,
+        ];
+        let encrypted = aes_cbc_padded(&AesSafe128Encryptor::new(&key), iv, &plaintext);
+        assert_eq!(&encrypted[..16], &iv);
+        assert_eq!(
+            &encrypted[16..32],
+            &[
+                0x76, 0x49, 0xab, 0xac, 0x81, 0x19, 0xb2, 0x46, 0xce, 0xe9, 0x8e, 0x9b, 0x12, 0xe9,
+                0x19, 0x7d
+            ]
+        );
+        // a full block of padding is appended
+        assert_eq!(encrypted.len(), 48);
+    }
+
+    #[test]
+    fn test_revision_6() {
+        use crypto::aessafe::AesSafe256Decryptor;
+        use crypto::symmetriccipher::BlockDecryptor;
+
+        let mut encryption = Encryption::new(
+            "user",
+            "owner",
+            Permissions::PRINT,
+            EncryptionMethod::Aes256,
+        );
+        encryption.encrypt_metadata = false;
+        let handler = SecurityHandler::new(&encryption, &[0; 16]);
+        let dictionary = handler.dictionary();
+        assert_eq!((dictionary.v, dictionary.r), (5, 6));
+        assert_eq!(dictionary.u.0.len(), 48);
+        assert_eq!(dictionary.o.0.len(), 48);
+
+        // the user password unlocks the file encryption key
+        let user_key = hash_r6(b"user", &dictionary.u.0[40..48], &[]);
+        let mut key = [0; 32];
+        let ue = &dictionary.ue.as_ref().unwrap().0;
+        let decryptor = AesSafe256Decryptor::new(&user_key);
+        decryptor.decrypt_block(&ue[..16], &mut key[..16]);
+        decryptor.decrypt_block(&ue[16..], &mut key[16..]);
+        for (i, byte) in key[16..].iter_mut().enumerate() {
+            *byte ^= ue[i];
+        }
+        assert_eq!(&key[..], &handler.key[..]);
+        assert_eq!(
+            hash_r6(b"user", &dictionary.u.0[32..40], &[]),
+            dictionary.u.0[..32]
+        );
+
+        let mut perms = [0; 16];
+        AesSafe256Decryptor::new(&key)
+            .decrypt_block(&dictionary.perms.as_ref().unwrap().0, &mut perms);
+        assert_eq!(&perms[..4], &(dictionary.p as i32).to_le_bytes());
+        assert_eq!(&perms[8..12], b"Fadb");
+    }
+
+    #[test]
+    fn test_permissions() {
+        assert_eq!(Permissions::empty().p_value(), -3904);
+        assert_eq!(Permissions::all().p_value(), -4);
+    }
+}
@@ -0,0 +1,168 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! The error type returned by all fallible operations of this crate.
+
+use std::fmt;
+use std::io;
+use std::panic::Location;
+
+use crate::object::RawIndirectReference;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Writing the output failed.
+    Io(io::Error),
+    /// The written objects do not form a valid document, e.g. because an object that the
+    /// document depends on is missing or has the wrong type.
+    InvalidObjectGraph(String),
+    /// An indirect reference does not point to any object.
+    UnresolvedReference(RawIndirectReference),
+    /// The document was finished although indirect references were created for objects that
+    /// were never written.
+    DanglingReferences(Vec<DanglingReference>),
+    /// A value cannot be represented in the output, e.g. a string that is not valid in the
+    /// requested encoding or a value the serializer does not support.
+    Encoding(String),
+    /// An existing PDF file could not be parsed.
+    Parse(String),
+    /// The library was used in a way that is not allowed, e.g. a feature was requested that
+    /// the document's version does not support or an operation was called too late.
+    InvalidInput(String),
+    /// The document would violate the rules of the standard it claims to conform to, e.g.
+    /// PDF/A. `object` is the offending object, if it has already been created.
+    Conformance {
+        object: Option<RawIndirectReference>,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            Error::InvalidObjectGraph(msg) => write!(f, "invalid object graph: {}", msg),
+            Error::UnresolvedReference(reference) => write!(
+                f,
+                "unresolved reference to object {} {}",
+                reference.0, reference.1
+            ),
+            Error::DanglingReferences(references) => {
+                write!(f, "objects were never written:")?;
+                for reference in references {
+                    write!(f, " {};", reference)?;
+                }
+                Ok(())
+            }
+            Error::Encoding(msg) => write!(f, "encoding error: {}", msg),
+            Error::Parse(msg) => write!(f, "parse error: {}", msg),
+            Error::InvalidInput(msg) => write!(f, "invalid input: {}", msg),
+            Error::Conformance {
+                object: Some(object),
+                message,
+            } => write!(
+                f,
+                "conformance violation in object {} {}: {}",
+                object.0, object.1, message
+            ),
+            Error::Conformance {
+                object: None,
+                message,
+            } => write!(f, "conformance violation: {}", message),
+        }
+    }
+}
+
+/// An indirect reference to an object that was never written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DanglingReference {
+    pub reference: RawIndirectReference,
+    /// The location of the call that created the reference, if it was created by the
+    /// document context.
+    pub created_at: Option<&'static Location<'static>>,
+    /// The objects the reference was written into. Only collected if
+    /// `DocumentContext::check_references` is enabled.
+    pub referenced_by: Vec<RawIndirectReference>,
+}
+
+impl fmt::Display for DanglingReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "object {} {}", self.reference.0, self.reference.1)?;
+        if let Some(location) = self.created_at {
+            write!(f, " created at {}", location)?;
+        }
+        for (index, object) in self.referenced_by.iter().enumerate() {
+            let separator = if index == 0 { " referenced by" } else { "," };
+            write!(f, "{} {} {}", separator, object.0, object.1)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    /// Errors that were passed through an `io::Error` by `From<Error> for io::Error` are
+    /// unwrapped again.
+    fn from(err: io::Error) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            let kind = err.kind();
+            return match err.into_inner().map(|inner| inner.downcast::<Error>()) {
+                Some(Ok(inner)) => *inner,
+                Some(Err(inner)) => Error::Io(io::Error::new(kind, inner)),
+                None => Error::Io(io::Error::from(kind)),
+            };
+        }
+        Error::Io(err)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(err) => err,
+            Error::InvalidInput(_) => io::Error::new(io::ErrorKind::InvalidInput, err),
+            _ => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_io_round_trip() {
+        let err = io::Error::from(Error::UnresolvedReference(RawIndirectReference(3, 0)));
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        match Error::from(err) {
+            Error::UnresolvedReference(reference) => {
+                assert_eq!(reference, RawIndirectReference(3, 0))
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+
+        let err = Error::from(io::Error::from(io::ErrorKind::WriteZero));
+        assert!(matches!(err, Error::Io(ref inner) if inner.kind() == io::ErrorKind::WriteZero));
+    }
+}
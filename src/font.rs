@@ -46,15 +46,10 @@ impl std::ops::Sub for FontUnit {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PdfFormat)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, PdfFormat)]
 pub enum FontType {
     Type0,
+    #[default]
     Type1,
     TrueType,
 }
-
-impl Default for FontType {
-    fn default() -> Self {
-        FontType::Type1
-    }
-}
@@ -1,6 +1,5 @@
 //! Contains the builtin PDF fonts
 
-use std::io::Result;
 
 use lemon_pdf_derive::PdfFormat;
 
@@ -10,6 +9,7 @@ use super::{
 };
 use crate::document::DocumentContext;
 use crate::object::{IndirectReference};
+use crate::{Error, Result};
 
 #[derive(Debug, Copy, Clone)]
 pub struct BuiltInFontMetrics {
@@ -92,8 +92,19 @@ impl SimpleFont {
 }
 
 impl BuiltInFont {
+    /// Create the font dictionary of the standard font. The font program is not embedded, so
+    /// this fails for PDF/A documents.
     pub fn font(self, context: &mut DocumentContext) -> Result<SimpleFont> {
         let metrics = self.metrics();
+        if context.pdfa_conformance().is_some() {
+            return Err(Error::Conformance {
+                object: None,
+                message: format!(
+                    "the standard font {} is not embedded",
+                    metrics.font_name
+                ),
+            });
+        }
 
         let flags = match metrics.family_name {
             "Helvetica" => FontFlags::NONSYMBOLIC,
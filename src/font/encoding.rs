@@ -51,17 +51,70 @@ impl Default for EncodingEntry {
 }
 
 impl EncodingEntry {
-    pub fn encode(&self, char_name: &str) -> Option<u8> {
+    /// The predefined encoding the character codes are looked up in if they are not
+    /// redefined by the differences.
+    fn base_encoding(&self) -> PredefinedEncoding {
         match self {
-            EncodingEntry::Predefined(encoding) => encoding.encode(char_name),
-            _ => unimplemented!(),
+            EncodingEntry::Predefined(encoding) => *encoding,
+            EncodingEntry::Custom(encoding) => encoding
+                .base_encoding
+                .unwrap_or(PredefinedEncoding::StandardEncoding),
         }
     }
 
-    pub fn decode(&self, code: u8) -> Option<&'static str> {
-        match self {
-            EncodingEntry::Predefined(encoding) => encoding.decode(code),
-            _ => unimplemented!(),
+    /// The character codes redefined by the differences of a custom encoding, together with
+    /// their character names. `None` if the differences are an indirect object, which cannot
+    /// be looked up.
+    fn differences(&self) -> Option<Vec<(u8, &str)>> {
+        let differences = match self {
+            EncodingEntry::Custom(Encoding {
+                differences: Some(differences),
+                ..
+            }) => differences,
+            _ => return Some(Vec::new()),
+        };
+        let differences = match differences {
+            Object::Direct(differences) => differences,
+            Object::Indirect(_) => return None,
+        };
+        let mut code = 0;
+        let mut result = Vec::new();
+        for difference in differences {
+            match difference {
+                Difference::Code(first_code) => code = *first_code,
+                Difference::Name(name) => {
+                    if let Ok(code) = u8::try_from(code) {
+                        result.push((code, name.as_str()));
+                    }
+                    code = code.saturating_add(1);
+                }
+            }
+        }
+        Some(result)
+    }
+
+    pub fn encode(&self, char_name: &str) -> Option<u8> {
+        let differences = self.differences()?;
+        // a later difference for the same code takes precedence
+        let mut redefined = Vec::new();
+        for &(code, name) in differences.iter().rev() {
+            if !redefined.contains(&code) {
+                if name == char_name {
+                    return Some(code);
+                }
+                redefined.push(code);
+            }
+        }
+        self.base_encoding()
+            .encode(char_name)
+            .filter(|code| !redefined.contains(code))
+    }
+
+    pub fn decode(&self, code: u8) -> Option<&str> {
+        let differences = self.differences()?;
+        match differences.iter().rev().find(|&&(redefined, _)| redefined == code) {
+            Some(&(_, name)) => Some(name),
+            None => self.base_encoding().decode(code),
         }
     }
 }
@@ -83,7 +136,15 @@ pub struct Encoding {
     #[skip_if("Option::is_none")]
     pub base_encoding: Option<PredefinedEncoding>,
     #[skip_if("Option::is_none")]
-    pub differences: Option<Object<Vec<usize>>>,
+    pub differences: Option<Object<Vec<Difference>>>,
+}
+
+/// An element of the /Differences array of an encoding. A character code is followed by the
+/// names of the characters it and the next codes stand for.
+#[derive(Debug, Clone, PartialEq, PdfFormat)]
+pub enum Difference {
+    Code(u32),
+    Name(String),
 }
 
 pub const EXPERT_ENCODING: [&str; 256] = [
@@ -1661,3 +1722,47 @@ pub const ZAPF_DINGBATS_ENCODING: [&str; 256] = [
     "", "a201", "a183", "a184", "a197", "a185", "a194", "a198", "a186", "a195", "a187", "a188",
     "a189", "a190", "a191", "",
 ];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::object::{Formatter, PdfFormat};
+
+    #[test]
+    fn test_differences() {
+        let name = |name: &str| Difference::Name(name.to_string());
+        let encoding = EncodingEntry::Custom(Encoding {
+            base_encoding: Some(PredefinedEncoding::WinAnsiEncoding),
+            differences: Some(Object::Direct(vec![
+                Difference::Code(65),
+                name("alpha"),
+                name("beta"),
+                Difference::Code(200),
+                name("A"),
+                Difference::Code(66),
+                name("gamma"),
+            ])),
+        });
+        assert_eq!(encoding.decode(65), Some("alpha"));
+        assert_eq!(encoding.decode(66), Some("gamma"));
+        assert_eq!(encoding.decode(200), Some("A"));
+        assert_eq!(encoding.decode(67), Some("C"));
+        assert_eq!(encoding.encode("alpha"), Some(65));
+        assert_eq!(encoding.encode("beta"), None);
+        assert_eq!(encoding.encode("gamma"), Some(66));
+        assert_eq!(encoding.encode("A"), Some(200));
+        // the code of Egrave in WinAnsiEncoding is redefined
+        assert_eq!(encoding.encode("Egrave"), None);
+        assert_eq!(encoding.encode("C"), Some(67));
+
+        let mut output = Vec::new();
+        encoding
+            .write(&mut Formatter::new(&mut output))
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let output: Vec<&str> = output.split_whitespace().collect();
+        assert!(output
+            .join(" ")
+            .contains("/Differences [ 65 /alpha /beta 200 /A 66 /gamma ]"));
+    }
+}
@@ -16,18 +16,13 @@ pub struct CompositeFont {
     pub to_unicode: Option<IndirectReference<Stream>>,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, PdfFormat)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, PdfFormat)]
 pub enum CIDFontType {
+    #[default]
     CIDFontType0,
     CIDFontType2,
 }
 
-impl Default for CIDFontType {
-    fn default() -> Self {
-        CIDFontType::CIDFontType0
-    }
-}
-
 #[derive(Debug, Clone, PartialEq, PdfFormat)]
 pub enum MetricsEntry {
     Range {
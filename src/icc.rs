@@ -0,0 +1,178 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! A generated sRGB ICC profile, used as the output intent of PDF/A documents.
+
+/// The D50 illuminant of the profile connection space.
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+/// The D65 white point of sRGB.
+const D65: [f64; 3] = [0.9505, 1.0, 1.089];
+
+/// The sRGB primaries adapted to D50 with the Bradford transform.
+const PRIMARIES: [[f64; 3]; 3] = [
+    [0.436_074_7, 0.222_504_5, 0.013_932_2],
+    [0.385_064_9, 0.716_878_6, 0.097_104_5],
+    [0.143_080_4, 0.060_616_9, 0.714_173_3],
+];
+
+/// The number of entries of the tone reproduction curve.
+const CURVE_POINTS: usize = 1024;
+
+/// The description of the profile, which is also used as the output condition of PDF/A
+/// output intents.
+pub(crate) const SRGB_DESCRIPTION: &str = "sRGB IEC61966-2.1";
+
+fn s15_fixed16(value: f64) -> [u8; 4] {
+    ((value * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn xyz_tag(xyz: [f64; 3]) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    for &component in &xyz {
+        tag.extend_from_slice(&s15_fixed16(component));
+    }
+    tag
+}
+
+/// A `textDescriptionType` tag with an ASCII description only.
+fn description_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"desc\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    // empty Unicode and ScriptCode descriptions
+    tag.extend_from_slice(&[0; 8 + 3 + 67]);
+    tag
+}
+
+fn text_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"text\0\0\0\0".to_vec();
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag
+}
+
+/// The sRGB tone reproduction curve as a table.
+fn curve_tag() -> Vec<u8> {
+    let mut tag = b"curv\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(CURVE_POINTS as u32).to_be_bytes());
+    for index in 0..CURVE_POINTS {
+        let value = index as f64 / (CURVE_POINTS - 1) as f64;
+        let linear = if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        };
+        tag.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+    tag
+}
+
+/// An ICC version 2 display profile for the sRGB color space.
+pub(crate) fn srgb_profile() -> Vec<u8> {
+    let blobs = [
+        description_tag(SRGB_DESCRIPTION),
+        text_tag("No copyright, use freely"),
+        xyz_tag(D65),
+        xyz_tag(PRIMARIES[0]),
+        xyz_tag(PRIMARIES[1]),
+        xyz_tag(PRIMARIES[2]),
+        curve_tag(),
+    ];
+    // the tag signatures with the index of their data, the three curves share theirs
+    let tags: [(&[u8; 4], usize); 9] = [
+        (b"desc", 0),
+        (b"cprt", 1),
+        (b"wtpt", 2),
+        (b"rXYZ", 3),
+        (b"gXYZ", 4),
+        (b"bXYZ", 5),
+        (b"rTRC", 6),
+        (b"gTRC", 6),
+        (b"bTRC", 6),
+    ];
+
+    let data_offset = 128 + 4 + 12 * tags.len();
+    let mut data = Vec::new();
+    let mut offsets = Vec::new();
+    for blob in &blobs {
+        offsets.push((data_offset + data.len()) as u32);
+        data.extend_from_slice(blob);
+        // tags are aligned to four bytes
+        data.resize((data.len() + 3) & !3, 0);
+    }
+    let size = (data_offset + data.len()) as u32;
+
+    let mut profile = Vec::with_capacity(size as usize);
+    profile.extend_from_slice(&size.to_be_bytes());
+    profile.extend_from_slice(&[0; 4]); // preferred CMM
+    profile.extend_from_slice(&[2, 0x10, 0, 0]); // version 2.1
+    profile.extend_from_slice(b"mntrRGB XYZ ");
+    // creation date 2020-01-01 00:00:00
+    for &field in &[2020u16, 1, 1, 0, 0, 0] {
+        profile.extend_from_slice(&field.to_be_bytes());
+    }
+    profile.extend_from_slice(b"acsp");
+    // platform, flags, manufacturer, model, attributes and rendering intent
+    profile.extend_from_slice(&[0; 28]);
+    for &component in &D50 {
+        profile.extend_from_slice(&s15_fixed16(component));
+    }
+    profile.resize(128, 0);
+
+    profile.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    for &(signature, blob) in &tags {
+        profile.extend_from_slice(signature);
+        profile.extend_from_slice(&offsets[blob].to_be_bytes());
+        profile.extend_from_slice(&(blobs[blob].len() as u32).to_be_bytes());
+    }
+    profile.extend_from_slice(&data);
+    profile
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_srgb_profile() {
+        let profile = srgb_profile();
+        let read_u32 = |pos: usize| {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(&profile[pos..pos + 4]);
+            u32::from_be_bytes(bytes) as usize
+        };
+        assert_eq!(read_u32(0), profile.len());
+        assert_eq!(&profile[36..40], b"acsp");
+        assert_eq!(read_u32(128), 9);
+        for index in 0..9 {
+            let entry = 132 + 12 * index;
+            let (offset, size) = (read_u32(entry + 4), read_u32(entry + 8));
+            assert_eq!(offset % 4, 0);
+            assert!(offset + size <= profile.len());
+            let expected_type: &[u8] = match &profile[entry..entry + 4] {
+                b"desc" => b"desc",
+                b"cprt" => b"text",
+                b"rTRC" | b"gTRC" | b"bTRC" => b"curv",
+                _ => b"XYZ ",
+            };
+            assert_eq!(&profile[offset..offset + 4], expected_type);
+        }
+        // the curve ends at 1.0
+        let curve = read_u32(132 + 12 * 8 + 4);
+        let end = curve + 12 + 2 * (CURVE_POINTS - 1);
+        assert_eq!(&profile[end..end + 2], &[0xFF, 0xFF]);
+    }
+}
@@ -0,0 +1,150 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! The document information dictionary.
+
+use std::collections::BTreeMap;
+use std::io::Result;
+
+use crate as lemon_pdf;
+use lemon_pdf_derive::PdfFormat;
+
+use crate::date::PdfDate;
+use crate::object::{text_string, Formatter, PdfFormat};
+
+/// Whether the document has been modified to include trapping information.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PdfFormat)]
+pub enum Trapped {
+    True,
+    False,
+    Unknown,
+}
+
+/// Metadata of the document. Text is encoded in PDFDocEncoding or UTF-16BE automatically.
+///
+/// In PDF 2.0, only `creation_date` and `mod_date` are not deprecated. The other entries
+/// should be given as XMP metadata instead.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DocumentInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    /// The application that created the original document the PDF was converted from.
+    pub creator: Option<String>,
+    /// The application that produced the PDF.
+    pub producer: Option<String>,
+    pub creation_date: Option<PdfDate>,
+    pub mod_date: Option<PdfDate>,
+    pub trapped: Option<Trapped>,
+    /// Additional entries with text values. Keys that collide with the entries above are
+    /// ignored.
+    pub custom: BTreeMap<String, String>,
+}
+
+/// The keys of the entries with a field in `DocumentInfo`.
+const STANDARD_KEYS: [&str; 9] = [
+    "Title",
+    "Author",
+    "Subject",
+    "Keywords",
+    "Creator",
+    "Producer",
+    "CreationDate",
+    "ModDate",
+    "Trapped",
+];
+
+impl DocumentInfo {
+    fn text_entries(&self) -> impl Iterator<Item = (&str, &String)> {
+        let standard = [
+            ("Title", &self.title),
+            ("Author", &self.author),
+            ("Subject", &self.subject),
+            ("Keywords", &self.keywords),
+            ("Creator", &self.creator),
+            ("Producer", &self.producer),
+        ];
+        let custom = self
+            .custom
+            .iter()
+            .filter(|(key, _)| !STANDARD_KEYS.contains(&key.as_str()))
+            .map(|(key, value)| (key.as_str(), value));
+        IntoIterator::into_iter(standard)
+            .filter_map(|(key, value)| value.as_ref().map(|value| (key, value)))
+            .chain(custom)
+    }
+
+    /// Returns `true` if no entry is set.
+    pub fn is_empty(&self) -> bool {
+        self.text_entries().next().is_none()
+            && self.creation_date.is_none()
+            && self.mod_date.is_none()
+            && self.trapped.is_none()
+    }
+
+    /// Returns `true` if entries other than the dates are set, which are deprecated in
+    /// PDF 2.0.
+    pub(crate) fn has_deprecated_entries(&self) -> bool {
+        self.text_entries().next().is_some() || self.trapped.is_some()
+    }
+}
+
+impl PdfFormat for DocumentInfo {
+    fn write(&self, f: &mut Formatter) -> Result<()> {
+        let mut dictionary = f.format_dictionary();
+        for (key, value) in self.text_entries() {
+            dictionary = dictionary.key_value(&key, &text_string(value));
+        }
+        if let Some(creation_date) = &self.creation_date {
+            dictionary = dictionary.key_value(&"CreationDate", creation_date);
+        }
+        if let Some(mod_date) = &self.mod_date {
+            dictionary = dictionary.key_value(&"ModDate", mod_date);
+        }
+        if let Some(trapped) = &self.trapped {
+            dictionary = dictionary.key_value(&"Trapped", trapped);
+        }
+        dictionary.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_document_info() {
+        let mut info = DocumentInfo::default();
+        assert!(info.is_empty());
+        info.mod_date = Some(PdfDate::new(2020, 1, 2));
+        assert!(!info.is_empty());
+        assert!(!info.has_deprecated_entries());
+
+        info.title = Some("Résumé".to_string());
+        info.author = Some("李白".to_string());
+        info.trapped = Some(Trapped::False);
+        info.custom
+            .insert("Company Name".to_string(), "ACME".to_string());
+        info.custom
+            .insert("Title".to_string(), "ignored".to_string());
+        let mut output = Vec::new();
+        info.write(&mut Formatter::new(&mut output)).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<< /Title (R\\351sum\\351)\n/Author (\\376\\377gNv})\n\
+             /Company#20Name (ACME)\n/ModDate (D:20200102000000Z)\n/Trapped /False\n>>"
+        );
+    }
+}
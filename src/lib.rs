@@ -17,23 +17,51 @@
 extern crate derive_more;
 
 pub mod array;
+#[cfg(feature = "tokio")]
+mod async_document;
+pub mod attachment;
 pub mod content;
 mod crossref;
+pub mod date;
 mod deserializer;
+pub mod destination;
 pub mod dictionary;
 mod document;
+pub mod encryption;
+mod error;
 pub mod font;
+mod icc;
+pub mod info;
+pub mod name_tree;
+pub mod number_tree;
 pub mod object;
+mod object_stream;
+pub mod optional_content;
+pub mod outline;
+pub mod page_label;
 pub mod pagetree;
+pub mod paper;
+pub mod pdfa;
+pub mod pdfua;
+mod reader;
 mod serializer;
+pub mod signature;
 pub mod stream;
 pub mod structure_tree;
 mod trailer;
+pub mod units;
+pub mod viewer;
+pub mod xmp;
 
 pub use self::content::Pt;
+pub use self::error::{DanglingReference, Error, Result};
+pub use self::info::DocumentInfo;
 pub use self::object::PdfFormat;
 pub use self::pagetree::Page;
+pub use self::units::{Cm, Inch, Mm};
 pub use crate::document::*;
+#[cfg(feature = "tokio")]
+pub use crate::async_document::AsyncDocumentContext;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,290 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! Name trees, which map strings to objects and can be split into several nodes, and the
+//! nodes they share with number trees.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use crate as lemon_pdf;
+use lemon_pdf_derive::PdfFormat;
+
+use crate::attachment::FileSpecification;
+use crate::destination::Destination;
+use crate::object::{Formatter, IndirectReference, PdfFormat};
+use crate::{DocumentContext, Result};
+
+/// The maximum number of entries of a leaf node.
+const MAX_ENTRIES: usize = 64;
+
+/// The maximum number of kids of an intermediate node.
+const MAX_KIDS: usize = 32;
+
+/// A map from byte strings to objects, which is written as a balanced name tree.
+///
+/// The entries are sorted by the bytes of their names. Large trees are split into several
+/// nodes, every node but the root has the `/Limits` of the names below it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameTree<T> {
+    entries: BTreeMap<Vec<u8>, T>,
+}
+
+impl<T> Default for NameTree<T> {
+    fn default() -> Self {
+        NameTree {
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: PdfFormat> NameTree<T> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Insert `value` under `name`. Returns the previous value of `name`.
+    pub fn insert(&mut self, name: impl Into<Vec<u8>>, value: T) -> Option<T> {
+        self.entries.insert(name.into(), value)
+    }
+
+    pub fn get(&self, name: &[u8]) -> Option<&T> {
+        self.entries.get(name)
+    }
+
+    pub fn remove(&mut self, name: &[u8]) -> Option<T> {
+        self.entries.remove(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over the entries, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &T)> {
+        self.entries
+            .iter()
+            .map(|(name, value)| (name.as_slice(), value))
+    }
+
+    /// Write the nodes of the tree and return its root.
+    pub fn write_to_context(
+        self,
+        context: &mut DocumentContext,
+    ) -> Result<IndirectReference<NameTreeNode<T>>> {
+        write_tree(context, self.entries, "Names")
+    }
+}
+
+/// A node of a name tree.
+pub type NameTreeNode<T> = TreeNode<Vec<u8>, T>;
+
+/// A node of a name tree or a number tree with keys of type `K`.
+#[derive(Debug)]
+pub enum TreeNode<K, T> {
+    Intermediate {
+        kids: Vec<IndirectReference<TreeNode<K, T>>>,
+        /// The first and last key below the node. `None` for the root.
+        limits: Option<[K; 2]>,
+    },
+    Leaf {
+        /// `Names` for name trees and `Nums` for number trees.
+        entries_key: &'static str,
+        entries: Vec<(K, T)>,
+        /// The first and last key of the node. `None` for the root.
+        limits: Option<[K; 2]>,
+    },
+}
+
+/// The `/Names` or `/Nums` array of a leaf, which alternates between keys and values.
+#[derive(Debug)]
+struct EntriesArray<'a, K, T>(&'a [(K, T)]);
+
+impl<'a, K: PdfFormat, T: PdfFormat> PdfFormat for EntriesArray<'a, K, T> {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        let mut array = f.format_array();
+        for (key, value) in self.0 {
+            array = array.value(key).value(value);
+        }
+        array.finish()
+    }
+}
+
+impl<K: PdfFormat, T: PdfFormat> PdfFormat for TreeNode<K, T> {
+    fn write(&self, f: &mut Formatter) -> io::Result<()> {
+        let (dictionary, limits) = match self {
+            TreeNode::Intermediate { kids, limits } => {
+                (f.format_dictionary().key_value(&"Kids", kids), limits)
+            }
+            TreeNode::Leaf {
+                entries_key,
+                entries,
+                limits,
+            } => (
+                f.format_dictionary()
+                    .key_value(entries_key, &EntriesArray(entries)),
+                limits,
+            ),
+        };
+        match limits {
+            Some(limits) => dictionary.key_value(&"Limits", limits).finish(),
+            None => dictionary.finish(),
+        }
+    }
+}
+
+/// Write the nodes of a tree with the sorted `entries` and return its root. The entries of
+/// the leaves are written to `entries_key`.
+///
+/// If there are more than `MAX_ENTRIES` entries, they are split into leaves of `MAX_ENTRIES`
+/// entries, which are arranged below the root with `MAX_KIDS` kids per node.
+pub(crate) fn write_tree<K: PdfFormat + Clone, T: PdfFormat>(
+    context: &mut DocumentContext,
+    entries: BTreeMap<K, T>,
+    entries_key: &'static str,
+) -> Result<IndirectReference<TreeNode<K, T>>> {
+    if entries.len() <= MAX_ENTRIES {
+        let root = TreeNode::Leaf {
+            entries_key,
+            entries: entries.into_iter().collect(),
+            limits: None,
+        };
+        return context.write_object_fn(|_, _| Ok(root));
+    }
+
+    // the written nodes of the current level with the first and last key below them
+    let mut level = Vec::new();
+    let mut entries = entries.into_iter().peekable();
+    while entries.peek().is_some() {
+        let leaf_entries: Vec<_> = entries.by_ref().take(MAX_ENTRIES).collect();
+        let limits = match (leaf_entries.first(), leaf_entries.last()) {
+            (Some((first, _)), Some((last, _))) => [first.clone(), last.clone()],
+            _ => break,
+        };
+        let leaf = TreeNode::Leaf {
+            entries_key,
+            entries: leaf_entries,
+            limits: Some(limits.clone()),
+        };
+        level.push((context.write_object_fn(|_, _| Ok(leaf))?, limits));
+    }
+
+    // group the nodes until there are few enough to be kids of the root
+    while level.len() > MAX_KIDS {
+        let mut next_level = Vec::new();
+        let mut nodes = level.into_iter().peekable();
+        while nodes.peek().is_some() {
+            let kids: Vec<_> = nodes.by_ref().take(MAX_KIDS).collect();
+            let limits = match (kids.first(), kids.last()) {
+                (Some((_, [first, _])), Some((_, [_, last]))) => [first.clone(), last.clone()],
+                _ => break,
+            };
+            let node = TreeNode::Intermediate {
+                kids: kids.into_iter().map(|(kid, _)| kid).collect(),
+                limits: Some(limits.clone()),
+            };
+            next_level.push((context.write_object_fn(|_, _| Ok(node))?, limits));
+        }
+        level = next_level;
+    }
+
+    let root = TreeNode::Intermediate {
+        kids: level.into_iter().map(|(kid, _)| kid).collect(),
+        limits: None,
+    };
+    context.write_object_fn(|_, _| Ok(root))
+}
+
+/// The name dictionary of the document catalog.
+#[derive(Debug, Default, PdfFormat)]
+#[omit_type(true)]
+pub(crate) struct NameDictionary {
+    #[skip_if("Option::is_none")]
+    pub dests: Option<IndirectReference<NameTreeNode<Destination>>>,
+    #[skip_if("Option::is_none")]
+    pub embedded_files: Option<IndirectReference<NameTreeNode<IndirectReference<FileSpecification>>>>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::object::RawIndirectReference;
+    use crate::reader::{ExistingDocument, RawObject};
+    use crate::{Page, Version};
+
+    /// Check the name tree node `node` and return the names below it.
+    fn check_node(
+        document: &ExistingDocument,
+        node: RawIndirectReference,
+        is_root: bool,
+    ) -> Vec<RawObject> {
+        let dictionary = document.resolve_dictionary(node).unwrap();
+        let names = match dictionary.get("Kids") {
+            Some(kids) => {
+                let kids = kids.as_array().unwrap();
+                assert!(kids.len() <= MAX_KIDS);
+                kids.iter()
+                    .flat_map(|kid| check_node(document, kid.as_reference().unwrap(), false))
+                    .collect()
+            }
+            None => {
+                let names = dictionary.get("Names").unwrap().as_array().unwrap();
+                assert!(names.len() <= 2 * MAX_ENTRIES);
+                names.into_iter().step_by(2).collect::<Vec<_>>()
+            }
+        };
+        match dictionary.get("Limits") {
+            Some(limits) => {
+                assert!(!is_root);
+                let limits = limits.as_array().unwrap();
+                assert_eq!(limits.first(), names.first());
+                assert_eq!(limits.last(), names.last());
+            }
+            None => assert!(is_root),
+        }
+        names
+    }
+
+    #[test]
+    fn test_name_tree() {
+        for &count in &[1, MAX_ENTRIES, MAX_ENTRIES * MAX_KIDS * 2 + 1] {
+            let mut output = Vec::new();
+            let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_7).unwrap();
+            let page = context.add_page(Page::new()).unwrap();
+            for i in (0..count).rev() {
+                let destination = Destination::Fit(page);
+                context
+                    .named_destinations
+                    .insert(format!("dest{:06}", i), destination);
+            }
+            context.finish().unwrap();
+
+            let document = ExistingDocument::parse(&output).unwrap();
+            let catalog = document
+                .resolve_dictionary(document.trailer.get_reference("Root").unwrap())
+                .unwrap();
+            let names = catalog.get("Names").unwrap().as_dictionary().unwrap();
+            let root = names.get_reference("Dests").unwrap();
+            let names = check_node(&document, root, true);
+            let expected: Vec<_> = (0..count)
+                .map(|i| RawObject(format!("(dest{:06})", i).into_bytes()))
+                .collect();
+            assert_eq!(names, expected);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! Number trees, which map integers to objects and can be split into several nodes.
+
+use std::collections::BTreeMap;
+
+use crate::name_tree::{write_tree, TreeNode};
+use crate::object::{IndirectReference, PdfFormat};
+use crate::{DocumentContext, Result};
+
+/// A map from integers to objects, which is written as a balanced number tree.
+///
+/// The entries are sorted by their keys. Large trees are split into several nodes, every node
+/// but the root has the `/Limits` of the keys below it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberTree<T> {
+    entries: BTreeMap<i64, T>,
+}
+
+impl<T> Default for NumberTree<T> {
+    fn default() -> Self {
+        NumberTree {
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: PdfFormat> NumberTree<T> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Insert `value` under `key`. Returns the previous value of `key`.
+    pub fn insert(&mut self, key: i64, value: T) -> Option<T> {
+        self.entries.insert(key, value)
+    }
+
+    pub fn get(&self, key: i64) -> Option<&T> {
+        self.entries.get(&key)
+    }
+
+    pub fn remove(&mut self, key: i64) -> Option<T> {
+        self.entries.remove(&key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over the entries, sorted by key.
+    pub fn iter(&self) -> impl Iterator<Item = (i64, &T)> {
+        self.entries.iter().map(|(&key, value)| (key, value))
+    }
+
+    /// Write the nodes of the tree and return its root.
+    pub fn write_to_context(
+        self,
+        context: &mut DocumentContext,
+    ) -> Result<IndirectReference<NumberTreeNode<T>>> {
+        write_tree(context, self.entries, "Nums")
+    }
+}
+
+/// A node of a number tree.
+pub type NumberTreeNode<T> = TreeNode<i64, T>;
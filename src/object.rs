@@ -20,15 +20,47 @@ use lemon_pdf_derive::PdfFormat;
 
 use crate::array::Array;
 use crate::dictionary::Dictionary;
+use crate::encryption::ObjectCipher;
 use crate::stream::Stream;
 use serde::Serialize;
 
 #[allow(missing_debug_implementations)]
 pub struct Formatter<'a> {
     pub(crate) writer: &'a mut dyn Write,
+    /// Encrypts strings and streams if the document is encrypted.
+    pub(crate) cipher: Option<&'a ObjectCipher>,
+    /// If set, every indirect reference that is written is collected here.
+    pub(crate) references: Option<&'a mut Vec<RawIndirectReference>>,
+    /// Set when a stream is written. Streams are always indirect objects, so the object being
+    /// written is a stream itself.
+    pub(crate) wrote_stream: bool,
 }
 
 impl<'a> Formatter<'a> {
+    pub(crate) fn new(writer: &'a mut dyn Write) -> Self {
+        Formatter {
+            writer,
+            cipher: None,
+            references: None,
+            wrote_stream: false,
+        }
+    }
+
+    pub(crate) fn with_cipher(writer: &'a mut dyn Write, cipher: Option<&'a ObjectCipher>) -> Self {
+        Formatter {
+            writer,
+            cipher,
+            references: None,
+            wrote_stream: false,
+        }
+    }
+
+    fn add_reference(&mut self, reference: RawIndirectReference) {
+        if let Some(references) = &mut self.references {
+            references.push(reference);
+        }
+    }
+
     pub fn format_dictionary<'b>(&'b mut self) -> DictionaryFormatter<'a, 'b>
     where
         'a: 'b,
@@ -122,14 +154,23 @@ pub trait PdfFormat: std::fmt::Debug {
     fn write(&self, output: &mut Formatter) -> Result<()>;
 }
 
-impl<'a> PdfFormat for &'a str {
+impl PdfFormat for &str {
     fn write(&self, output: &mut Formatter) -> Result<()> {
-        // TODO: proper escaping
-        write!(output, "/{}", self)
+        write!(output, "/")?;
+        for &byte in self.as_bytes() {
+            match byte {
+                b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%' | b'#' => {
+                    write!(output, "#{:02X}", byte)?
+                }
+                b'!'..=b'~' => output.write_all(&[byte])?,
+                _ => write!(output, "#{:02X}", byte)?,
+            }
+        }
+        Ok(())
     }
 }
 
-impl<'a, T> PdfFormat for &'a T
+impl<T> PdfFormat for &T
 where
     T: PdfFormat,
 {
@@ -153,6 +194,68 @@ impl PdfFormat for String {
     }
 }
 
+/// Encode `text` as a PDF text string: in PDFDocEncoding if possible, otherwise as UTF-16BE
+/// with a byte order mark.
+pub(crate) fn text_string(text: &str) -> Vec<u8> {
+    match text.chars().map(pdf_doc_encode).collect::<Option<Vec<u8>>>() {
+        // a string starting with the byte order mark would be read as UTF-16
+        Some(bytes) if !bytes.starts_with(&[0xFE, 0xFF]) => bytes,
+        _ => utf16_text_string(text),
+    }
+}
+
+/// The code of `chr` in PDFDocEncoding.
+fn pdf_doc_encode(chr: char) -> Option<u8> {
+    let code = match chr {
+        '\t' | '\n' | '\r' | ' '..='~' => chr as u8,
+        '\u{2022}' => 0x80,
+        '\u{2020}' => 0x81,
+        '\u{2021}' => 0x82,
+        '\u{2026}' => 0x83,
+        '\u{2014}' => 0x84,
+        '\u{2013}' => 0x85,
+        '\u{0192}' => 0x86,
+        '\u{2044}' => 0x87,
+        '\u{2039}' => 0x88,
+        '\u{203A}' => 0x89,
+        '\u{2212}' => 0x8A,
+        '\u{2030}' => 0x8B,
+        '\u{201E}' => 0x8C,
+        '\u{201C}' => 0x8D,
+        '\u{201D}' => 0x8E,
+        '\u{2018}' => 0x8F,
+        '\u{2019}' => 0x90,
+        '\u{201A}' => 0x91,
+        '\u{2122}' => 0x92,
+        '\u{FB01}' => 0x93,
+        '\u{FB02}' => 0x94,
+        '\u{0141}' => 0x95,
+        '\u{0152}' => 0x96,
+        '\u{0160}' => 0x97,
+        '\u{0178}' => 0x98,
+        '\u{017D}' => 0x99,
+        '\u{0131}' => 0x9A,
+        '\u{0142}' => 0x9B,
+        '\u{0153}' => 0x9C,
+        '\u{0161}' => 0x9D,
+        '\u{017E}' => 0x9E,
+        '\u{20AC}' => 0xA0,
+        // the upper half of Latin-1 without the soft hyphen
+        '\u{A1}'..='\u{AC}' | '\u{AE}'..='\u{FF}' => chr as u8,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Encode `text` as a PDF text string in UTF-16BE with a byte order mark.
+pub(crate) fn utf16_text_string(text: &str) -> Vec<u8> {
+    let mut bytes = vec![0xFE, 0xFF];
+    for unit in text.encode_utf16() {
+        bytes.extend_from_slice(&unit.to_be_bytes());
+    }
+    bytes
+}
+
 pub trait WriteEscaped {
     fn write_escaped(&mut self, bytes: &[u8]) -> std::io::Result<()>;
     fn write_hex_escaped(&mut self, bytes: &[u8]) -> std::io::Result<()>;
@@ -169,7 +272,8 @@ impl<W: Write> WriteEscaped for W {
                 b'\n' => self.write_all(b"\\n")?,
                 b')' => self.write_all(b"\\)")?,
                 b'(' => self.write_all(b"\\(")?,
-                non_graphic if !byte.is_ascii_graphic() => write!(self, "\\d{:03o}", non_graphic)?,
+                b'\\' => self.write_all(b"\\\\")?,
+                non_graphic if !byte.is_ascii_graphic() => write!(self, "\\{:03o}", non_graphic)?,
                 other => self.write_all(&[other])?
             }
         }
@@ -184,11 +288,21 @@ impl<W: Write> WriteEscaped for W {
     }
 }
 
-impl<'a> PdfFormat for &'a [u8] {
+impl PdfFormat for &[u8] {
     fn write(&self, output: &mut Formatter) -> Result<()> {
-        write!(output, "(")?;
-        output.write_escaped(self)?;
-        write!(output, ")")
+        match output.cipher {
+            Some(cipher) => {
+                let encrypted = cipher.encrypt(self);
+                write!(output, "<")?;
+                output.write_hex_escaped(&encrypted)?;
+                write!(output, ">")
+            }
+            None => {
+                write!(output, "(")?;
+                output.write_escaped(self)?;
+                write!(output, ")")
+            }
+        }
     }
 }
 
@@ -216,6 +330,12 @@ impl PdfFormat for u32 {
     }
 }
 
+impl PdfFormat for u64 {
+    fn write(&self, output: &mut Formatter) -> Result<()> {
+        write!(output, "{}", self)
+    }
+}
+
 impl PdfFormat for usize {
     fn write(&self, output: &mut Formatter) -> Result<()> {
         write!(output, "{}", self)
@@ -228,6 +348,19 @@ impl PdfFormat for i64 {
     }
 }
 
+/// A byte string that is written in hexadecimal form and never encrypted, e.g. for binary
+/// data in the trailer or the encryption dictionary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HexString(pub Vec<u8>);
+
+impl PdfFormat for HexString {
+    fn write(&self, output: &mut Formatter) -> Result<()> {
+        write!(output, "<")?;
+        output.write_hex_escaped(&self.0)?;
+        write!(output, ">")
+    }
+}
+
 #[derive(Debug, From, PdfFormat)]
 pub enum Value {
     Null,
@@ -253,10 +386,17 @@ impl From<f32> for Value {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 #[serde(rename = "R")]
 pub struct RawIndirectReference(pub i64, pub i64);
 
+impl PdfFormat for RawIndirectReference {
+    fn write(&self, output: &mut Formatter) -> Result<()> {
+        output.add_reference(*self);
+        write!(output, "{} {} R", self.0, self.1)
+    }
+}
+
 impl<T> From<IndirectReference<T>> for RawIndirectReference {
     fn from(other: IndirectReference<T>) -> Self {
         RawIndirectReference(other.number(), other.generation())
@@ -271,7 +411,8 @@ impl<T> From<IndirectReference<T>> for RawIndirectReference {
 #[serde(into = "RawIndirectReference")]
 pub struct IndirectReference<T> {
     raw: RawIndirectReference,
-    _marker: PhantomData<T>,
+    // a reference is just a pair of numbers, so it is `Send` and `Sync` regardless of `T`
+    _marker: PhantomData<fn() -> T>,
 }
 
 impl<T> PartialEq for IndirectReference<T> {
@@ -282,6 +423,18 @@ impl<T> PartialEq for IndirectReference<T> {
 
 impl<T> Eq for IndirectReference<T> {}
 
+impl<T> PartialOrd for IndirectReference<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for IndirectReference<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.raw().cmp(&other.raw())
+    }
+}
+
 impl<T> Hash for IndirectReference<T> {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.raw.hash(state)
@@ -338,7 +491,7 @@ impl<T> IndirectReference<T> {
 
 impl<T> PdfFormat for IndirectReference<T> {
     fn write(&self, output: &mut Formatter) -> Result<()> {
-        write!(output, "{} {} R", self.number(), self.generation())
+        self.raw.write(output)
     }
 }
 
@@ -379,4 +532,30 @@ mod test {
         outp.write_hex_escaped(b"\nabc").unwrap();
         assert_eq!("0A616263", std::str::from_utf8(&outp).unwrap());
     }
+
+    #[test]
+    fn test_text_string() {
+        assert_eq!(text_string("abc"), b"abc");
+        assert_eq!(text_string("M\u{fc}ller \u{2013} 5\u{20ac}"), b"M\xfcller \x85 5\xa0");
+        assert_eq!(text_string("\u{4e2d}"), b"\xfe\xff\x4e\x2d");
+        assert_eq!(text_string("\u{fe}\u{ff}"), b"\xfe\xff\x00\xfe\x00\xff");
+
+        let mut outp = vec![];
+        outp.write_escaped(&utf16_text_string("a\\")).unwrap();
+        assert_eq!("\\376\\377\\000a\\000\\\\", std::str::from_utf8(&outp).unwrap());
+    }
+
+    #[test]
+    fn test_name() {
+        let mut output = Vec::new();
+        "Custom Key#1/(x)".write(&mut Formatter::new(&mut output)).unwrap();
+        assert_eq!(output, b"/Custom#20Key#231#2F#28x#29");
+    }
+
+    #[test]
+    fn test_large_integer() {
+        let mut output = Vec::new();
+        5_000_000_000u64.write(&mut Formatter::new(&mut output)).unwrap();
+        assert_eq!(output, b"5000000000");
+    }
 }
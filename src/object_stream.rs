@@ -0,0 +1,73 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+use std::io::Write;
+
+use crate::stream::{Stream, StreamEncoder, StreamFilter};
+use crate::Result;
+
+/// Collects non-stream objects that are to be packed into a compressed object stream
+/// (`/Type /ObjStm`).
+#[derive(Debug, Default)]
+pub(crate) struct ObjectStreamBuilder {
+    objects: Vec<(u32, Vec<u8>)>,
+}
+
+impl ObjectStreamBuilder {
+    /// The maximum number of objects that are put into a single object stream.
+    pub const MAX_OBJECTS: usize = 100;
+
+    /// Add the serialized object with number `number` and return its index within the stream.
+    pub fn push(&mut self, number: u32, bytes: Vec<u8>) -> usize {
+        self.objects.push((number, bytes));
+        self.objects.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Build the object stream from the collected objects and reset the builder.
+    ///
+    /// Returns the object numbers in the order in which they are stored in the stream.
+    pub fn finish(&mut self) -> Result<(Vec<u32>, Stream)> {
+        let objects = std::mem::take(&mut self.objects);
+
+        let mut header = Vec::new();
+        let mut offset = 0;
+        for (number, bytes) in &objects {
+            write!(header, "{} {} ", number, offset)?;
+            offset += bytes.len() + 1;
+        }
+        header.push(b'\n');
+
+        let mut encoder = StreamEncoder::new(Some(StreamFilter::Deflate));
+        encoder.write_all(&header)?;
+        for (_, bytes) in &objects {
+            encoder.write_all(bytes)?;
+            encoder.write_all(b"\n")?;
+        }
+
+        let mut stream = encoder.into_stream()?;
+        stream.add_key_value("Type".to_owned(), Box::new("ObjStm"));
+        stream.add_key_value("N".to_owned(), Box::new(objects.len()));
+        stream.add_key_value("First".to_owned(), Box::new(header.len()));
+
+        Ok((objects.into_iter().map(|(number, _)| number).collect(), stream))
+    }
+}
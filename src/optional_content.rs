@@ -0,0 +1,186 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! Optional content, i.e. layers of page content that the user can show or hide.
+
+use crate as lemon_pdf;
+use lemon_pdf_derive::PdfFormat;
+
+use crate::object::{text_string, IndirectReference};
+
+/// An optional content group, which is created with
+/// `DocumentContext::add_optional_content_group`.
+#[derive(Debug, PdfFormat)]
+#[rename("OCG")]
+pub struct OptionalContentGroup {
+    pub(crate) name: Vec<u8>,
+}
+
+/// An item of the list of groups that viewers show, see
+/// `DocumentContext::optional_content_order`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderItem {
+    /// A group and the items nested below it.
+    Group(IndirectReference<OptionalContentGroup>, Vec<OrderItem>),
+    /// A label that cannot be toggled itself and the items nested below it.
+    Label(String, Vec<OrderItem>),
+}
+
+/// An element of the `/Order` array of an optional content configuration.
+#[derive(Debug, PdfFormat)]
+pub(crate) enum OrderEntry {
+    Group(IndirectReference<OptionalContentGroup>),
+    /// The items nested below the previous group, or below the label in the first element.
+    Nested(Vec<OrderEntry>),
+    Label(Vec<u8>),
+}
+
+impl OrderEntry {
+    /// The entries of the `/Order` array for `items`.
+    pub fn from_items(items: Vec<OrderItem>) -> Vec<OrderEntry> {
+        let mut entries = Vec::new();
+        for item in items {
+            match item {
+                OrderItem::Group(group, children) => {
+                    entries.push(OrderEntry::Group(group));
+                    if !children.is_empty() {
+                        entries.push(OrderEntry::Nested(OrderEntry::from_items(children)));
+                    }
+                }
+                OrderItem::Label(label, children) => {
+                    let mut nested = vec![OrderEntry::Label(text_string(&label))];
+                    nested.extend(OrderEntry::from_items(children));
+                    entries.push(OrderEntry::Nested(nested));
+                }
+            }
+        }
+        entries
+    }
+}
+
+/// The optional content properties dictionary of the document catalog.
+#[derive(Debug, PdfFormat)]
+#[omit_type(true)]
+pub(crate) struct OptionalContentProperties {
+    #[rename("OCGs")]
+    pub groups: Vec<IndirectReference<OptionalContentGroup>>,
+    /// The configuration when the document is opened.
+    #[rename("D")]
+    pub default_configuration: OptionalContentConfiguration,
+}
+
+/// An optional content configuration dictionary.
+#[derive(Debug, PdfFormat)]
+#[omit_type(true)]
+pub(crate) struct OptionalContentConfiguration {
+    /// Required by PDF/A.
+    pub name: Vec<u8>,
+    /// The groups that are visible.
+    #[rename("ON")]
+    pub on: Vec<IndirectReference<OptionalContentGroup>>,
+    /// The groups that are hidden.
+    #[rename("OFF")]
+    pub off: Vec<IndirectReference<OptionalContentGroup>>,
+    pub order: Vec<OrderEntry>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::ExistingDocument;
+    use crate::{DocumentContext, Page, Pt, Version};
+
+    #[test]
+    fn test_optional_content() {
+        let mut output = Vec::new();
+        let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_4).unwrap();
+        let grid = context
+            .add_optional_content_group("Background grid", false)
+            .unwrap();
+        let dimensions = context
+            .add_optional_content_group("Dimensions", true)
+            .unwrap();
+        let annotations = context
+            .add_optional_content_group("Annotations", true)
+            .unwrap();
+        context.optional_content_order = vec![OrderItem::Label(
+            "Drawing".to_string(),
+            vec![
+                OrderItem::Group(dimensions, vec![OrderItem::Group(annotations, Vec::new())]),
+                OrderItem::Group(grid, Vec::new()),
+            ],
+        )];
+        let mut page = Page::new();
+        page.add_content(&mut context, None, |page| {
+            page.begin_optional_content(grid)?;
+            page.move_to(Pt(0.0), Pt(10.0))?;
+            page.line_to(Pt(100.0), Pt(10.0))?;
+            page.stroke_path()?;
+            page.end_marked_content()
+        })
+        .unwrap();
+        context.add_page(page).unwrap();
+        context.finish().unwrap();
+
+        let document = ExistingDocument::parse(&output).unwrap();
+        let catalog = document
+            .resolve_dictionary(document.trailer.get_reference("Root").unwrap())
+            .unwrap();
+        assert_eq!(catalog.get("Version").unwrap().0, b"/1.5");
+        let properties = catalog
+            .get("OCProperties")
+            .unwrap()
+            .as_dictionary()
+            .unwrap();
+        assert_eq!(properties.get("OCGs").unwrap().as_array().unwrap().len(), 3);
+        let configuration = properties.get("D").unwrap().as_dictionary().unwrap();
+        let references = |key| {
+            let array = configuration.get(key).unwrap().as_array().unwrap();
+            array
+                .iter()
+                .map(|value| value.as_reference().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(references("ON"), vec![dimensions.raw(), annotations.raw()]);
+        assert_eq!(references("OFF"), vec![grid.raw()]);
+        let order = configuration.get("Order").unwrap().as_array().unwrap();
+        let drawing = order[0].as_array().unwrap();
+        assert_eq!(drawing[0].0, b"(Drawing)");
+        assert_eq!(drawing[1].as_reference(), Some(dimensions.raw()));
+        let nested = drawing[2].as_array().unwrap();
+        assert_eq!(nested[0].as_reference(), Some(annotations.raw()));
+        assert_eq!(drawing[3].as_reference(), Some(grid.raw()));
+
+        let pages = document
+            .resolve_dictionary(catalog.get_reference("Pages").unwrap())
+            .unwrap();
+        let kids = pages.get("Kids").unwrap().as_array().unwrap();
+        let page = document
+            .resolve_dictionary(kids[0].as_reference().unwrap())
+            .unwrap();
+        let resources = page.get("Resources").unwrap().as_dictionary().unwrap();
+        let page_properties = resources
+            .get("Properties")
+            .unwrap()
+            .as_dictionary()
+            .unwrap();
+        assert_eq!(page_properties.get_reference("OC0"), Some(grid.raw()));
+        let contents = page.get("Contents").unwrap().as_array().unwrap();
+        let (_, content) = document
+            .resolve_stream(contents[0].as_reference().unwrap())
+            .unwrap();
+        assert!(content.starts_with(b"/OC /OC0 BDC "));
+        assert!(content.ends_with(b"EMC "));
+    }
+}
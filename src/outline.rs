@@ -0,0 +1,237 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! The document outline, a tree of bookmarks that lets the user navigate the document.
+
+use crate as lemon_pdf;
+use lemon_pdf_derive::PdfFormat;
+
+use crate::destination::Destination;
+use crate::object::{utf16_text_string, IndirectReference};
+use crate::{DocumentContext, Page, Result};
+
+/// An item of the document outline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineItem {
+    pub title: String,
+    /// Where the viewer jumps to when the item is activated.
+    pub destination: Option<Destination>,
+    pub children: Vec<OutlineItem>,
+    /// Whether the children are shown initially. Defaults to `false`.
+    pub open: bool,
+    /// The color of the title as red, green and blue components between 0.0 and 1.0.
+    pub color: Option<[f64; 3]>,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+impl OutlineItem {
+    /// Create an item that displays the whole `page`.
+    pub fn new(title: impl Into<String>, page: IndirectReference<Page>) -> Self {
+        OutlineItem {
+            title: title.into(),
+            destination: Some(Destination::Fit(page)),
+            children: Vec::new(),
+            open: false,
+            color: None,
+            bold: false,
+            italic: false,
+        }
+    }
+
+    /// The style flags of the `/F` entry.
+    fn flags(&self) -> Option<u32> {
+        let flags = u32::from(self.italic) | u32::from(self.bold) << 1;
+        Some(flags).filter(|&flags| flags != 0)
+    }
+}
+
+/// The outline dictionary, the root of the outline tree.
+#[derive(Debug, PdfFormat)]
+#[rename("Outlines")]
+pub(crate) struct Outlines {
+    first: IndirectReference<OutlineItemDictionary>,
+    last: IndirectReference<OutlineItemDictionary>,
+    /// The number of visible items at all levels.
+    count: i64,
+}
+
+#[derive(Debug, PdfFormat)]
+#[omit_type(true)]
+struct OutlineItemDictionary {
+    title: Vec<u8>,
+    /// The parent item or the outline dictionary.
+    parent: IndirectReference<()>,
+    #[skip_if("Option::is_none")]
+    prev: Option<IndirectReference<OutlineItemDictionary>>,
+    #[skip_if("Option::is_none")]
+    next: Option<IndirectReference<OutlineItemDictionary>>,
+    #[skip_if("Option::is_none")]
+    first: Option<IndirectReference<OutlineItemDictionary>>,
+    #[skip_if("Option::is_none")]
+    last: Option<IndirectReference<OutlineItemDictionary>>,
+    /// The number of visible descendants if the item is open, or the negated number of
+    /// descendants that would be visible if it were opened.
+    #[skip_if("Option::is_none")]
+    count: Option<i64>,
+    #[skip_if("Option::is_none")]
+    dest: Option<Destination>,
+    #[rename("C")]
+    #[skip_if("Option::is_none")]
+    color: Option<Vec<f64>>,
+    #[rename("F")]
+    #[skip_if("Option::is_none")]
+    flags: Option<u32>,
+}
+
+/// The number of items that are visible if their parent is open.
+fn visible_count(items: &[OutlineItem]) -> i64 {
+    items
+        .iter()
+        .map(|item| {
+            1 + if item.open {
+                visible_count(&item.children)
+            } else {
+                0
+            }
+        })
+        .sum()
+}
+
+/// Write `items` and their descendants. Returns the first and the last item.
+fn write_items(
+    context: &mut DocumentContext,
+    items: Vec<OutlineItem>,
+    parent: IndirectReference<()>,
+) -> Result<
+    Option<(
+        IndirectReference<OutlineItemDictionary>,
+        IndirectReference<OutlineItemDictionary>,
+    )>,
+> {
+    let references: Vec<IndirectReference<OutlineItemDictionary>> = items
+        .iter()
+        .map(|_| context.reserve_reference())
+        .collect::<Result<_>>()?;
+    for (index, (item, &reference)) in items.into_iter().zip(&references).enumerate() {
+        let count = match visible_count(&item.children) {
+            0 => None,
+            count if item.open => Some(count),
+            count => Some(-count),
+        };
+        let flags = item.flags();
+        let children = write_items(context, item.children, reference.convert())?;
+        let dictionary = OutlineItemDictionary {
+            title: utf16_text_string(&item.title),
+            parent,
+            prev: index
+                .checked_sub(1)
+                .and_then(|prev| references.get(prev).copied()),
+            next: references.get(index + 1).copied(),
+            first: children.map(|(first, _)| first),
+            last: children.map(|(_, last)| last),
+            count,
+            dest: item.destination,
+            color: item.color.map(|color| color.to_vec()),
+            flags,
+        };
+        context.write_indirect_object(dictionary, reference.raw())?;
+    }
+    Ok(references.first().copied().zip(references.last().copied()))
+}
+
+/// Write the outline tree with the top-level items `items`. Returns `None` if there are no
+/// items.
+pub(crate) fn write_outline(
+    context: &mut DocumentContext,
+    items: Vec<OutlineItem>,
+) -> Result<Option<IndirectReference<Outlines>>> {
+    if items.is_empty() {
+        return Ok(None);
+    }
+    let count = visible_count(&items);
+    let root = context.reserve_reference()?;
+    if let Some((first, last)) = write_items(context, items, root.convert())? {
+        context.write_indirect_object(Outlines { first, last, count }, root.raw())?;
+    }
+    Ok(Some(root))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::{ExistingDocument, RawDictionary};
+    use crate::Version;
+
+    #[test]
+    fn test_outline() {
+        let mut output = Vec::new();
+        let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_7).unwrap();
+        let pages: Vec<_> = (0..3)
+            .map(|_| context.add_page(Page::new()).unwrap())
+            .collect();
+
+        let mut chapter_1 = OutlineItem::new("Chapter 1", pages[0]);
+        chapter_1.open = true;
+        let mut section_1_2 = OutlineItem::new("Section 1.2", pages[1]);
+        section_1_2
+            .children
+            .push(OutlineItem::new("Section 1.2.1", pages[1]));
+        chapter_1.children = vec![OutlineItem::new("Section 1.1", pages[0]), section_1_2];
+        let mut chapter_2 = OutlineItem::new("Kapitel 2 – Übersicht", pages[2]);
+        chapter_2.bold = true;
+        chapter_2.italic = true;
+        chapter_2.color = Some([1.0, 0.0, 0.0]);
+        context.outline = vec![chapter_1, chapter_2];
+        context.finish().unwrap();
+
+        let document = ExistingDocument::parse(&output).unwrap();
+        let resolve = |dict: &RawDictionary, key| {
+            let reference = dict.get_reference(key).unwrap();
+            (reference, document.resolve_dictionary(reference).unwrap())
+        };
+        let catalog = document
+            .resolve_dictionary(document.trailer.get_reference("Root").unwrap())
+            .unwrap();
+        let (root_ref, root) = resolve(&catalog, "Outlines");
+        assert_eq!(root.get_integer("Count"), Some(4));
+
+        let (chapter_1_ref, chapter_1) = resolve(&root, "First");
+        let (chapter_2_ref, chapter_2) = resolve(&root, "Last");
+        assert_eq!(chapter_1.get_reference("Parent"), Some(root_ref));
+        assert_eq!(chapter_1.get_reference("Next"), Some(chapter_2_ref));
+        assert_eq!(chapter_2.get_reference("Prev"), Some(chapter_1_ref));
+        assert_eq!(chapter_1.get("Prev"), None);
+        assert_eq!(chapter_1.get_integer("Count"), Some(2));
+        assert!(chapter_1
+            .get("Title")
+            .unwrap()
+            .0
+            .starts_with(b"(\\376\\377"));
+
+        let (_, section_1_1) = resolve(&chapter_1, "First");
+        let (_, section_1_2) = resolve(&chapter_1, "Last");
+        assert_eq!(section_1_1.get_reference("Parent"), Some(chapter_1_ref));
+        assert_eq!(section_1_2.get_integer("Count"), Some(-1));
+        let (_, section_1_2_1) = resolve(&section_1_2, "First");
+        assert_eq!(section_1_2_1.get("Count"), None);
+
+        assert_eq!(chapter_2.get("Count"), None);
+        assert_eq!(chapter_2.get_integer("F"), Some(3));
+        assert_eq!(chapter_2.get("C").unwrap().0, b"[ 1.00 0.00 0.00 ]");
+        let destination = chapter_2.get("Dest").unwrap().as_array().unwrap();
+        assert_eq!(destination[0].as_reference(), Some(pages[2].raw()));
+        assert_eq!(destination[1].0, b"/Fit");
+    }
+}
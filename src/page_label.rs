@@ -0,0 +1,136 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! Page labels, which are displayed by viewers instead of the page numbers.
+
+use std::io::Result;
+
+use crate::object::{text_string, Formatter, PdfFormat};
+
+/// The numbering style of page labels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PageLabelStyle {
+    /// 1, 2, 3, ...
+    Decimal,
+    /// I, II, III, ...
+    UpperRoman,
+    /// i, ii, iii, ...
+    LowerRoman,
+    /// A to Z, then AA to ZZ, ...
+    UpperLetters,
+    /// a to z, then aa to zz, ...
+    LowerLetters,
+}
+
+impl PdfFormat for PageLabelStyle {
+    fn write(&self, f: &mut Formatter) -> Result<()> {
+        let name = match self {
+            PageLabelStyle::Decimal => "D",
+            PageLabelStyle::UpperRoman => "R",
+            PageLabelStyle::LowerRoman => "r",
+            PageLabelStyle::UpperLetters => "A",
+            PageLabelStyle::LowerLetters => "a",
+        };
+        name.write(f)
+    }
+}
+
+/// The labels of a range of pages, which starts at the page the label is set for and ends
+/// before the next page with a label. See `DocumentContext::page_labels`.
+///
+/// The label of a page is `prefix` followed by its number in `style`, counting from `start`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageLabel {
+    /// The numbering style. If `None`, the labels consist of the prefix only.
+    pub style: Option<PageLabelStyle>,
+    pub prefix: Option<String>,
+    /// The number of the first page of the range. Defaults to 1.
+    pub start: u32,
+}
+
+impl PageLabel {
+    pub fn new(style: PageLabelStyle) -> Self {
+        PageLabel {
+            style: Some(style),
+            prefix: None,
+            start: 1,
+        }
+    }
+
+    /// Labels that are numbered in `style` after `prefix`, like "A-1".
+    pub fn with_prefix(style: PageLabelStyle, prefix: impl Into<String>) -> Self {
+        PageLabel {
+            prefix: Some(prefix.into()),
+            ..PageLabel::new(style)
+        }
+    }
+}
+
+impl PdfFormat for PageLabel {
+    fn write(&self, f: &mut Formatter) -> Result<()> {
+        let mut dictionary = f.format_dictionary().key_value(&"Type", &"PageLabel");
+        if let Some(style) = &self.style {
+            dictionary = dictionary.key_value(&"S", style);
+        }
+        if let Some(prefix) = &self.prefix {
+            dictionary = dictionary.key_value(&"P", &text_string(prefix));
+        }
+        if self.start != 1 {
+            dictionary = dictionary.key_value(&"St", &self.start);
+        }
+        dictionary.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::{ExistingDocument, RawObject};
+    use crate::{DocumentContext, Page, Version};
+
+    #[test]
+    fn test_page_labels() {
+        let mut output = Vec::new();
+        let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_7).unwrap();
+        for _ in 0..6 {
+            context.add_page(Page::new()).unwrap();
+        }
+        let mut appendix = PageLabel::with_prefix(PageLabelStyle::Decimal, "A-");
+        appendix.start = 2;
+        context.page_labels.insert(4, appendix);
+        context
+            .page_labels
+            .insert(0, PageLabel::new(PageLabelStyle::LowerRoman));
+        context
+            .page_labels
+            .insert(2, PageLabel::new(PageLabelStyle::Decimal));
+        context.finish().unwrap();
+
+        let document = ExistingDocument::parse(&output).unwrap();
+        let catalog = document
+            .resolve_dictionary(document.trailer.get_reference("Root").unwrap())
+            .unwrap();
+        let root = document
+            .resolve_dictionary(catalog.get_reference("PageLabels").unwrap())
+            .unwrap();
+        let nums = root.get("Nums").unwrap().as_array().unwrap();
+        let keys: Vec<_> = nums.iter().step_by(2).map(RawObject::as_integer).collect();
+        assert_eq!(keys, vec![Some(0), Some(2), Some(4)]);
+        let first = nums[1].as_dictionary().unwrap();
+        assert_eq!(first.get("S").unwrap().0, b"/r");
+        let appendix = nums[5].as_dictionary().unwrap();
+        assert_eq!(appendix.get("P").unwrap().0, b"(A-)");
+        assert_eq!(appendix.get_integer("St"), Some(2));
+    }
+}
@@ -12,20 +12,22 @@
 //    See the License for the specific language governing permissions and
 //    limitations under the License.
 
-use std::collections::HashMap;
-use std::f64::consts::SQRT_2;
-use std::io::Result;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 
 use crate as lemon_pdf;
 use lemon_pdf_derive::PdfFormat;
 
 use crate::array::Array;
-use crate::content::PageContext;
+use crate::content::{ExtGState, PageContext};
 use crate::document::DocumentContext;
 use crate::font::Font;
-use crate::object::{IndirectReference, Object, Value};
+use crate::object::{Formatter, IndirectReference, Object, PdfFormat, Value};
+use crate::optional_content::OptionalContentGroup;
+use crate::paper::{larger_a_size, PaperSize};
 use crate::stream::{Stream, StreamEncoder, StreamFilter};
 use crate::Pt;
+use crate::{Error, Result};
 
 #[derive(Debug, Clone, PdfFormat)]
 pub struct Pages {
@@ -33,6 +35,9 @@ pub struct Pages {
     #[skip_if("Option::is_none")]
     parent: Option<IndirectReference<Pages>>,
     kids: Vec<Object<PageTreeNode>>,
+    /// The resources inherited by the pages below this node that have none of their own.
+    #[skip_if("Option::is_none")]
+    resources: Option<IndirectReference<ResourceDictionary>>,
 }
 
 impl Default for Pages {
@@ -41,10 +46,13 @@ impl Default for Pages {
             kids: Vec::new(),
             parent: None,
             count: -1,
+            resources: None,
         }
     }
 }
 
+// boxing the pages would need a `PdfFormat` implementation for `Box`
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug, PdfFormat)]
 enum PageTreeNode {
     Tree(Pages),
@@ -71,51 +79,261 @@ impl Pages {
         context: &mut DocumentContext,
     ) -> Result<IndirectReference<Pages>> {
         context.write_object_fn(|context, self_reference| {
-            let mut array = Vec::new();
-            for child in self.kids {
-                match child {
-                    Object::Direct(PageTreeNode::Tree(mut tree)) => {
-                        tree.parent = Some(self_reference);
-                        let tree_obj = tree.write_to_context(context)?;
-                        array.push(tree_obj.convert().into())
-                    }
-                    Object::Direct(PageTreeNode::Page(mut page)) => {
-                        page.set_parent(self_reference);
-                        let page_obj = context.write_object(page)?;
-                        array.push(page_obj.convert().into());
-                    }
-                    _ => unreachable!(),
-                }
-            }
-            self.kids = array;
+            let kids = std::mem::take(&mut self.kids);
+            self.kids = Pages::write_kids(kids, context, self_reference)?
+                .into_iter()
+                .map(|kid| kid.convert().into())
+                .collect();
             self.count = self.kids.len() as i64;
             Ok(self)
         })
     }
+
+    fn write_kids(
+        kids: Vec<Object<PageTreeNode>>,
+        context: &mut DocumentContext,
+        parent: IndirectReference<Pages>,
+    ) -> Result<Vec<IndirectReference<()>>> {
+        let mut array = Vec::new();
+        for child in kids {
+            match child {
+                Object::Direct(PageTreeNode::Tree(mut tree)) => {
+                    tree.parent = Some(parent);
+                    let tree_obj = tree.write_to_context(context)?;
+                    array.push(tree_obj.convert())
+                }
+                Object::Direct(PageTreeNode::Page(mut page)) => {
+                    page.set_parent(parent);
+                    let page_obj = context.write_object_fn(|_, _| Ok(page))?;
+                    array.push(page_obj.convert());
+                }
+                Object::Indirect(reference) => array.push(reference.convert()),
+            }
+        }
+        Ok(array)
+    }
+}
+
+/// The maximum number of kids of the nodes written by `PageTreeWriter`.
+const MAX_KIDS: usize = 32;
+
+/// A node of the page tree whose pages have already been written.
+#[derive(Debug)]
+struct PageTreeNodeRefs {
+    reference: IndirectReference<Pages>,
+    kids: Vec<IndirectReference<()>>,
+    count: i64,
+}
+
+impl PageTreeNodeRefs {
+    fn write(
+        self,
+        context: &mut DocumentContext,
+        parent: Option<IndirectReference<Pages>>,
+        resources: Option<IndirectReference<ResourceDictionary>>,
+    ) -> Result<()> {
+        let node = Pages {
+            count: self.count,
+            parent,
+            kids: self.kids.into_iter().map(|kid| kid.convert().into()).collect(),
+            resources,
+        };
+        context.write_indirect_object(node, self.reference.raw())
+    }
+}
+
+/// Writes pages as soon as they are added and builds a balanced page tree above them, so only
+/// the references of the pages are kept in memory.
+///
+/// The pages are grouped into leaf nodes of up to `MAX_KIDS` pages. When the page tree is
+/// finished, the leaf nodes are grouped into levels of nodes with up to `MAX_KIDS` kids each
+/// until a single root node remains, so every page has the same depth.
+#[derive(Debug, Default)]
+pub(crate) struct PageTreeWriter {
+    /// The parent of the root node when appending to an existing page tree.
+    parent: Option<IndirectReference<Pages>>,
+    /// The leaf nodes that hold the pages. Only the last one may have room for more pages.
+    leaves: Vec<PageTreeNodeRefs>,
+    count: i64,
+    /// The shared resources as they were written when the first page was added, and their
+    /// reference if they are not empty.
+    shared_resources: Option<(ResourceDictionary, Option<IndirectReference<ResourceDictionary>>)>,
+}
+
+impl PageTreeWriter {
+    /// A page tree that is appended to the existing node `parent`.
+    pub fn with_parent(parent: IndirectReference<Pages>) -> Self {
+        PageTreeWriter {
+            parent: Some(parent),
+            ..Default::default()
+        }
+    }
+
+    pub fn page_count(&self) -> i64 {
+        self.count
+    }
+
+    /// Write the shared resources of `context` the first time this is called. Fails if they
+    /// have been changed since, because the pages that are already written inherit them.
+    fn shared_resources(
+        &mut self,
+        context: &mut DocumentContext,
+    ) -> Result<Option<IndirectReference<ResourceDictionary>>> {
+        match &self.shared_resources {
+            Some((shared, reference)) if *shared == context.shared_resources => Ok(*reference),
+            Some(_) => Err(Error::InvalidInput(
+                "the shared resources cannot be changed once pages have been added".to_string(),
+            )),
+            None => {
+                let shared = context.shared_resources.clone();
+                let reference = if shared.is_empty() {
+                    None
+                } else {
+                    Some(context.write_object(shared.clone())?)
+                };
+                self.shared_resources = Some((shared, reference));
+                Ok(reference)
+            }
+        }
+    }
+
+    /// Write `page` as the next page of the document.
+    pub fn add_page(
+        &mut self,
+        context: &mut DocumentContext,
+        mut page: Page,
+    ) -> Result<IndirectReference<Page>> {
+        self.shared_resources(context)?;
+        if self.leaves.last().is_none_or(|leaf| leaf.kids.len() >= MAX_KIDS) {
+            self.leaves.push(PageTreeNodeRefs {
+                reference: context.reserve_reference()?,
+                kids: Vec::new(),
+                count: 0,
+            });
+        }
+        let leaf = self.leaves.last_mut().unwrap();
+        page.set_parent(leaf.reference);
+        // `write_object_fn` never deduplicates, identical pages must stay separate objects
+        let reference = context.write_object_fn(|_, _| Ok(page))?;
+        leaf.kids.push(reference.convert());
+        leaf.count += 1;
+        self.count += 1;
+        Ok(reference)
+    }
+
+    /// Write the nodes of the page tree and return its root.
+    pub fn finish(mut self, context: &mut DocumentContext) -> Result<IndirectReference<Pages>> {
+        // the shared resources are written once and inherited by the pages through the root
+        let resources = self.shared_resources(context)?;
+        let mut level = self.leaves;
+        if level.is_empty() {
+            level.push(PageTreeNodeRefs {
+                reference: context.reserve_reference()?,
+                kids: Vec::new(),
+                count: 0,
+            });
+        }
+
+        // group the nodes level by level until only the root is left
+        while level.len() > 1 {
+            let mut next_level = Vec::new();
+            let mut nodes = level.into_iter().peekable();
+            while nodes.peek().is_some() {
+                let mut parent = PageTreeNodeRefs {
+                    reference: context.reserve_reference()?,
+                    kids: Vec::new(),
+                    count: 0,
+                };
+                for node in nodes.by_ref().take(MAX_KIDS) {
+                    parent.kids.push(node.reference.convert());
+                    parent.count += node.count;
+                    node.write(context, Some(parent.reference), None)?;
+                }
+                next_level.push(parent);
+            }
+            level = next_level;
+        }
+
+        let root = level.pop().unwrap();
+        let reference = root.reference;
+        root.write(context, self.parent, resources)?;
+        Ok(reference)
+    }
 }
 
 #[derive(Clone, Debug, Default, PartialEq, PdfFormat)]
 #[omit_type(true)]
 pub struct ResourceDictionary {
-    pub font: HashMap<String, IndirectReference<Font>>,
+    pub font: BTreeMap<String, IndirectReference<Font>>,
+    #[rename("ExtGState")]
+    #[skip_if("BTreeMap::is_empty")]
+    pub ext_g_state: BTreeMap<String, ExtGState>,
+    /// The property lists of marked content, e.g. optional content groups.
+    #[skip_if("BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, IndirectReference<OptionalContentGroup>>,
+}
+
+impl ResourceDictionary {
+    pub fn is_empty(&self) -> bool {
+        self.font.is_empty() && self.ext_g_state.is_empty() && self.properties.is_empty()
+    }
 }
 
 /// A convenience struct to create PDF pages.
 #[derive(Clone, Debug, PdfFormat)]
 pub struct Page {
+    /// The resources of the content of the page. If not set, the page inherits the
+    /// `DocumentContext::shared_resources`.
+    #[skip_if("Option::is_none")]
     pub resources: Option<ResourceDictionary>,
     pub media_box: [Pt; 4],
+    /// The region the page is clipped to when it is displayed or printed. Defaults to the
+    /// media box.
+    #[skip_if("Option::is_none")]
+    pub crop_box: Option<[Pt; 4]>,
+    /// The region the page is clipped to in production, including the bleed area. Defaults
+    /// to the crop box.
+    #[skip_if("Option::is_none")]
+    pub bleed_box: Option<[Pt; 4]>,
+    /// The dimensions of the finished page after trimming. Defaults to the crop box.
+    #[skip_if("Option::is_none")]
+    pub trim_box: Option<[Pt; 4]>,
+    /// The meaningful content of the page. Defaults to the crop box.
+    #[skip_if("Option::is_none")]
+    pub art_box: Option<[Pt; 4]>,
+    /// How the page is rotated when it is displayed or printed.
+    #[skip_if("Option::is_none")]
+    pub rotate: Option<Rotation>,
+    /// The size of a unit in multiples of 1/72 inch, which allows pages larger than 200
+    /// inches. Requires PDF 1.6.
+    #[skip_if("Option::is_none")]
+    pub user_unit: Option<f64>,
     #[skip_if("Option::is_none")]
     pub parent: Option<IndirectReference<Pages>>,
     pub contents: Vec<IndirectReference<Stream>>,
+    /// The annotations of the page, e.g. a `SignatureField`.
+    #[skip_if("Vec::is_empty")]
+    pub annots: Vec<IndirectReference<()>>,
+    /// The key of the page in the parent tree of the structure tree. Set by `add_content` if
+    /// the page has tagged content.
+    #[skip_if("Option::is_none")]
+    pub struct_parents: Option<i64>,
 }
 impl Page {
     pub fn new() -> Self {
         Page {
             resources: None,
-            media_box: MediaBox::paper_din_a(4).as_array(),
+            media_box: MediaBox::paper(PaperSize::A(4)).as_array(),
+            crop_box: None,
+            bleed_box: None,
+            trim_box: None,
+            art_box: None,
+            rotate: None,
+            user_unit: None,
             parent: None,
             contents: Vec::new(),
+            annots: Vec::new(),
+            struct_parents: None,
         }
     }
 
@@ -123,19 +341,79 @@ impl Page {
         self.media_box = media_box.as_array()
     }
 
+    pub fn set_crop_box(&mut self, crop_box: MediaBox) {
+        self.crop_box = Some(crop_box.as_array())
+    }
+
+    pub fn set_bleed_box(&mut self, bleed_box: MediaBox) {
+        self.bleed_box = Some(bleed_box.as_array())
+    }
+
+    pub fn set_trim_box(&mut self, trim_box: MediaBox) {
+        self.trim_box = Some(trim_box.as_array())
+    }
+
+    pub fn set_art_box(&mut self, art_box: MediaBox) {
+        self.art_box = Some(art_box.as_array())
+    }
+
+    /// Set the trim box to `trim_box` and the media box and bleed box to `trim_box` enlarged
+    /// by `bleed` on every side.
+    pub fn set_bleed(&mut self, trim_box: MediaBox, bleed: Pt) {
+        self.set_media_box(trim_box.with_bleed(bleed));
+        self.set_bleed_box(trim_box.with_bleed(bleed));
+        self.set_trim_box(trim_box);
+    }
+
+    /// Check that the page boxes lie inside the media box.
+    pub(crate) fn check_boxes(&self) -> Result<()> {
+        let media_box = MediaBox::from_array(self.media_box);
+        let boxes = [
+            ("CropBox", self.crop_box),
+            ("BleedBox", self.bleed_box),
+            ("TrimBox", self.trim_box),
+            ("ArtBox", self.art_box),
+        ];
+        for &(name, page_box) in &boxes {
+            if let Some(page_box) = page_box {
+                if !media_box.contains(MediaBox::from_array(page_box)) {
+                    return Err(Error::InvalidInput(format!(
+                        "the {} of the page is not inside its MediaBox",
+                        name
+                    )));
+                }
+            }
+        }
+        if self.user_unit.is_some_and(|user_unit| user_unit <= 0.0) {
+            return Err(Error::InvalidInput(
+                "the UserUnit of the page has to be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
     fn get_context<'context, 'borrow>(
         &mut self,
         context: &'borrow mut DocumentContext<'context>,
         stream_filter: Option<StreamFilter>,
     ) -> PageContext<'_, 'context, 'borrow> {
+        let next_mcid = context.marked_content.mcid_count(self.struct_parents);
+        // the resources of previous content streams of the page are kept
+        let resources = self.resources.clone().unwrap_or_default();
         PageContext {
-            fonts: HashMap::new(),
+            fonts: resources.font,
+            ext_g_states: resources.ext_g_state,
             page: self,
+            optional_content_groups: resources.properties,
             content_stream: StreamEncoder::new(stream_filter),
+            marked_content: Vec::new(),
+            next_mcid,
             pdf_context: context,
         }
     }
 
+    /// Add a content stream produced by `content_f` to the page. The stream is drawn after
+    /// the previous content streams of the page.
     pub fn add_content<'context, 'borrow>(
         &mut self,
         context: &mut DocumentContext<'context>,
@@ -175,17 +453,116 @@ impl MediaBox {
         }
     }
 
+    /// A standard paper size in portrait orientation with the lower left corner at the origin.
+    pub fn paper(size: PaperSize) -> Self {
+        let (width, height) = size.size();
+        MediaBox::new(Pt(0.0), Pt(0.0), width, height)
+    }
+
+    /// The A series paper size `num`, see `PaperSize::A`. Negative numbers give the larger
+    /// formats of DIN 476, 2A0 (1189 × 1682 mm) for `-1` and 4A0 (1682 × 2378 mm) for `-2`,
+    /// and their continuation for smaller numbers.
     pub fn paper_din_a(num: i32) -> Self {
-        const A_AREA: f64 = 1.0;
-        const M_TO_PT: f64 = 2834.65;
+        let (width, height) = match u8::try_from(num) {
+            Ok(number) => PaperSize::A(number).size(),
+            Err(_) if num > 0 => PaperSize::A(u8::MAX).size(),
+            Err(_) => larger_a_size(num.unsigned_abs()),
+        };
+        MediaBox::new(Pt(0.0), Pt(0.0), width, height)
+    }
 
-        let height = M_TO_PT * (A_AREA * SQRT_2).sqrt() / SQRT_2.powi(num);
-        MediaBox::new(Pt(0.0), Pt(0.0), Pt(height / SQRT_2), Pt(height))
+    pub fn width(self) -> Pt {
+        let normalized = self.normalized();
+        normalized.xmax - normalized.xmin
+    }
+
+    pub fn height(self) -> Pt {
+        let normalized = self.normalized();
+        normalized.ymax - normalized.ymin
+    }
+
+    /// The box in landscape orientation, i.e. with width and height swapped if it is higher
+    /// than wide. The lower left corner stays in place.
+    pub fn landscape(self) -> Self {
+        let normalized = self.normalized();
+        let (width, height) = (self.width(), self.height());
+        if width >= height {
+            return normalized;
+        }
+        MediaBox::new(
+            normalized.xmin,
+            normalized.ymin,
+            normalized.xmin + height,
+            normalized.ymin + width,
+        )
     }
 
     pub fn as_array(self) -> [Pt; 4] {
         [self.xmin, self.ymin, self.xmax, self.ymax]
     }
+
+    pub fn from_array(array: [Pt; 4]) -> Self {
+        MediaBox::new(array[0], array[1], array[2], array[3])
+    }
+
+    /// The box enlarged by `bleed` on every side, e.g. the media box for a trim box.
+    pub fn with_bleed(self, bleed: Pt) -> Self {
+        let normalized = self.normalized();
+        MediaBox::new(
+            normalized.xmin - bleed,
+            normalized.ymin - bleed,
+            normalized.xmax + bleed,
+            normalized.ymax + bleed,
+        )
+    }
+
+    /// The box reduced by `inset` on every side, e.g. the trim box for a bleed box.
+    pub fn inset(self, inset: Pt) -> Self {
+        self.with_bleed(-inset)
+    }
+
+    /// Whether `other` lies inside this box.
+    pub fn contains(self, other: MediaBox) -> bool {
+        let (outer, inner) = (self.normalized(), other.normalized());
+        outer.xmin <= inner.xmin
+            && outer.ymin <= inner.ymin
+            && inner.xmax <= outer.xmax
+            && inner.ymax <= outer.ymax
+    }
+
+    /// The box with the lower left corner first, as the corners can be given in any order.
+    fn normalized(self) -> Self {
+        MediaBox::new(
+            Pt(self.xmin.0.min(self.xmax.0)),
+            Pt(self.ymin.0.min(self.ymax.0)),
+            Pt(self.xmin.0.max(self.xmax.0)),
+            Pt(self.ymin.0.max(self.ymax.0)),
+        )
+    }
+}
+
+/// The clockwise rotation of a page when it is displayed or printed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rotation {
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+impl Rotation {
+    pub fn degrees(self) -> i64 {
+        match self {
+            Rotation::Clockwise90 => 90,
+            Rotation::Clockwise180 => 180,
+            Rotation::Clockwise270 => 270,
+        }
+    }
+}
+
+impl PdfFormat for Rotation {
+    fn write(&self, f: &mut Formatter) -> std::io::Result<()> {
+        self.degrees().write(f)
+    }
 }
 
 impl Default for Page {
@@ -196,11 +573,203 @@ impl Default for Page {
 
 impl From<MediaBox> for Object<Value> {
     fn from(mediabox: MediaBox) -> Object<Value> {
-        let mut array = Array::new();
-        array.push(Object::Direct(mediabox.xmin.0.into()));
-        array.push(Object::Direct(mediabox.ymin.0.into()));
-        array.push(Object::Direct(mediabox.xmax.0.into()));
-        array.push(Object::Direct(mediabox.ymax.0.into()));
+        let array: Array = vec![
+            Object::Direct(mediabox.xmin.0.into()),
+            Object::Direct(mediabox.ymin.0.into()),
+            Object::Direct(mediabox.xmax.0.into()),
+            Object::Direct(mediabox.ymax.0.into()),
+        ];
         Object::Direct(array.into())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::font::builtin::BuiltInFont;
+    use crate::font::Font;
+    use crate::object::RawIndirectReference;
+    use crate::reader::ExistingDocument;
+    use crate::{Mm, Version};
+
+    /// Check the page tree node `node` and return the number of pages below it. The depths of
+    /// the pages are added to `page_depths`.
+    fn check_node(
+        document: &ExistingDocument,
+        node: RawIndirectReference,
+        parent: Option<RawIndirectReference>,
+        depth: usize,
+        page_depths: &mut BTreeSet<usize>,
+    ) -> i64 {
+        let dictionary = document.resolve_dictionary(node).unwrap();
+        assert_eq!(dictionary.get_reference("Parent"), parent);
+        if dictionary.get("Type").unwrap().0 == b"/Page" {
+            page_depths.insert(depth);
+            return 1;
+        }
+        let kids = dictionary.get("Kids").unwrap().as_array().unwrap();
+        assert!(kids.len() <= MAX_KIDS);
+        let count = kids
+            .iter()
+            .map(|kid| {
+                let kid = kid.as_reference().unwrap();
+                check_node(document, kid, Some(node), depth + 1, page_depths)
+            })
+            .sum();
+        assert_eq!(dictionary.get_integer("Count"), Some(count));
+        count
+    }
+
+    #[test]
+    fn test_balanced_page_tree() {
+        let page_counts = [0, 1, MAX_KIDS, MAX_KIDS + 1, MAX_KIDS * MAX_KIDS + 2 * MAX_KIDS + 1];
+        for &page_count in &page_counts {
+            let mut output = Vec::new();
+            let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_7).unwrap();
+            for _ in 0..page_count {
+                context.add_page(Page::new()).unwrap();
+            }
+            context.finish().unwrap();
+
+            let document = ExistingDocument::parse(&output).unwrap();
+            let catalog = document
+                .resolve_dictionary(document.trailer.get_reference("Root").unwrap())
+                .unwrap();
+            let root = catalog.get_reference("Pages").unwrap();
+            let mut page_depths = BTreeSet::new();
+            let count = check_node(&document, root, None, 0, &mut page_depths);
+            assert_eq!(count, page_count as i64);
+            // every page has the same depth
+            assert!(page_depths.len() <= 1);
+        }
+    }
+
+    #[test]
+    fn test_page_boxes() {
+        let trim_box = MediaBox::new(Pt(0.0), Pt(0.0), Pt(595.0), Pt(842.0));
+        assert_eq!(
+            trim_box.with_bleed(Pt(9.0)),
+            MediaBox::new(Pt(-9.0), Pt(-9.0), Pt(604.0), Pt(851.0))
+        );
+        assert_eq!(trim_box.with_bleed(Pt(9.0)).inset(Pt(9.0)), trim_box);
+        let in_mm = |media_box: MediaBox| {
+            let (width, height) = (Mm::from(media_box.width()), Mm::from(media_box.height()));
+            (width.0.round(), height.0.round())
+        };
+        assert_eq!(in_mm(MediaBox::paper_din_a(4)), (210.0, 297.0));
+        assert_eq!(in_mm(MediaBox::paper_din_a(0)), (841.0, 1189.0));
+        assert_eq!(in_mm(MediaBox::paper_din_a(-1)), (1189.0, 1682.0));
+        assert_eq!(in_mm(MediaBox::paper_din_a(-2)), (1682.0, 2378.0));
+        let letter = MediaBox::paper(PaperSize::Letter).landscape();
+        assert_eq!(letter, MediaBox::new(Pt(0.0), Pt(0.0), Pt(792.0), Pt(612.0)));
+        assert_eq!(letter.landscape(), letter);
+
+        let mut output = Vec::new();
+        let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_4).unwrap();
+        let mut page = Page::new();
+        page.set_bleed(trim_box, Pt(9.0));
+        page.set_crop_box(trim_box.with_bleed(Pt(10.0)));
+        assert!(matches!(
+            context.add_page(page.clone()),
+            Err(Error::InvalidInput(_))
+        ));
+        page.crop_box = None;
+        page.rotate = Some(Rotation::Clockwise90);
+        page.user_unit = Some(10.0);
+        context.add_page(page).unwrap();
+        context.finish().unwrap();
+
+        let document = ExistingDocument::parse(&output).unwrap();
+        let catalog = document
+            .resolve_dictionary(document.trailer.get_reference("Root").unwrap())
+            .unwrap();
+        assert_eq!(catalog.get("Version").unwrap().0, b"/1.6");
+        let pages = document
+            .resolve_dictionary(catalog.get_reference("Pages").unwrap())
+            .unwrap();
+        let kids = pages.get("Kids").unwrap().as_array().unwrap();
+        let page = document
+            .resolve_dictionary(kids[0].as_reference().unwrap())
+            .unwrap();
+        let media_box = page.get("MediaBox").unwrap().as_array().unwrap();
+        assert_eq!(media_box[0].0, b"-9.00");
+        assert_eq!(page.get("BleedBox"), page.get("MediaBox"));
+        assert_eq!(page.get("TrimBox").unwrap().as_array().unwrap()[3].0, b"842.00");
+        assert_eq!(page.get("CropBox"), None);
+        assert_eq!(page.get_integer("Rotate"), Some(90));
+        assert_eq!(page.get("UserUnit").unwrap().0, b"10.00");
+    }
+
+    #[test]
+    fn test_shared_resources() {
+        let mut output = Vec::new();
+        let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_7).unwrap();
+        let helvetica = BuiltInFont::Helvetica.font(&mut context).unwrap();
+        let helvetica = context.write_object(Font::Simple(helvetica)).unwrap();
+        let courier = BuiltInFont::Courier.font(&mut context).unwrap();
+        let courier = context.write_object(Font::Simple(courier)).unwrap();
+        let times = BuiltInFont::TimesRoman.font(&mut context).unwrap();
+        let times = context.write_object(Font::Simple(times)).unwrap();
+        assert_eq!(context.add_shared_font(helvetica).unwrap(), "F0");
+        assert_eq!(context.add_shared_font(helvetica).unwrap(), "F0");
+
+        let mut pages = Vec::new();
+        for index in 0..2 * MAX_KIDS {
+            let mut page = Page::new();
+            page.add_content(&mut context, None, |page_context| {
+                assert_eq!(page_context.add_font(helvetica), "F0");
+                if index == 0 {
+                    assert_eq!(page_context.add_font(courier), "F1");
+                }
+                Ok(())
+            })
+            .unwrap();
+            if index == 0 {
+                // a further content stream extends the resources of the page
+                page.add_content(&mut context, None, |page_context| {
+                    assert_eq!(page_context.add_font(courier), "F1");
+                    assert_eq!(page_context.add_font(times), "F2");
+                    Ok(())
+                })
+                .unwrap();
+                assert_eq!(page.contents.len(), 2);
+            }
+            pages.push(context.add_page(page).unwrap());
+        }
+        // the pages already inherit the shared resources
+        assert_eq!(context.add_shared_font(helvetica).unwrap(), "F0");
+        assert!(matches!(context.add_shared_font(courier), Err(Error::InvalidInput(_))));
+        context.shared_resources.font.insert("F1".to_string(), courier);
+        assert!(matches!(context.add_page(Page::new()), Err(Error::InvalidInput(_))));
+        context.shared_resources.font.remove("F1");
+        context.finish().unwrap();
+
+        let document = ExistingDocument::parse(&output).unwrap();
+        let catalog = document
+            .resolve_dictionary(document.trailer.get_reference("Root").unwrap())
+            .unwrap();
+        let root = document
+            .resolve_dictionary(catalog.get_reference("Pages").unwrap())
+            .unwrap();
+        let shared = document
+            .resolve_dictionary(root.get_reference("Resources").unwrap())
+            .unwrap();
+        let fonts = shared.get("Font").unwrap().as_dictionary().unwrap();
+        assert_eq!(fonts.get_reference("F0"), Some(helvetica.raw()));
+        assert_eq!(fonts.get("F1"), None);
+
+        // the resources of a page replace the inherited ones and have to repeat them
+        let first = document.resolve_dictionary(pages[0].raw()).unwrap();
+        let resources = first.get("Resources").unwrap().as_dictionary().unwrap();
+        let fonts = resources.get("Font").unwrap().as_dictionary().unwrap();
+        assert_eq!(fonts.get_reference("F0"), Some(helvetica.raw()));
+        assert_eq!(fonts.get_reference("F1"), Some(courier.raw()));
+        assert_eq!(fonts.get_reference("F2"), Some(times.raw()));
+        for page in &pages[1..] {
+            let page = document.resolve_dictionary(page.raw()).unwrap();
+            assert_eq!(page.get("Resources"), None);
+        }
+    }
+}
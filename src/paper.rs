@@ -0,0 +1,112 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! Standard paper sizes, see `MediaBox::paper`.
+
+use crate::units::{Inch, Mm};
+use crate::Pt;
+
+/// A standard paper or envelope size.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PaperSize {
+    /// The ISO 216 A series, e.g. `A(4)` for A4 (210 × 297 mm).
+    A(u8),
+    /// The ISO 216 B series, e.g. `B(5)` for B5 (176 × 250 mm).
+    B(u8),
+    /// The ISO 269 C series of envelopes, e.g. `C(5)` for C5 (162 × 229 mm).
+    C(u8),
+    /// US Letter, 8.5 × 11 in.
+    Letter,
+    /// US Legal, 8.5 × 14 in.
+    Legal,
+    /// US Tabloid, 11 × 17 in.
+    Tabloid,
+    /// The DL envelope, 110 × 220 mm.
+    EnvelopeDL,
+    /// The US No. 10 envelope, 4.125 × 9.5 in.
+    EnvelopeNo10,
+    /// The US Monarch envelope, 3.875 × 7.5 in.
+    EnvelopeMonarch,
+}
+
+impl PaperSize {
+    /// The width and height in portrait orientation.
+    pub fn size(self) -> (Pt, Pt) {
+        let (width, height) = match self {
+            PaperSize::A(number) => iso_size(841, 1189, number),
+            PaperSize::B(number) => iso_size(1000, 1414, number),
+            PaperSize::C(number) => iso_size(917, 1297, number),
+            PaperSize::EnvelopeDL => (110, 220),
+            PaperSize::Letter => return inches(8.5, 11.0),
+            PaperSize::Legal => return inches(8.5, 14.0),
+            PaperSize::Tabloid => return inches(11.0, 17.0),
+            PaperSize::EnvelopeNo10 => return inches(4.125, 9.5),
+            PaperSize::EnvelopeMonarch => return inches(3.875, 7.5),
+        };
+        (Mm(width as f64).into(), Mm(height as f64).into())
+    }
+}
+
+/// The size in millimetres of the format `number` of an ISO series whose format 0 has the
+/// size `width` × `height`. Each format is the previous one halved, rounded down to whole
+/// millimetres.
+fn iso_size(width: u32, height: u32, number: u8) -> (u32, u32) {
+    (0..number).fold((width, height), |(width, height), _| (height / 2, width))
+}
+
+/// The size of the A series format that is `doublings` times as large as A0, e.g. 2A0 for one
+/// doubling. Doubling a format needs no rounding, so the sizes are exact.
+pub(crate) fn larger_a_size(doublings: u32) -> (Pt, Pt) {
+    // every second doubling gives A0 scaled by 2 in both directions
+    let (width, height) = if doublings.is_multiple_of(2) { (841, 1189) } else { (1189, 1682) };
+    let factor = 2f64.powi((doublings / 2) as i32);
+    (
+        Mm(width as f64 * factor).into(),
+        Mm(height as f64 * factor).into(),
+    )
+}
+
+fn inches(width: f64, height: f64) -> (Pt, Pt) {
+    (Inch(width).into(), Inch(height).into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_paper_sizes() {
+        let in_mm = |size: PaperSize| {
+            let (width, height) = size.size();
+            (Mm::from(width).0.round(), Mm::from(height).0.round())
+        };
+        assert_eq!(in_mm(PaperSize::A(0)), (841.0, 1189.0));
+        assert_eq!(in_mm(PaperSize::A(4)), (210.0, 297.0));
+        assert_eq!(in_mm(PaperSize::A(10)), (26.0, 37.0));
+        assert_eq!(in_mm(PaperSize::B(5)), (176.0, 250.0));
+        assert_eq!(in_mm(PaperSize::B(9)), (44.0, 62.0));
+        assert_eq!(in_mm(PaperSize::C(5)), (162.0, 229.0));
+        assert_eq!(in_mm(PaperSize::C(6)), (114.0, 162.0));
+        assert_eq!(PaperSize::Letter.size(), (Pt(612.0), Pt(792.0)));
+        assert_eq!(PaperSize::EnvelopeNo10.size(), (Pt(297.0), Pt(684.0)));
+
+        let in_mm = |(width, height): (Pt, Pt)| {
+            (Mm::from(width).0.round(), Mm::from(height).0.round())
+        };
+        assert_eq!(in_mm(larger_a_size(0)), in_mm(PaperSize::A(0).size()));
+        assert_eq!(in_mm(larger_a_size(1)), (1189.0, 1682.0));
+        assert_eq!(in_mm(larger_a_size(2)), (1682.0, 2378.0));
+        assert_eq!(in_mm(larger_a_size(3)), (2378.0, 3364.0));
+    }
+}
@@ -0,0 +1,273 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! PDF/A, the subset of PDF for long-term archiving.
+
+use std::io::Write;
+
+use crate as lemon_pdf;
+use lemon_pdf_derive::PdfFormat;
+
+use crate::icc::{srgb_profile, SRGB_DESCRIPTION};
+use crate::object::{IndirectReference, RawIndirectReference};
+use crate::reader::{RawDictionary, RawObject};
+use crate::stream::{Stream, StreamEncoder, StreamFilter};
+use crate::xmp::PdfAIdentification;
+use crate::{DocumentContext, Error, Result, Version};
+
+/// A PDF/A conformance level, see `DocumentContext::set_pdfa_conformance`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PdfAConformance {
+    /// PDF/A-2b (ISO 19005-2), which requires the visual appearance to be preserved.
+    PdfA2b,
+    /// PDF/A-3b (ISO 19005-3), which is PDF/A-2b with arbitrary embedded files.
+    PdfA3b,
+}
+
+impl PdfAConformance {
+    /// The part of ISO 19005.
+    pub fn part(self) -> u8 {
+        match self {
+            PdfAConformance::PdfA2b => 2,
+            PdfAConformance::PdfA3b => 3,
+        }
+    }
+
+    pub(crate) fn identification(self) -> PdfAIdentification {
+        PdfAIdentification {
+            part: self.part(),
+            conformance: "B".to_string(),
+        }
+    }
+}
+
+/// An output intent that describes the colors of the document as sRGB.
+#[derive(Debug, PdfFormat)]
+#[rename("OutputIntent")]
+pub(crate) struct OutputIntent {
+    #[rename("S")]
+    subtype: &'static str,
+    output_condition_identifier: Vec<u8>,
+    info: Vec<u8>,
+    dest_output_profile: IndirectReference<Stream>,
+}
+
+impl OutputIntent {
+    /// Write the sRGB ICC profile and return the output intent that uses it.
+    pub fn srgb(context: &mut DocumentContext) -> Result<Self> {
+        let mut encoder = StreamEncoder::new(Some(StreamFilter::Deflate));
+        encoder.write_all(&srgb_profile())?;
+        let mut profile = encoder.into_stream()?;
+        profile.add_key_value("N".to_string(), Box::new(3i64));
+        Ok(OutputIntent {
+            subtype: "GTS_PDFA1",
+            output_condition_identifier: SRGB_DESCRIPTION.as_bytes().to_vec(),
+            info: SRGB_DESCRIPTION.as_bytes().to_vec(),
+            dest_output_profile: context.write_object(profile)?,
+        })
+    }
+}
+
+/// PDF/A-2 and PDF/A-3 are based on PDF 1.7.
+pub(crate) fn check_version(version: Version) -> Result<()> {
+    if version <= Version::Pdf1_7 {
+        return Ok(());
+    }
+    Err(Error::Conformance {
+        object: None,
+        message: format!("PDF/A does not allow PDF version {}", version.number()),
+    })
+}
+
+fn violation(reference: RawIndirectReference, message: String) -> Error {
+    Error::Conformance {
+        object: Some(reference),
+        message,
+    }
+}
+
+/// Check the serialized object `bytes` for features that PDF/A forbids: fonts that are not
+/// embedded, the LZW filter and JavaScript.
+pub(crate) fn check_object(reference: RawIndirectReference, bytes: &[u8]) -> Result<()> {
+    check_value(reference, &RawObject(bytes.to_vec()))
+}
+
+fn check_value(reference: RawIndirectReference, value: &RawObject) -> Result<()> {
+    if let Some(dictionary) = value.as_dictionary() {
+        check_dictionary(reference, &dictionary)?;
+        for (_, value) in dictionary.iter() {
+            check_value(reference, value)?;
+        }
+    } else if let Some(array) = value.as_array() {
+        for value in &array {
+            check_value(reference, value)?;
+        }
+    }
+    Ok(())
+}
+
+fn check_dictionary(reference: RawIndirectReference, dictionary: &RawDictionary) -> Result<()> {
+    let is = |key: &str, name: &[u8]| dictionary.get(key).is_some_and(|value| value.0 == name);
+    let name_of = |key: &str| {
+        dictionary.get(key).map_or_else(String::new, |name| {
+            String::from_utf8_lossy(name.0.strip_prefix(b"/").unwrap_or(&name.0)).into_owned()
+        })
+    };
+
+    if is("Type", b"/FontDescriptor")
+        && ["FontFile", "FontFile2", "FontFile3"]
+            .iter()
+            .all(|key| dictionary.get(key).is_none())
+    {
+        let message = format!("the font {} is not embedded", name_of("FontName"));
+        return Err(violation(reference, message));
+    }
+    let is_simple_font = ["/Type1", "/MMType1", "/TrueType"]
+        .iter()
+        .any(|subtype| is("Subtype", subtype.as_bytes()));
+    if is("Type", b"/Font") && is_simple_font && dictionary.get("FontDescriptor").is_none() {
+        let message = format!("the font {} is not embedded", name_of("BaseFont"));
+        return Err(violation(reference, message));
+    }
+
+    let uses_lzw = dictionary.get("Filter").is_some_and(|filter| {
+        filter.0 == b"/LZWDecode"
+            || filter
+                .as_array()
+                .is_some_and(|filters| filters.iter().any(|filter| filter.0 == b"/LZWDecode"))
+    });
+    if uses_lzw {
+        return Err(violation(reference, "the LZW filter is used".to_string()));
+    }
+
+    if is("S", b"/JavaScript") || dictionary.get("JS").is_some() {
+        return Err(violation(
+            reference,
+            "a JavaScript action is used".to_string(),
+        ));
+    }
+    if dictionary.get("JavaScript").is_some() {
+        let message = "the name dictionary contains JavaScript".to_string();
+        return Err(violation(reference, message));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encryption::{Encryption, EncryptionMethod, Permissions};
+    use crate::font::builtin::BuiltInFont;
+    use crate::reader::ExistingDocument;
+    use crate::xmp::XmpMetadata;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_pdfa() {
+        let mut context = DocumentContext::with_writer(Vec::new(), Version::Pdf1_7).unwrap();
+        context
+            .set_pdfa_conformance(PdfAConformance::PdfA2b)
+            .unwrap();
+        let encryption = Encryption::new("", "", Permissions::PRINT, EncryptionMethod::Aes256);
+        assert!(matches!(
+            context.set_encryption(encryption),
+            Err(Error::Conformance { .. })
+        ));
+        assert!(matches!(
+            BuiltInFont::Helvetica.font(&mut context),
+            Err(Error::Conformance { .. })
+        ));
+
+        let mut stream = StreamEncoder::new(None).into_stream().unwrap();
+        stream.add_key_value("Filter".to_string(), Box::new("LZWDecode"));
+        match context.write_object(stream) {
+            Err(Error::Conformance {
+                object: Some(_),
+                message,
+            }) => assert_eq!(message, "the LZW filter is used"),
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+        let mut action = BTreeMap::new();
+        action.insert("S", "JavaScript");
+        assert!(matches!(
+            context.write_object(action),
+            Err(Error::Conformance {
+                object: Some(_),
+                ..
+            })
+        ));
+        // objects written through serde are checked as well
+        #[derive(serde::Serialize)]
+        enum ActionType {
+            JavaScript,
+        }
+        #[derive(serde::Serialize)]
+        #[serde(rename = "Action")]
+        struct Action {
+            #[serde(rename = "S")]
+            action_type: ActionType,
+            #[serde(rename = "JS")]
+            script: &'static str,
+        }
+        let action = Action {
+            action_type: ActionType::JavaScript,
+            script: "x",
+        };
+        match context.write_object1(action) {
+            Err(Error::Conformance { object: Some(_), message }) => {
+                assert_eq!(message, "a JavaScript action is used")
+            }
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+
+        let mut output = Vec::new();
+        let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_7).unwrap();
+        context
+            .set_pdfa_conformance(PdfAConformance::PdfA2b)
+            .unwrap();
+        let mut xmp_metadata = XmpMetadata::new();
+        xmp_metadata.add_property("http://ns.example.com/dam/1.0/", "dam", "AssetId", "A-17");
+        context.xmp_metadata = Some(xmp_metadata);
+        context.finish().unwrap();
+
+        let document = ExistingDocument::parse(&output).unwrap();
+        assert!(document.trailer.get("ID").is_some());
+        let catalog = document
+            .resolve_dictionary(document.trailer.get_reference("Root").unwrap())
+            .unwrap();
+        let output_intents = catalog.get("OutputIntents").unwrap().as_array().unwrap();
+        let output_intent = output_intents[0].as_dictionary().unwrap();
+        assert_eq!(output_intent.get("S").unwrap().0, b"/GTS_PDFA1");
+        let (profile, _) = document
+            .resolve_stream(output_intent.get_reference("DestOutputProfile").unwrap())
+            .unwrap();
+        assert_eq!(profile.get_integer("N"), Some(3));
+        let (_, metadata) = document
+            .resolve_stream(catalog.get_reference("Metadata").unwrap())
+            .unwrap();
+        let metadata = String::from_utf8(metadata).unwrap();
+        assert!(metadata.contains("<pdfaid:part>2</pdfaid:part>"));
+        assert!(metadata.contains("<pdfaid:conformance>B</pdfaid:conformance>"));
+        assert!(metadata.contains(
+            "<pdfaSchema:namespaceURI>http://ns.example.com/dam/1.0/</pdfaSchema:namespaceURI>"
+        ));
+        assert!(metadata.contains("<pdfaProperty:name>AssetId</pdfaProperty:name>"));
+
+        let mut context = DocumentContext::with_writer(Vec::new(), Version::Pdf2_0).unwrap();
+        assert!(matches!(
+            context.set_pdfa_conformance(PdfAConformance::PdfA3b),
+            Err(Error::Conformance { object: None, .. })
+        ));
+    }
+}
@@ -0,0 +1,300 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! PDF/UA, the subset of tagged PDF for universal accessibility.
+
+use crate::object::RawIndirectReference;
+use crate::structure_tree::{StandardStructureType, StructureElement};
+use crate::{Error, Result, Version};
+
+/// A PDF/UA conformance level, see `DocumentContext::set_pdfua_conformance`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PdfUAConformance {
+    /// PDF/UA-1 (ISO 14289-1).
+    PdfUA1,
+}
+
+impl PdfUAConformance {
+    /// The part of ISO 14289.
+    pub fn part(self) -> u8 {
+        match self {
+            PdfUAConformance::PdfUA1 => 1,
+        }
+    }
+}
+
+/// PDF/UA-1 is based on PDF 1.7.
+pub(crate) fn check_version(version: Version) -> Result<()> {
+    if version <= Version::Pdf1_7 {
+        return Ok(());
+    }
+    Err(Error::Conformance {
+        object: None,
+        message: format!("PDF/UA-1 does not allow PDF version {}", version.number()),
+    })
+}
+
+/// The operators that paint content, which has to be tagged or marked as an artifact.
+const PAINTING_OPERATORS: [&str; 16] = [
+    "S", "s", "f", "F", "f*", "B", "B*", "b", "b*", "sh", "Do", "BI", "Tj", "TJ", "'", "\"",
+];
+
+/// Check that the content painted by `operator` is `tagged`, i.e. part of a marked-content
+/// sequence of tagged content or an artifact.
+pub(crate) fn check_operator(operator: &str, tagged: bool) -> Result<()> {
+    if tagged || !PAINTING_OPERATORS.contains(&operator) {
+        return Ok(());
+    }
+    Err(Error::Conformance {
+        object: None,
+        message: format!(
+            "the content painted by {} is neither tagged nor an artifact",
+            operator
+        ),
+    })
+}
+
+/// Checks the structure elements in document order.
+#[derive(Debug, Default)]
+pub(crate) struct StructureChecker {
+    /// The level of the previous numbered heading, 0 before the first one.
+    heading_level: u8,
+}
+
+impl StructureChecker {
+    pub fn check(
+        &mut self,
+        reference: RawIndirectReference,
+        element: &StructureElement,
+    ) -> Result<()> {
+        use StandardStructureType::*;
+        let violation = |message: String| Error::Conformance {
+            object: Some(reference),
+            message,
+        };
+
+        let structure_type = element.structure_type;
+        let needs_alt = structure_type == Figure || structure_type == Formula;
+        if needs_alt && element.alt.is_none() && element.actual_text.is_none() {
+            return Err(violation(format!(
+                "the {:?} has no alternate description",
+                structure_type
+            )));
+        }
+        if let Some(level) = structure_type.heading_level() {
+            if level > self.heading_level + 1 {
+                let message = match self.heading_level {
+                    0 => format!("the first heading is H{} instead of H1", level),
+                    previous => format!("the heading H{} follows H{}", level, previous),
+                };
+                return Err(violation(message));
+            }
+            self.heading_level = level;
+        }
+        if structure_type == Table && !element.contains(TH) {
+            return Err(violation("the table has no header cells".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pagetree::Page;
+    use crate::reader::ExistingDocument;
+    use crate::{DocumentContext, Pt};
+
+    fn message(result: Result<()>) -> String {
+        match result {
+            Err(Error::Conformance { message, .. }) => message,
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_check_operator() {
+        assert!(check_operator("f", true).is_ok());
+        assert!(check_operator("re", false).is_ok());
+        assert_eq!(
+            message(check_operator("Tj", false)),
+            "the content painted by Tj is neither tagged nor an artifact"
+        );
+        assert!(check_version(Version::Pdf1_7).is_ok());
+        assert!(check_version(Version::Pdf2_0).is_err());
+    }
+
+    #[test]
+    fn test_structure_checker() {
+        use StandardStructureType::*;
+        let reference = RawIndirectReference(5, 0);
+        let check = |checker: &mut StructureChecker, element: &StructureElement| {
+            checker.check(reference, element)
+        };
+        let mut checker = StructureChecker::default();
+
+        let mut figure = StructureElement::new(Figure);
+        assert!(matches!(
+            check(&mut checker, &figure),
+            Err(Error::Conformance { object: Some(object), .. }) if object == reference
+        ));
+        figure.actual_text = Some("A red square".to_string());
+        assert!(check(&mut checker, &figure).is_ok());
+        let formula = StructureElement::new(Formula);
+        assert_eq!(
+            message(check(&mut checker, &formula)),
+            "the Formula has no alternate description"
+        );
+
+        assert_eq!(
+            message(check(&mut checker, &StructureElement::new(H3))),
+            "the first heading is H3 instead of H1"
+        );
+        for &heading in &[H1, H2, H2, H1, H2, H3] {
+            assert!(check(&mut checker, &StructureElement::new(heading)).is_ok());
+        }
+        assert_eq!(
+            message(check(&mut checker, &StructureElement::new(H5))),
+            "the heading H5 follows H3"
+        );
+
+        let mut table = StructureElement::new(Table);
+        let mut row = StructureElement::new(TR);
+        row.add_child(StructureElement::new(TD));
+        table.add_child(row.clone());
+        assert_eq!(
+            message(check(&mut checker, &table)),
+            "the table has no header cells"
+        );
+        let mut header_row = StructureElement::new(TR);
+        header_row.add_child(StructureElement::new(TH));
+        let mut table = StructureElement::new(Table);
+        table.add_child(header_row);
+        table.add_child(row);
+        assert!(check(&mut checker, &table).is_ok());
+    }
+
+    #[test]
+    fn test_pdfua() {
+        let mut output = Vec::new();
+        let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_7).unwrap();
+        context
+            .set_pdfua_conformance(PdfUAConformance::PdfUA1)
+            .unwrap();
+        context.language = Some("en-US".to_string());
+        context.document_info.title = Some("Annual Report".to_string());
+        let mut page = Page::new();
+        page.add_content(&mut context, None, |page| {
+            page.begin_artifact()?;
+            page.rect(Pt(10.0), Pt(10.0), Pt(100.0), Pt(1.0))?;
+            page.fill_path()?;
+            page.end_marked_content()
+        })
+        .unwrap();
+        // every tag is in a content stream of its own, the identifiers are unique on the page
+        for (index, &tag) in [StandardStructureType::H1, StandardStructureType::Figure]
+            .iter()
+            .enumerate()
+        {
+            page.add_content(&mut context, None, |page| {
+                assert_eq!(page.begin_tagged_content(tag)?, index as i64);
+                page.rect(Pt(10.0), Pt(100.0), Pt(100.0), Pt(100.0))?;
+                page.fill_path()?;
+                page.end_marked_content()
+            })
+            .unwrap();
+        }
+        // untagged content is rejected while it is added
+        let result = page.add_content(&mut context, None, |page| {
+            page.rect(Pt(10.0), Pt(10.0), Pt(100.0), Pt(1.0))?;
+            page.fill_path()
+        });
+        assert!(matches!(
+            result,
+            Err(Error::Conformance { object: None, .. })
+        ));
+        let page = context.add_page(page).unwrap();
+        let mut heading = StructureElement::new(StandardStructureType::H1);
+        heading.add_marked_content(page, 0);
+        let mut figure = StructureElement::new(StandardStructureType::Figure);
+        figure.alt = Some("A red square".to_string());
+        figure.add_marked_content(page, 1);
+        let mut document_element = StructureElement::new(StandardStructureType::Document);
+        document_element.add_child(heading);
+        document_element.add_child(figure);
+        context.structure_tree = vec![document_element];
+        context.finish().unwrap();
+
+        let document = ExistingDocument::parse(&output).unwrap();
+        let catalog = document
+            .resolve_dictionary(document.trailer.get_reference("Root").unwrap())
+            .unwrap();
+        assert_eq!(catalog.get("Lang").unwrap().0, b"(en-US)");
+        let mark_info = catalog.get("MarkInfo").unwrap().as_dictionary().unwrap();
+        assert_eq!(mark_info.get("Marked").unwrap().0, b"true");
+        let viewer_preferences = catalog
+            .get("ViewerPreferences")
+            .unwrap()
+            .as_dictionary()
+            .unwrap();
+        assert_eq!(
+            viewer_preferences.get("DisplayDocTitle").unwrap().0,
+            b"true"
+        );
+        let (_, metadata) = document
+            .resolve_stream(catalog.get_reference("Metadata").unwrap())
+            .unwrap();
+        assert!(String::from_utf8(metadata)
+            .unwrap()
+            .contains("<pdfuaid:part>1</pdfuaid:part>"));
+
+        let root = document
+            .resolve_dictionary(catalog.get_reference("StructTreeRoot").unwrap())
+            .unwrap();
+        assert_eq!(root.get_integer("ParentTreeNextKey"), Some(1));
+        let parent_tree = document
+            .resolve_dictionary(root.get_reference("ParentTree").unwrap())
+            .unwrap();
+        let nums = parent_tree.get("Nums").unwrap().as_array().unwrap();
+        assert_eq!(nums[0].as_integer(), Some(0));
+        let parents = nums[1].as_array().unwrap();
+        assert_eq!(parents.len(), 2);
+        let heading = document
+            .resolve_dictionary(parents[0].as_reference().unwrap())
+            .unwrap();
+        assert_eq!(heading.get("S").unwrap().0, b"/H1");
+        let kids = heading.get("K").unwrap().as_array().unwrap();
+        assert_eq!(kids[0].as_integer(), Some(0));
+        let figure = document
+            .resolve_dictionary(parents[1].as_reference().unwrap())
+            .unwrap();
+        assert_eq!(figure.get("Alt").unwrap().0, b"(A\\040red\\040square)");
+
+        // the marked content referenced by the structure elements is on the page
+        let page = document
+            .resolve_dictionary(heading.get_reference("Pg").unwrap())
+            .unwrap();
+        assert_eq!(page.get_integer("StructParents"), Some(0));
+        let contents = page.get("Contents").unwrap().as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        let content: Vec<u8> = contents
+            .iter()
+            .flat_map(|stream| document.resolve_stream(stream.as_reference().unwrap()).unwrap().1)
+            .collect();
+        let content = String::from_utf8(content).unwrap();
+        assert!(content.contains("/Artifact BMC"));
+        assert!(content.contains("/H1 <</MCID 0>> BDC"));
+        assert!(content.contains("/Figure <</MCID 1>> BDC"));
+    }
+}
@@ -0,0 +1,879 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! A minimal reader for existing PDF files.
+//!
+//! It understands just enough of the file structure to append an incremental update: the chain
+//! of cross-reference sections (tables as well as streams), the trailer and the top-level
+//! entries of dictionary objects. Values are kept as unparsed bytes and written back verbatim.
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+
+use crate::object::{Formatter, PdfFormat, RawIndirectReference};
+use crate::{Error, Result};
+
+/// Nested arrays and dictionaries deeper than this are rejected instead of risking a stack
+/// overflow.
+const MAX_NESTING: usize = 256;
+
+fn invalid_data(msg: impl Into<String>) -> Error {
+    Error::Parse(msg.into())
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, 0 | 9 | 10 | 12 | 13 | 32)
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(
+        byte,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+fn is_regular(byte: u8) -> bool {
+    !is_whitespace(byte) && !is_delimiter(byte)
+}
+
+/// Skip whitespace and comments.
+fn skip_whitespace(data: &[u8], pos: usize) -> usize {
+    let mut pos = pos.min(data.len());
+    while pos < data.len() {
+        if is_whitespace(data[pos]) {
+            pos += 1;
+        } else if data[pos] == b'%' {
+            while pos < data.len() && data[pos] != b'\n' && data[pos] != b'\r' {
+                pos += 1;
+            }
+        } else {
+            break;
+        }
+    }
+    pos
+}
+
+/// Returns the end of the run of regular characters starting at `pos`.
+fn regular_end(data: &[u8], mut pos: usize) -> usize {
+    while pos < data.len() && is_regular(data[pos]) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Read the next keyword or number starting at `pos` (after skipping whitespace).
+fn next_token(data: &[u8], pos: usize) -> (&[u8], usize) {
+    let start = skip_whitespace(data, pos);
+    let end = regular_end(data, start);
+    (&data[start..end], end)
+}
+
+fn parse_number<T: std::str::FromStr>(token: &[u8]) -> Option<T> {
+    std::str::from_utf8(token).ok()?.trim().parse().ok()
+}
+
+/// Returns the end of the value starting at `pos` (after skipping whitespace). Indirect
+/// references `n g R` are treated as a single value.
+fn skip_value(data: &[u8], pos: usize) -> Result<usize> {
+    skip_nested_value(data, pos, 0)
+}
+
+fn skip_nested_value(data: &[u8], pos: usize, depth: usize) -> Result<usize> {
+    if depth > MAX_NESTING {
+        return Err(invalid_data("objects are nested too deeply"));
+    }
+    let pos = skip_whitespace(data, pos);
+    let eof = || invalid_data("unexpected end of file");
+    match data.get(pos).copied().ok_or_else(eof)? {
+        b'/' => Ok(regular_end(data, pos + 1)),
+        b'(' => {
+            let mut depth = 0;
+            let mut pos = pos;
+            loop {
+                match data.get(pos).copied().ok_or_else(eof)? {
+                    b'\\' => pos += 1,
+                    b'(' => depth += 1,
+                    b')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Ok(pos + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                pos += 1;
+            }
+        }
+        b'<' if data.get(pos + 1) == Some(&b'<') => {
+            let (_, end) = parse_nested_dictionary(data, pos, depth + 1)?;
+            Ok(end)
+        }
+        b'<' => data[pos..]
+            .iter()
+            .position(|&byte| byte == b'>')
+            .map(|len| pos + len + 1)
+            .ok_or_else(eof),
+        b'[' => {
+            let mut pos = pos + 1;
+            loop {
+                pos = skip_whitespace(data, pos);
+                match data.get(pos).copied().ok_or_else(eof)? {
+                    b']' => return Ok(pos + 1),
+                    _ => pos = skip_nested_value(data, pos, depth + 1)?,
+                }
+            }
+        }
+        byte if is_regular(byte) => {
+            let end = regular_end(data, pos);
+            if parse_number::<u32>(&data[pos..end]).is_some() {
+                // check whether this is the start of an indirect reference
+                let (generation, generation_end) = next_token(data, end);
+                let (keyword, keyword_end) = next_token(data, generation_end);
+                if parse_number::<u32>(generation).is_some() && keyword == b"R" {
+                    return Ok(keyword_end);
+                }
+            }
+            Ok(end)
+        }
+        other => Err(invalid_data(format!(
+            "unexpected character {:?} at offset {}",
+            other as char, pos
+        ))),
+    }
+}
+
+/// The unparsed bytes of a PDF object. They are written back verbatim.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RawObject(pub Vec<u8>);
+
+impl RawObject {
+    pub fn as_integer(&self) -> Option<i64> {
+        parse_number(&self.0)
+    }
+
+    pub fn as_reference(&self) -> Option<RawIndirectReference> {
+        let mut parts = self.0.split(|&byte| is_whitespace(byte)).filter(|s| !s.is_empty());
+        let number = parse_number(parts.next()?)?;
+        let generation = parse_number(parts.next()?)?;
+        match (parts.next(), parts.next()) {
+            (Some(b"R"), None) => Some(RawIndirectReference(number, generation)),
+            _ => None,
+        }
+    }
+
+    /// Returns the elements of an array.
+    pub fn as_array(&self) -> Option<Vec<RawObject>> {
+        let data = &self.0;
+        let mut pos = skip_whitespace(data, 0);
+        if data.get(pos) != Some(&b'[') {
+            return None;
+        }
+        pos += 1;
+        let mut elements = Vec::new();
+        loop {
+            pos = skip_whitespace(data, pos);
+            match data.get(pos)? {
+                b']' => return Some(elements),
+                _ => {
+                    let end = skip_value(data, pos).ok()?;
+                    elements.push(RawObject(data[pos..end].to_vec()));
+                    pos = end;
+                }
+            }
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<RawDictionary> {
+        let pos = skip_whitespace(&self.0, 0);
+        parse_dictionary(&self.0, pos).ok().map(|(dict, _)| dict)
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.0 == b"null"
+    }
+
+    /// Returns the indirect references in the object, including those inside of arrays and
+    /// dictionaries.
+    pub fn references(&self) -> Vec<RawIndirectReference> {
+        let mut references = Vec::new();
+        self.collect_references(&mut references);
+        references
+    }
+
+    fn collect_references(&self, references: &mut Vec<RawIndirectReference>) {
+        if let Some(reference) = self.as_reference() {
+            references.push(reference);
+        } else if let Some(dictionary) = self.as_dictionary() {
+            for (_, value) in dictionary.iter() {
+                value.collect_references(references);
+            }
+        } else if let Some(array) = self.as_array() {
+            for value in &array {
+                value.collect_references(references);
+            }
+        }
+    }
+}
+
+impl PdfFormat for RawObject {
+    fn write(&self, output: &mut Formatter) -> std::io::Result<()> {
+        output.write_all(&self.0)
+    }
+}
+
+/// A dictionary whose values are kept as unparsed bytes. The order of the entries is preserved.
+/// The keys are the bytes of the names without the leading slash, still escaped as in the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct RawDictionary {
+    entries: Vec<(Vec<u8>, RawObject)>,
+}
+
+impl RawDictionary {
+    pub fn get(&self, key: &str) -> Option<&RawObject> {
+        self.entries
+            .iter()
+            .find(|(entry_key, _)| entry_key == key.as_bytes())
+            .map(|(_, value)| value)
+    }
+
+    pub fn set(&mut self, key: impl AsRef<[u8]>, value: RawObject) {
+        let key = key.as_ref();
+        match self.entries.iter_mut().find(|(entry_key, _)| entry_key == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_vec(), value)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &RawObject)> {
+        self.entries
+            .iter()
+            .map(|(key, value)| (key.as_slice(), value))
+    }
+
+    pub fn get_integer(&self, key: &str) -> Option<i64> {
+        self.get(key).and_then(RawObject::as_integer)
+    }
+
+    pub fn get_reference(&self, key: &str) -> Option<RawIndirectReference> {
+        self.get(key).and_then(RawObject::as_reference)
+    }
+}
+
+impl PdfFormat for RawDictionary {
+    fn write(&self, f: &mut Formatter) -> std::io::Result<()> {
+        let mut dict_fmt = f.format_dictionary();
+        for (key, value) in &self.entries {
+            // the keys are still escaped as in the file
+            let key = RawObject([&b"/"[..], key].concat());
+            dict_fmt = dict_fmt.key_value(&key, value);
+        }
+        dict_fmt.finish()
+    }
+}
+
+/// Parse the dictionary starting at `pos` and return it together with its end position.
+fn parse_dictionary(data: &[u8], pos: usize) -> Result<(RawDictionary, usize)> {
+    parse_nested_dictionary(data, pos, 0)
+}
+
+fn parse_nested_dictionary(
+    data: &[u8],
+    pos: usize,
+    depth: usize,
+) -> Result<(RawDictionary, usize)> {
+    let mut pos = skip_whitespace(data, pos);
+    if !data[pos..].starts_with(b"<<") {
+        return Err(invalid_data(format!("expected a dictionary at offset {}", pos)));
+    }
+    pos += 2;
+    let mut dict = RawDictionary::default();
+    loop {
+        pos = skip_whitespace(data, pos);
+        if data[pos..].starts_with(b">>") {
+            return Ok((dict, pos + 2));
+        }
+        if data.get(pos) != Some(&b'/') {
+            return Err(invalid_data(format!(
+                "expected a dictionary key at offset {}",
+                pos
+            )));
+        }
+        let key_end = regular_end(data, pos + 1);
+        let key = data[pos + 1..key_end].to_vec();
+        let value_start = skip_whitespace(data, key_end);
+        let value_end = skip_nested_value(data, value_start, depth)?;
+        dict.entries
+            .push((key, RawObject(data[value_start..value_end].to_vec())));
+        pos = value_end;
+    }
+}
+
+/// The location of an object in an existing file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Location {
+    Free,
+    Offset(u64),
+    Compressed { stream: u32, index: u32 },
+}
+
+/// An existing PDF file that is to be extended by an incremental update.
+#[derive(Debug)]
+pub(crate) struct ExistingDocument<'a> {
+    data: &'a [u8],
+    /// The offset of the last cross-reference section.
+    pub startxref: u64,
+    /// The trailer of the last cross-reference section.
+    pub trailer: RawDictionary,
+    /// Whether the last cross-reference section is a cross-reference stream.
+    pub uses_xref_stream: bool,
+    locations: HashMap<u32, Location>,
+}
+
+impl<'a> ExistingDocument<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let startxref = find_startxref(data)?;
+        let mut document = ExistingDocument {
+            data,
+            startxref,
+            trailer: RawDictionary::default(),
+            uses_xref_stream: false,
+            locations: HashMap::new(),
+        };
+
+        let mut visited = HashSet::new();
+        let mut next = Some(startxref);
+        let mut first = true;
+        while let Some(offset) = next {
+            if !visited.insert(offset) {
+                return Err(invalid_data("cyclic /Prev chain in cross-reference"));
+            }
+            let (trailer, is_stream) = document.read_xref_section(offset)?;
+            // hybrid files reference an additional cross-reference stream from the trailer
+            if let Some(xref_stm) = trailer.get_integer("XRefStm") {
+                document.read_xref_section(xref_stm as u64)?;
+            }
+            next = trailer.get_integer("Prev").map(|prev| prev as u64);
+            if first {
+                document.trailer = trailer;
+                document.uses_xref_stream = is_stream;
+                first = false;
+            }
+        }
+        Ok(document)
+    }
+
+    /// One more than the highest object number, i.e. the first number that is free for new
+    /// objects. This is the `/Size` of the trailer unless the cross-reference sections contain
+    /// higher object numbers.
+    pub fn size(&self) -> Result<u32> {
+        let size = self
+            .trailer
+            .get_integer("Size")
+            .ok_or_else(|| invalid_data("trailer has no /Size entry"))?;
+        let size = u32::try_from(size)
+            .map_err(|_| invalid_data(format!("invalid /Size {} in trailer", size)))?;
+        // a /Size that is too small must not make new objects replace existing ones
+        let next_number = match self.locations.keys().max() {
+            Some(&highest) => highest
+                .checked_add(1)
+                .ok_or_else(|| invalid_data("object number out of range"))?,
+            None => 1,
+        };
+        Ok(size.max(next_number))
+    }
+
+    fn add_location(&mut self, number: u32, location: Location) {
+        // sections are read from newest to oldest, so the first entry wins
+        self.locations.entry(number).or_insert(location);
+    }
+
+    /// Read the cross-reference section at `offset`. Returns its trailer dictionary and
+    /// whether it is a cross-reference stream.
+    fn read_xref_section(&mut self, offset: u64) -> Result<(RawDictionary, bool)> {
+        let data = self.data;
+        let pos = skip_whitespace(data, offset as usize);
+        if !data[pos..].starts_with(b"xref") {
+            let (dict, stream) = self.read_object_at(offset)?;
+            self.read_xref_stream(&dict, &stream)?;
+            return Ok((dict, true));
+        }
+
+        let mut pos = pos + 4;
+        loop {
+            let (token, end) = next_token(data, pos);
+            if token == b"trailer" {
+                let (trailer, _) = parse_dictionary(data, end)?;
+                return Ok((trailer, false));
+            }
+            let start: u32 = parse_number(token)
+                .ok_or_else(|| invalid_data("invalid cross-reference subsection"))?;
+            let (count, end) = next_token(data, end);
+            let count: u32 = parse_number(count)
+                .ok_or_else(|| invalid_data("invalid cross-reference subsection"))?;
+            pos = end;
+            for number in start..start.saturating_add(count) {
+                let (offset, end) = next_token(data, pos);
+                let (_generation, end) = next_token(data, end);
+                let (kind, end) = next_token(data, end);
+                pos = end;
+                let location = match kind {
+                    b"n" => Location::Offset(
+                        parse_number(offset)
+                            .ok_or_else(|| invalid_data("invalid cross-reference entry"))?,
+                    ),
+                    b"f" => Location::Free,
+                    _ => return Err(invalid_data("invalid cross-reference entry")),
+                };
+                self.add_location(number, location);
+            }
+        }
+    }
+
+    fn read_xref_stream(&mut self, dict: &RawDictionary, data: &[u8]) -> Result<()> {
+        let widths = dict
+            .get("W")
+            .and_then(RawObject::as_array)
+            .and_then(|widths| widths.iter().map(RawObject::as_integer).collect::<Option<Vec<_>>>())
+            .filter(|widths| widths.len() == 3)
+            .ok_or_else(|| invalid_data("cross-reference stream has an invalid /W entry"))?;
+        let widths = widths
+            .into_iter()
+            .map(|width| usize::try_from(width).ok().filter(|&width| width <= 8))
+            .collect::<Option<Vec<usize>>>()
+            .ok_or_else(|| invalid_data("cross-reference stream has an invalid /W entry"))?;
+        let row_len: usize = widths.iter().sum();
+        let index = match dict.get("Index").and_then(RawObject::as_array) {
+            Some(index) => index
+                .iter()
+                .map(RawObject::as_integer)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid_data("cross-reference stream has an invalid /Index"))?,
+            None => vec![0, dict.get_integer("Size").unwrap_or(0)],
+        };
+
+        let read_field = |row: &[u8]| row.iter().fold(0u64, |acc, &byte| acc << 8 | u64::from(byte));
+        let mut rows = data.chunks(row_len.max(1));
+        for range in index.chunks(2) {
+            let (start, count) = match *range {
+                [start, count] => (start as u32, count as u32),
+                _ => return Err(invalid_data("cross-reference stream has an invalid /Index")),
+            };
+            for number in start..start.saturating_add(count) {
+                let row = rows
+                    .next()
+                    .filter(|row| row.len() == row_len)
+                    .ok_or_else(|| invalid_data("cross-reference stream is too short"))?;
+                let kind = if widths[0] == 0 {
+                    1
+                } else {
+                    read_field(&row[..widths[0]])
+                };
+                let field2 = read_field(&row[widths[0]..widths[0] + widths[1]]);
+                let field3 = read_field(&row[widths[0] + widths[1]..]);
+                let location = match kind {
+                    0 => Location::Free,
+                    1 => Location::Offset(field2),
+                    2 => Location::Compressed {
+                        stream: field2 as u32,
+                        index: field3 as u32,
+                    },
+                    // unknown entry types are to be treated as null objects
+                    _ => continue,
+                };
+                self.add_location(number, location);
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the indirect object at `offset`. Returns its dictionary and, if the object is a
+    /// stream, the decoded stream data.
+    fn read_object_at(&self, offset: u64) -> Result<(RawDictionary, Vec<u8>)> {
+        let data = self.data;
+        let (_number, end) = next_token(data, offset as usize);
+        let (_generation, end) = next_token(data, end);
+        let (keyword, end) = next_token(data, end);
+        if keyword != b"obj" {
+            return Err(invalid_data(format!("expected an object at offset {}", offset)));
+        }
+        let (dict, end) = parse_dictionary(data, end)?;
+        let (keyword, mut end) = next_token(data, end);
+        if keyword != b"stream" {
+            return Ok((dict, Vec::new()));
+        }
+        if data.get(end) == Some(&b'\r') {
+            end += 1;
+        }
+        if data.get(end) == Some(&b'\n') {
+            end += 1;
+        }
+
+        let length = match dict.get("Length") {
+            Some(length) => match length.as_reference() {
+                // An indirect length inside of an object stream could refer back to the
+                // object stream itself, so only uncompressed objects are accepted.
+                Some(reference) => match self.locations.get(&(reference.0 as u32)) {
+                    Some(Location::Offset(_)) => self.resolve(reference)?.as_integer(),
+                    _ => None,
+                },
+                None => length.as_integer(),
+            },
+            None => None,
+        }
+        .ok_or_else(|| invalid_data("stream has an invalid /Length"))?;
+        let raw = usize::try_from(length)
+            .ok()
+            .and_then(|length| data.get(end..end.checked_add(length)?))
+            .ok_or_else(|| invalid_data("stream extends beyond the end of the file"))?;
+        let decoded = decode_stream(&dict, raw)?;
+        Ok((dict, decoded))
+    }
+
+    /// Resolve an indirect reference to a stream. Returns its dictionary and the decoded
+    /// stream data.
+    #[cfg(test)]
+    pub fn resolve_stream(
+        &self,
+        reference: RawIndirectReference,
+    ) -> Result<(RawDictionary, Vec<u8>)> {
+        match self.locations.get(&(reference.0 as u32)) {
+            Some(&Location::Offset(offset)) => self.read_object_at(offset),
+            // streams cannot be stored in object streams
+            Some(Location::Compressed { .. }) => Err(invalid_data(format!(
+                "object {} is not a stream",
+                reference.0
+            ))),
+            _ => Err(Error::UnresolvedReference(reference)),
+        }
+    }
+
+    /// Resolve an indirect reference to the unparsed value of the object.
+    pub fn resolve(&self, reference: RawIndirectReference) -> Result<RawObject> {
+        let missing = || Error::UnresolvedReference(reference);
+        match self
+            .locations
+            .get(&(reference.0 as u32))
+            .ok_or_else(missing)?
+        {
+            Location::Free => Err(missing()),
+            &Location::Offset(offset) => {
+                let data = self.data;
+                let (_number, end) = next_token(data, offset as usize);
+                let (_generation, end) = next_token(data, end);
+                let (keyword, end) = next_token(data, end);
+                if keyword != b"obj" {
+                    return Err(invalid_data(format!("expected an object at offset {}", offset)));
+                }
+                let start = skip_whitespace(data, end);
+                let end = skip_value(data, start)?;
+                Ok(RawObject(data[start..end].to_vec()))
+            }
+            &Location::Compressed { stream, index } => {
+                let offset = match self.locations.get(&stream) {
+                    Some(&Location::Offset(offset)) => offset,
+                    _ => return Err(invalid_data(format!("object stream {} is missing", stream))),
+                };
+                let (dict, data) = self.read_object_at(offset)?;
+                let first = dict
+                    .get_integer("First")
+                    .ok_or_else(|| invalid_data("object stream has no /First entry"))?;
+                let count = dict
+                    .get_integer("N")
+                    .ok_or_else(|| invalid_data("object stream has no /N entry"))?;
+                if i64::from(index) >= count {
+                    return Err(invalid_data(format!(
+                        "object stream {} has no object at index {}",
+                        stream, index
+                    )));
+                }
+                let invalid_header = || invalid_data("invalid object stream header");
+                let mut pos = 0;
+                let mut object_offset = 0;
+                for _ in 0..=index {
+                    if pos >= data.len() {
+                        return Err(invalid_header());
+                    }
+                    let (number, end) = next_token(&data, pos);
+                    let (offset, end) = next_token(&data, end);
+                    parse_number::<u32>(number).ok_or_else(invalid_header)?;
+                    object_offset = parse_number::<usize>(offset).ok_or_else(invalid_header)?;
+                    pos = end;
+                }
+                let start = usize::try_from(first)
+                    .ok()
+                    .and_then(|first| first.checked_add(object_offset))
+                    .ok_or_else(invalid_header)?;
+                let start = skip_whitespace(&data, start);
+                let end = skip_value(&data, start)?;
+                Ok(RawObject(data[start..end].to_vec()))
+            }
+        }
+    }
+
+    /// Resolve an indirect reference to a dictionary object.
+    pub fn resolve_dictionary(&self, reference: RawIndirectReference) -> Result<RawDictionary> {
+        self.resolve(reference)?.as_dictionary().ok_or_else(|| {
+            invalid_data(format!("object {:?} is not a dictionary", reference))
+        })
+    }
+}
+
+/// Find the offset of the last cross-reference section.
+fn find_startxref(data: &[u8]) -> Result<u64> {
+    let tail_start = data.len().saturating_sub(1024);
+    let tail = &data[tail_start..];
+    let pos = tail
+        .windows(9)
+        .rposition(|window| window == b"startxref")
+        .ok_or_else(|| invalid_data("could not find startxref"))?;
+    let (offset, _) = next_token(tail, pos + 9);
+    parse_number(offset).ok_or_else(|| invalid_data("invalid startxref offset"))
+}
+
+fn decode_stream(dict: &RawDictionary, raw: &[u8]) -> Result<Vec<u8>> {
+    let filter = dict.get("Filter").map(|filter| match filter.as_array() {
+        Some(filters) => filters.into_iter().map(|filter| filter.0).collect(),
+        None => vec![filter.0.clone()],
+    });
+    let data = match filter.as_deref() {
+        None | Some([]) => raw.to_vec(),
+        Some([filter]) if filter == b"/FlateDecode" => {
+            let mut decoded = Vec::new();
+            ZlibDecoder::new(raw)
+                .read_to_end(&mut decoded)
+                .map_err(|err| invalid_data(format!("invalid compressed stream: {}", err)))?;
+            decoded
+        }
+        Some(_) => return Err(invalid_data("unsupported stream filter")),
+    };
+
+    let params = dict.get("DecodeParms").and_then(|params| {
+        params
+            .as_dictionary()
+            .or_else(|| params.as_array()?.first()?.as_dictionary())
+    });
+    match params {
+        Some(params) => {
+            let predictor = params.get_integer("Predictor").unwrap_or(1);
+            let columns = usize::try_from(params.get_integer("Columns").unwrap_or(1))
+                .ok()
+                .filter(|&columns| columns > 0)
+                .ok_or_else(|| invalid_data("invalid predictor columns"))?;
+            match predictor {
+                1 => Ok(data),
+                10..=15 => png_unpredict(&data, columns),
+                _ => Err(invalid_data("unsupported stream predictor")),
+            }
+        }
+        None => Ok(data),
+    }
+}
+
+/// Undo the PNG predictors for data with one byte per pixel.
+fn png_unpredict(data: &[u8], columns: usize) -> Result<Vec<u8>> {
+    if columns
+        .checked_add(1)
+        .is_none_or(|row_len| !data.len().is_multiple_of(row_len))
+    {
+        return Err(invalid_data("invalid predictor row"));
+    }
+    let mut output = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; columns.min(data.len())];
+    for row in data.chunks(columns + 1) {
+        if row.len() != columns + 1 {
+            return Err(invalid_data("invalid predictor row"));
+        }
+        let mut current = row[1..].to_vec();
+        for i in 0..columns {
+            let left = if i > 0 { current[i - 1] } else { 0 };
+            let up = previous[i];
+            let up_left = if i > 0 { previous[i - 1] } else { 0 };
+            current[i] = current[i].wrapping_add(match row[0] {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((u16::from(left) + u16::from(up)) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(invalid_data("invalid predictor row")),
+            });
+        }
+        output.extend_from_slice(&current);
+        previous = current;
+    }
+    Ok(output)
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let p = i16::from(left) + i16::from(up) - i16::from(up_left);
+    let pa = (p - i16::from(left)).abs();
+    let pb = (p - i16::from(up)).abs();
+    let pc = (p - i16::from(up_left)).abs();
+    if pa <= pb && pa <= pc {
+        left
+    } else if pb <= pc {
+        up
+    } else {
+        up_left
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_dictionary() {
+        let data = b"<< /Type /Pages /Kids [ 3 0 R 4 0 R ] /Count 2 /Name (a\\)b) /D << /X 1 >> >>";
+        let (dict, end) = parse_dictionary(data, 0).unwrap();
+        assert_eq!(end, data.len());
+        assert_eq!(dict.get("Type"), Some(&RawObject(b"/Pages".to_vec())));
+        assert_eq!(dict.get_integer("Count"), Some(2));
+        assert_eq!(dict.get("Name"), Some(&RawObject(b"(a\\)b)".to_vec())));
+        let kids = dict.get("Kids").and_then(RawObject::as_array).unwrap();
+        assert_eq!(kids[1].as_reference(), Some(RawIndirectReference(4, 0)));
+        let nested = dict.get("D").and_then(RawObject::as_dictionary).unwrap();
+        assert_eq!(nested.get_integer("X"), Some(1));
+
+        let data = b"<< /A#20B 1 >>";
+        let (dict, _) = parse_dictionary(data, 0).unwrap();
+        let mut output = Vec::new();
+        dict.write(&mut Formatter::new(&mut output)).unwrap();
+        assert_eq!(output, b"<< /A#20B 1\n>>");
+
+        // names that are not UTF-8 are kept as they are
+        let data = b"<< /A\xE4 1 >>";
+        let (dict, _) = parse_dictionary(data, 0).unwrap();
+        let mut output = Vec::new();
+        dict.write(&mut Formatter::new(&mut output)).unwrap();
+        assert_eq!(output, b"<< /A\xE4 1\n>>");
+    }
+
+    #[test]
+    fn test_read_xref_table() {
+        let mut data = b"%PDF-1.7\n".to_vec();
+        let catalog_offset = data.len();
+        data.extend_from_slice(b"1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n");
+        let xref_offset = data.len();
+        write!(
+            data,
+            "xref\n0 2\n0000000000 65535 f\r\n{:0>10} 00000 n\r\ntrailer\n<< /Size 2 /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            catalog_offset, xref_offset
+        )
+        .unwrap();
+
+        let document = ExistingDocument::parse(&data).unwrap();
+        assert_eq!(document.startxref, xref_offset as u64);
+        assert_eq!(document.size().unwrap(), 2);
+        let root = document.trailer.get_reference("Root").unwrap();
+        let catalog = document.resolve_dictionary(root).unwrap();
+        assert_eq!(catalog.get_reference("Pages"), Some(RawIndirectReference(2, 0)));
+
+        assert!(matches!(
+            document.resolve(RawIndirectReference(5, 0)),
+            Err(Error::UnresolvedReference(RawIndirectReference(5, 0)))
+        ));
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        let nested = format!("1 0 obj\n<< /A {}\nstartxref\n0\n", "[".repeat(100_000));
+        let inputs: Vec<&[u8]> = vec![
+            b"",
+            b"%PDF-1.7\nstartxref\n",
+            b"%PDF-1.7\nstartxref\n999999\n%%EOF",
+            b"xref\n4294967295 2\n",
+            b"1 0 obj\n<< /Length -5 >>\nstream\nstartxref\n0\n",
+            b"1 0 obj\n<< /Length 0 /W [ 1 100 1 ] >>\nstream\nstartxref\n0\n",
+            nested.as_bytes(),
+        ];
+        for input in inputs {
+            assert!(matches!(ExistingDocument::parse(input), Err(Error::Parse(_))));
+        }
+
+        let with_size = |size: &str| {
+            let mut data = b"%PDF-1.7\n".to_vec();
+            let catalog_offset = data.len();
+            data.extend_from_slice(b"1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n");
+            let pages_offset = data.len();
+            data.extend_from_slice(b"2 0 obj\n<< /Type /Pages /Kids [] /Count 0 >>\nendobj\n");
+            let xref_offset = data.len();
+            write!(
+                data,
+                "xref\n0 3\n0000000000 65535 f\r\n{:0>10} 00000 n\r\n{:0>10} 00000 n\r\ntrailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                catalog_offset, pages_offset, size, xref_offset
+            )
+            .unwrap();
+            data
+        };
+        for &size in &["-1", "4294967296", "1.5"] {
+            let data = with_size(size);
+            let document = ExistingDocument::parse(&data).unwrap();
+            assert!(matches!(document.size(), Err(Error::Parse(_))));
+        }
+        // new objects are numbered after the existing ones even if /Size is too small
+        for &(size, expected) in &[("0", 3), ("1", 3), ("10", 10)] {
+            let data = with_size(size);
+            let document = ExistingDocument::parse(&data).unwrap();
+            assert_eq!(document.size().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_object_stream() {
+        let object_stream = |count: u32, content: &str| {
+            let mut data = Vec::new();
+            write!(
+                data,
+                "1 0 obj\n<< /Type /ObjStm /N {} /First 8 /Length {} >>\nstream\n{}\nendstream\n",
+                count,
+                content.len(),
+                content
+            )
+            .unwrap();
+            data
+        };
+        fn document(data: &[u8]) -> ExistingDocument<'_> {
+            ExistingDocument {
+                data,
+                startxref: 0,
+                trailer: RawDictionary::default(),
+                uses_xref_stream: true,
+                locations: (0..4)
+                    .map(|index| (index + 2, Location::Compressed { stream: 1, index }))
+                    .chain(Some((1, Location::Offset(0))))
+                    .collect(),
+            }
+        }
+        let resolve = |document: &ExistingDocument, number| {
+            document.resolve(RawIndirectReference(number, 0))
+        };
+
+        let data = object_stream(2, "2 0 3 2 5 6");
+        let valid = document(&data);
+        assert_eq!(resolve(&valid, 2).unwrap(), RawObject(b"5".to_vec()));
+        assert_eq!(resolve(&valid, 3).unwrap(), RawObject(b"6".to_vec()));
+        // the index is checked against /N before the header is read
+        assert!(matches!(resolve(&valid, 4), Err(Error::Parse(_))));
+
+        // the header ends before the object, or has an invalid entry
+        for &content in &["2 0 3 2 ", "2 0 3 x 5 6", "2 0 [ ] 5 6"] {
+            let data = object_stream(u32::MAX, content);
+            let malformed = document(&data);
+            assert!(matches!(resolve(&malformed, 5), Err(Error::Parse(_))));
+        }
+    }
+}
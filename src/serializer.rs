@@ -6,9 +6,14 @@ use error::{Error, Result};
 
 use std::io::Write;
 
+use crate::encryption::ObjectCipher;
+use crate::object::WriteEscaped;
+
 #[derive(Debug)]
 pub struct PdfSerializer<W> {
     pub output: W,
+    /// Encrypts strings if the document is encrypted.
+    pub(crate) cipher: Option<ObjectCipher>,
 }
 
 impl<'b, W: Write> Serializer for &'b mut PdfSerializer<W> {
@@ -67,29 +72,24 @@ impl<'b, W: Write> Serializer for &'b mut PdfSerializer<W> {
         self.serialize_bytes(v.as_bytes())
     }
     fn serialize_bytes(self, bytes: &[u8]) -> Result<Self::Ok> {
-        self.output.write_all(b"(")?;
-        for &byte in bytes {
-            match byte {
-                0x0c /* Form Feed */ => self.output.write_all(b"\\f")?,
-                0x08 /* Backspace */ => self.output.write_all(b"\\b")?,
-                b'\t' => self.output.write_all(b"\\t")?,
-                b'\r' => self.output.write_all(b"\\r")?,
-                b'\n' => self.output.write_all(b"\\n")?,
-                b')' => self.output.write_all(b"\\)")?,
-                b'(' => self.output.write_all(b"\\(")?,
-                non_graphic if !byte.is_ascii_graphic() => write!(self.output, "\\d{:03o}", non_graphic)?,
-                other => self.output.write_all(&[other])?
-            }
+        if let Some(cipher) = &self.cipher {
+            let encrypted = cipher.encrypt(bytes);
+            self.output.write_all(b"<")?;
+            self.output.write_hex_escaped(&encrypted)?;
+            self.output.write_all(b">")?;
+            return Ok(());
         }
+        self.output.write_all(b"(")?;
+        self.output.write_escaped(bytes)?;
         self.output.write_all(b")")?;
         Ok(())
     }
     fn serialize_none(self) -> Result<Self::Ok> {
         Ok(write!(self.output, "null")?)
     }
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Self::Ok>
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         value.serialize(self)
     }
@@ -107,23 +107,23 @@ impl<'b, W: Write> Serializer for &'b mut PdfSerializer<W> {
     ) -> Result<Self::Ok> {
         Ok(write!(self.output, "/{}", variant)?)
     }
-    fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         value.serialize(self)
     }
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _value: &T,
     ) -> Result<Self::Ok>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        todo!()
+        Err(Error::Message(format!("cannot serialize enum variant {}", variant)))
     }
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
         write!(self.output, "[ ")?;
@@ -161,7 +161,7 @@ impl<'b, W: Write> Serializer for &'b mut PdfSerializer<W> {
     fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
         write!(self.output, "<< ")?;
         if !name.is_empty() {
-            write!(self.output, "/Type /{}\n", name)?;
+            writeln!(self.output, "/Type /{}", name)?;
         }
         Ok(self)
     }
@@ -169,14 +169,14 @@ impl<'b, W: Write> Serializer for &'b mut PdfSerializer<W> {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        todo!()
+        Err(Error::Message(format!("cannot serialize enum variant {}", variant)))
     }
-    fn collect_str<T: ?Sized>(self, value: &T) -> Result<Self::Ok>
+    fn collect_str<T>(self, value: &T) -> Result<Self::Ok>
     where
-        T: std::fmt::Display,
+        T: ?Sized + std::fmt::Display,
     {
         Ok(write!(self.output, "{}", value)?)
     }
@@ -188,12 +188,12 @@ pub struct TupleStructSerializer<'a, W> {
     name: &'static str,
 }
 
-impl<'a, W: Write> ser::SerializeSeq for &'a mut PdfSerializer<W> {
+impl<W: Write> ser::SerializeSeq for &mut PdfSerializer<W> {
     type Ok = ();
     type Error = Error;
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         value.serialize(&mut **self)?;
         Ok(write!(self.output, " ")?)
@@ -203,12 +203,12 @@ impl<'a, W: Write> ser::SerializeSeq for &'a mut PdfSerializer<W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeTuple for &'a mut PdfSerializer<W> {
+impl<W: Write> ser::SerializeTuple for &mut PdfSerializer<W> {
     type Ok = ();
     type Error = Error;
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         ser::SerializeSeq::serialize_element(self, value)
     }
@@ -217,12 +217,12 @@ impl<'a, W: Write> ser::SerializeTuple for &'a mut PdfSerializer<W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeTupleStruct for TupleStructSerializer<'a, W> {
+impl<W: Write> ser::SerializeTupleStruct for TupleStructSerializer<'_, W> {
     type Ok = ();
     type Error = Error;
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         ser::SerializeSeq::serialize_element(&mut self.serializer, value)
     }
@@ -231,12 +231,12 @@ impl<'a, W: Write> ser::SerializeTupleStruct for TupleStructSerializer<'a, W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeTupleVariant for TupleStructSerializer<'a, W> {
+impl<W: Write> ser::SerializeTupleVariant for TupleStructSerializer<'_, W> {
     type Ok = ();
     type Error = Error;
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         ser::SerializeTupleStruct::serialize_field(self, value)
     }
@@ -245,33 +245,33 @@ impl<'a, W: Write> ser::SerializeTupleVariant for TupleStructSerializer<'a, W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeMap for &'a mut PdfSerializer<W> {
+impl<W: Write> ser::SerializeMap for &mut PdfSerializer<W> {
     type Ok = ();
     type Error = Error;
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<()>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         ser::SerializeSeq::serialize_element(self, key)
     }
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         value.serialize(&mut **self)?;
-        Ok(write!(self.output, "\n")?)
+        Ok(writeln!(self.output)?)
     }
     fn end(self) -> Result<Self::Ok> {
         Ok(write!(self.output, ">>")?)
     }
 }
 
-impl<'a, W: Write> ser::SerializeStruct for &'a mut PdfSerializer<W> {
+impl<W: Write> ser::SerializeStruct for &mut PdfSerializer<W> {
     type Ok = ();
     type Error = Error;
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         self.serialize_unit_variant("struct", 0, key)?;
         write!(self.output, " ")?;
@@ -282,17 +282,17 @@ impl<'a, W: Write> ser::SerializeStruct for &'a mut PdfSerializer<W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeStructVariant for &'a mut PdfSerializer<W> {
+impl<W: Write> ser::SerializeStructVariant for &mut PdfSerializer<W> {
     type Ok = ();
     type Error = Error;
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, _value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        todo!()
+        Err(Error::Message("cannot serialize struct variants".to_string()))
     }
     fn end(self) -> Result<Self::Ok> {
-        todo!()
+        Err(Error::Message("cannot serialize struct variants".to_string()))
     }
 }
 
@@ -305,6 +305,7 @@ mod test {
         let mut content: Vec<u8> = vec![];
         let serializer = &mut PdfSerializer {
             output: &mut content,
+            cipher: None,
         };
         val.serialize(serializer).unwrap();
         assert_eq!(std::str::from_utf8(&content).unwrap(), against)
@@ -317,7 +318,7 @@ mod test {
 
     #[test]
     fn test_escape_sequence() {
-        test_serializer(["\n", "\x00"], "[ (\\n) (\\d000) ]")
+        test_serializer(["\n", "\x00", "\\"], "[ (\\n) (\\000) (\\\\) ]")
     }
 
     #[test]
@@ -335,7 +336,7 @@ mod test {
         struct TStruct {
             a: u32,
             b: &'static str,
-        };
+        }
         test_serializer(
             TStruct { a: 10, b: "ten" },
             "<< /Type /TStruct\n/a 10\n/b (ten)\n>>",
@@ -346,7 +347,7 @@ mod test {
         struct TStruct2 {
             a: u32,
             b: &'static str,
-        };
+        }
         test_serializer(TStruct2 { a: 10, b: "ten" }, "<< /a 10\n/b (ten)\n>>")
     }
 
@@ -356,12 +357,6 @@ mod test {
         enum TEnum {
             A,
             B(u32, u32),
-        };
-
-        #[derive(Serialize)]
-        struct CStruct {
-            a: u32,
-            b: &'static str,
         }
 
         test_serializer(TEnum::A, "/A");
@@ -11,24 +11,36 @@ pub enum Error {
     Utf8(Utf8Error),
     FromUtf8(FromUtf8Error),
     SpuriousDictEnd,
+    #[from(ignore)]
+    Message(String),
 }
 
 impl std::error::Error for Error {}
 
+impl From<Error> for crate::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(err) => crate::Error::from(err),
+            Error::SpuriousDictEnd => crate::Error::Parse("unexpected end of dictionary".to_string()),
+            other => crate::Error::Encoding(other.to_string()),
+        }
+    }
+}
+
 impl ser::Error for Error {
-    fn custom<T>(_msg: T) -> Self
+    fn custom<T>(msg: T) -> Self
     where
         T: std::fmt::Display,
     {
-        todo!()
+        Error::Message(msg.to_string())
     }
 }
 
 impl de::Error for Error {
-    fn custom<T>(_msg: T) -> Self
+    fn custom<T>(msg: T) -> Self
     where
         T: std::fmt::Display,
     {
-        todo!()
+        Error::Message(msg.to_string())
     }
 }
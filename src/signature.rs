@@ -0,0 +1,316 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! Digital signatures.
+//!
+//! A document is signed by passing a `Signature` to `DocumentContext::sign`. The signature
+//! dictionary is written with placeholders for `/ByteRange` and `/Contents`. Once the whole
+//! document has been written, the byte ranges are filled in and the `Signer` is called to
+//! compute the signature, which is then patched into the `/Contents` entry.
+
+use std::io::{Result, Write};
+
+use crate as lemon_pdf;
+use crate::object::{text_string, Formatter, HexString, IndirectReference, PdfFormat};
+use crate::Pt;
+use crate::Error;
+use lemon_pdf_derive::PdfFormat;
+
+/// Computes the cryptographic signature of a document.
+pub trait Signer {
+    /// An upper bound for the length in bytes of the signatures returned by `sign`. This much
+    /// space is reserved in the document.
+    fn max_signature_length(&self) -> usize;
+
+    /// Compute a DER-encoded detached CMS (PKCS#7) signature of `data`, which consists of
+    /// all bytes of the file except the `/Contents` entry of the signature dictionary. An error
+    /// is returned from `DocumentContext::finish`.
+    fn sign(&mut self, data: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// The settings for signing a document.
+pub struct Signature<'a> {
+    /// The (partial) name of the signature field.
+    pub field_name: String,
+    pub signer: Box<dyn Signer + Send + 'a>,
+    /// The name of the person or authority signing the document.
+    pub name: Option<String>,
+    pub reason: Option<String>,
+    pub location: Option<String>,
+    /// Information on how to contact the signer.
+    pub contact_info: Option<String>,
+}
+
+impl<'a> Signature<'a> {
+    pub fn new(field_name: impl Into<String>, signer: impl Signer + Send + 'a) -> Self {
+        Signature {
+            field_name: field_name.into(),
+            signer: Box::new(signer),
+            name: None,
+            reason: None,
+            location: None,
+            contact_info: None,
+        }
+    }
+}
+
+impl<'a> std::fmt::Debug for Signature<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Signature")
+            .field("field_name", &self.field_name)
+            .field("name", &self.name)
+            .field("reason", &self.reason)
+            .field("location", &self.location)
+            .field("contact_info", &self.contact_info)
+            .finish()
+    }
+}
+
+/// The width of the `/ByteRange` array, which is large enough for any file smaller than
+/// 10 GB.
+const BYTE_RANGE_WIDTH: usize = 42;
+
+/// Reserves space for the `/ByteRange` array.
+#[derive(Debug)]
+struct ByteRangePlaceholder;
+
+impl PdfFormat for ByteRangePlaceholder {
+    fn write(&self, f: &mut Formatter) -> Result<()> {
+        write!(f, "{}", byte_range_array(&[0, 0, 0, 0]))
+    }
+}
+
+fn byte_range_array(byte_range: &[usize; 4]) -> String {
+    let array = format!(
+        "[ {} {} {} {}",
+        byte_range[0], byte_range[1], byte_range[2], byte_range[3]
+    );
+    format!("{:width$}]", array, width = BYTE_RANGE_WIDTH - 1)
+}
+
+/// The signature dictionary that is the value of the signature field.
+#[derive(Debug, PdfFormat)]
+#[rename("Sig")]
+pub(crate) struct SignatureDictionary {
+    filter: &'static str,
+    sub_filter: &'static str,
+    byte_range: ByteRangePlaceholder,
+    /// Zeros that are replaced by the signature.
+    contents: HexString,
+    #[skip_if("Option::is_none")]
+    name: Option<Vec<u8>>,
+    #[skip_if("Option::is_none")]
+    reason: Option<Vec<u8>>,
+    #[skip_if("Option::is_none")]
+    location: Option<Vec<u8>>,
+    #[skip_if("Option::is_none")]
+    contact_info: Option<Vec<u8>>,
+}
+
+impl SignatureDictionary {
+    pub fn new(signature: &Signature) -> Self {
+        let text = |text: &Option<String>| text.as_deref().map(text_string);
+        SignatureDictionary {
+            filter: "Adobe.PPKLite",
+            sub_filter: "adbe.pkcs7.detached",
+            byte_range: ByteRangePlaceholder,
+            contents: HexString(vec![0; signature.signer.max_signature_length()]),
+            name: text(&signature.name),
+            reason: text(&signature.reason),
+            location: text(&signature.location),
+            contact_info: text(&signature.contact_info),
+        }
+    }
+}
+
+/// An invisible signature field, which is at the same time its widget annotation.
+///
+/// The field should be added to the annotations of a page.
+#[derive(Debug, PdfFormat)]
+#[rename("Annot")]
+pub struct SignatureField {
+    subtype: &'static str,
+    #[rename("FT")]
+    field_type: &'static str,
+    #[rename("T")]
+    name: Vec<u8>,
+    #[rename("V")]
+    value: IndirectReference<SignatureDictionary>,
+    rect: [Pt; 4],
+    /// The annotation flags `Print` and `Locked`.
+    #[rename("F")]
+    flags: u32,
+}
+
+impl SignatureField {
+    pub(crate) fn new(name: &str, value: IndirectReference<SignatureDictionary>) -> Self {
+        SignatureField {
+            subtype: "Widget",
+            field_type: "Sig",
+            name: text_string(name),
+            value,
+            rect: [Pt(0.0); 4],
+            flags: 4 | 128,
+        }
+    }
+}
+
+/// The interactive form dictionary of the document catalog.
+#[derive(Debug, PdfFormat)]
+#[omit_type(true)]
+pub(crate) struct AcroForm {
+    fields: Vec<IndirectReference<SignatureField>>,
+    /// `SignaturesExist` and `AppendOnly`.
+    sig_flags: u32,
+}
+
+impl AcroForm {
+    pub fn with_signature(field: IndirectReference<SignatureField>) -> Self {
+        AcroForm {
+            fields: vec![field],
+            sig_flags: 3,
+        }
+    }
+}
+
+/// The location of the placeholders of a written signature dictionary.
+pub(crate) struct SignaturePlaceholder<'a> {
+    pub signer: Box<dyn Signer + Send + 'a>,
+    /// The offset of the `/ByteRange` array in the file.
+    pub byte_range: usize,
+    /// The offset and length of the `/Contents` hex string in the file, including the angle
+    /// brackets.
+    pub contents: (usize, usize),
+}
+
+impl<'a> SignaturePlaceholder<'a> {
+    /// Find the placeholders in the serialized signature dictionary `bytes` that starts at
+    /// `offset` in the file.
+    pub fn find(
+        signer: Box<dyn Signer + Send + 'a>,
+        bytes: &[u8],
+        offset: u64,
+    ) -> crate::Result<Self> {
+        let find = |needle: &[u8]| {
+            bytes
+                .windows(needle.len())
+                .position(|window| window == needle)
+                .map(|position| offset as usize + position + needle.len())
+                .ok_or_else(|| {
+                    Error::InvalidObjectGraph("signature placeholder not found".to_string())
+                })
+        };
+        let byte_range = find(b"/ByteRange ")?;
+        let contents = find(b"/Contents ")?;
+        let length = 2 * signer.max_signature_length() + 2;
+        Ok(SignaturePlaceholder {
+            signer,
+            byte_range,
+            contents: (contents, length),
+        })
+    }
+
+    /// Fill in the byte ranges and the signature of the complete file `data`.
+    pub fn apply(mut self, data: &mut [u8]) -> crate::Result<()> {
+        let (contents_start, contents_length) = self.contents;
+        let contents_end = contents_start + contents_length;
+        let byte_range =
+            byte_range_array(&[0, contents_start, contents_end, data.len() - contents_end]);
+        if byte_range.len() != BYTE_RANGE_WIDTH {
+            return Err(Error::Encoding(
+                "file too large for the reserved /ByteRange".to_string(),
+            ));
+        }
+        data[self.byte_range..self.byte_range + BYTE_RANGE_WIDTH]
+            .copy_from_slice(byte_range.as_bytes());
+
+        let mut signed_data = data[..contents_start].to_vec();
+        signed_data.extend_from_slice(&data[contents_end..]);
+        let signature = self.signer.sign(&signed_data)?;
+        if 2 * signature.len() + 2 > contents_length {
+            return Err(Error::InvalidInput(
+                "signature is larger than the maximum signature length".to_string(),
+            ));
+        }
+        let hex: String = signature
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        data[contents_start + 1..contents_start + 1 + hex.len()].copy_from_slice(hex.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encryption::md5;
+    use crate::pagetree::Page;
+    use crate::{DocumentContext, Version};
+
+    /// Uses the MD5 hash of the data as the "signature".
+    struct HashSigner;
+
+    impl Signer for HashSigner {
+        fn max_signature_length(&self) -> usize {
+            32
+        }
+
+        fn sign(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+            Ok(md5(&[data]).to_vec())
+        }
+    }
+
+    #[test]
+    fn test_sign() {
+        let mut output = Vec::new();
+        let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_7).unwrap();
+        let mut signature = Signature::new("Signature1", HashSigner);
+        signature.reason = Some("Approval".to_string());
+        let field = context.sign(signature).unwrap();
+        let mut page = Page::new();
+        page.annots.push(field.convert());
+        context.add_page(page).unwrap();
+        context.finish().unwrap();
+
+        let position = |needle: &[u8]| {
+            output
+                .windows(needle.len())
+                .position(|window| window == needle)
+                .unwrap()
+        };
+        let byte_range_start = position(b"/ByteRange [") + 11;
+        let byte_range: Vec<usize> = std::str::from_utf8(
+            &output[byte_range_start + 1..byte_range_start + BYTE_RANGE_WIDTH - 1],
+        )
+        .unwrap()
+        .split_whitespace()
+        .map(|number| number.parse().unwrap())
+        .collect();
+        assert_eq!(byte_range[0], 0);
+        assert_eq!(byte_range[2] + byte_range[3], output.len());
+        assert_eq!(output[byte_range[1]], b'<');
+        assert_eq!(output[byte_range[2] - 1], b'>');
+
+        let mut signed_data = output[..byte_range[1]].to_vec();
+        signed_data.extend_from_slice(&output[byte_range[2]..]);
+        let hex: String = md5(&[&signed_data])
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        assert!(output[byte_range[1] + 1..].starts_with(hex.as_bytes()));
+        position(b"/AcroForm << /Fields [");
+        position(b"/Annots [");
+    }
+}
@@ -12,6 +12,7 @@
 //    See the License for the specific language governing permissions and
 //    limitations under the License.
 
+use std::convert::TryFrom;
 use std::io::{Cursor, Result, Write};
 use std::ops::Deref;
 
@@ -58,15 +59,14 @@ impl StreamEncoder {
         StreamEncoder { enc_type }
     }
 
-    pub fn into_stream(self) -> Stream {
+    pub fn into_stream(self) -> crate::Result<Stream> {
         let (bytes, filter) = match self.enc_type {
             StreamEncoderType::Identity(enc) => (enc.into_inner(), None),
-            StreamEncoderType::Deflate(enc) => (
-                enc.finish().unwrap().into_inner(),
-                Some(StreamFilter::Deflate),
-            ),
+            StreamEncoderType::Deflate(enc) => {
+                (enc.finish()?.into_inner(), Some(StreamFilter::Deflate))
+            }
         };
-        Stream::with_bytes(bytes, filter)
+        Ok(Stream::with_bytes(bytes, filter))
     }
 }
 
@@ -86,9 +86,11 @@ impl Write for StreamEncoder {
     }
 }
 
-impl From<StreamEncoder> for Value {
-    fn from(stream_encoder: StreamEncoder) -> Self {
-        Value::Stream(stream_encoder.into_stream())
+impl TryFrom<StreamEncoder> for Value {
+    type Error = crate::Error;
+
+    fn try_from(stream_encoder: StreamEncoder) -> crate::Result<Self> {
+        Ok(Value::Stream(stream_encoder.into_stream()?))
     }
 }
 
@@ -114,23 +116,45 @@ impl Stream {
     pub fn add_key_value(&mut self, key: String, value: Box<dyn PdfFormat>) {
         self.additional_keys.insert(key, value);
     }
+
+    /// Whether this is a metadata stream, i.e. has the entry `/Type /Metadata`.
+    fn is_metadata(&self) -> bool {
+        let mut type_name = Vec::new();
+        match self.additional_keys.get("Type") {
+            Some(value) => {
+                value.write(&mut Formatter::new(&mut type_name)).is_ok()
+                    && type_name == b"/Metadata"
+            }
+            None => false,
+        }
+    }
 }
 
 impl PdfFormat for Stream {
     fn write(&self, f: &mut Formatter) -> Result<()> {
+        let encrypted;
+        let bytes = match f.cipher {
+            Some(cipher) if cipher.encrypts_metadata() || !self.is_metadata() => {
+                encrypted = cipher.encrypt(&self.bytes);
+                &encrypted
+            }
+            _ => &self.bytes,
+        };
+
         let mut dict_formatter = f.format_dictionary();
         match self.filter {
             None => {}
             Some(filter) => dict_formatter = dict_formatter.key_value(&"Filter", &filter),
         }
-        let mut dict_formatter = dict_formatter.key_value(&"Length", &self.bytes.len());
+        let mut dict_formatter = dict_formatter.key_value(&"Length", &bytes.len());
         for (key, value) in self.additional_keys.iter() {
             dict_formatter = dict_formatter.key_value(key, value.deref());
         }
         dict_formatter.finish()?;
 
+        f.wrote_stream = true;
         write!(f, "\nstream\n")?;
-        f.write_all(&self.bytes)?;
+        f.write_all(bytes)?;
         write!(f, "\nendstream")
     }
 }
@@ -1,15 +1,38 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
 use crate as lemon_pdf;
 
-use crate::{Page, object::{Object, IndirectReference}};
+use crate::number_tree::{NumberTree, NumberTreeNode};
+use crate::object::{text_string, IndirectReference, Object, RawIndirectReference};
+use crate::pdfua::StructureChecker;
+use crate::{DocumentContext, Error, Page, Result};
 
 use lemon_pdf_derive::PdfFormat;
 
 #[derive(Debug, Clone, PdfFormat)]
 pub struct StructTreeRoot {
-    pub k: Vec<Object<StructElem>>
+    pub k: Vec<Object<StructElem>>,
+    /// Maps the `/StructParents` entries of pages and the `/StructParent` entries of
+    /// annotations to the structure elements.
+    #[skip_if("Option::is_none")]
+    pub parent_tree: Option<IndirectReference<NumberTreeNode<ParentTreeEntry>>>,
+    /// The key for the next entry of the parent tree.
+    #[skip_if("Option::is_none")]
+    pub parent_tree_next_key: Option<i64>,
 }
 
+/// An entry of the parent tree of a `StructTreeRoot`.
 #[derive(Debug, Clone, PdfFormat)]
+pub enum ParentTreeEntry {
+    /// The structure elements of the marked-content sequences of a page, indexed by their
+    /// marked-content identifiers.
+    Page(Vec<Option<IndirectReference<StructElem>>>),
+    /// The structure element of an annotation or XObject.
+    Object(IndirectReference<StructElem>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PdfFormat)]
 pub enum StandardStructureType {
     Document,
     Part,
@@ -42,12 +65,39 @@ pub enum StandardStructureType {
     THead,
     TBody,
     TFoot,
+    Span,
+    Quote,
+    Note,
+    Reference,
+    BibEntry,
+    Code,
+    Link,
+    Annot,
+    Figure,
+    Formula,
+    Form,
+}
+
+impl StandardStructureType {
+    /// The level of a numbered heading `H1` to `H6`.
+    pub fn heading_level(self) -> Option<u8> {
+        use StandardStructureType::*;
+        match self {
+            H1 => Some(1),
+            H2 => Some(2),
+            H3 => Some(3),
+            H4 => Some(4),
+            H5 => Some(5),
+            H6 => Some(6),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PdfFormat)]
 pub enum StructureParent {
     Root(IndirectReference<StructTreeRoot>),
-    Elem(IndirectReference<StructElem>)
+    Elem(IndirectReference<StructElem>),
 }
 
 #[derive(Debug, Clone, PdfFormat)]
@@ -55,9 +105,333 @@ pub struct StructElem {
     pub s: StandardStructureType,
     pub p: StructureParent,
     #[rename("ID")]
-    #[skip_if(Vec::is_empty)]
+    #[skip_if("Vec::is_empty")]
     pub id: Vec<u8>,
+    /// The page of the marked-content sequences that are given by their identifiers only.
+    #[skip_if("Option::is_none")]
     pub pg: Option<IndirectReference<Page>>,
     /// the children
-    pub k: Vec<Object<StructElem>>
-}
\ No newline at end of file
+    pub k: Vec<StructKid>,
+    #[skip_if("Option::is_none")]
+    pub alt: Option<Vec<u8>>,
+    #[skip_if("Option::is_none")]
+    pub actual_text: Option<Vec<u8>>,
+    #[skip_if("Option::is_none")]
+    pub lang: Option<Vec<u8>>,
+}
+
+/// A child of a `StructElem`.
+#[derive(Debug, Clone, PdfFormat)]
+pub enum StructKid {
+    Element(IndirectReference<StructElem>),
+    /// A marked-content sequence on the page `/Pg` of the element.
+    MarkedContent(i64),
+    MarkedContentReference(MarkedContentReference),
+}
+
+/// A marked-content sequence on a different page than the `/Pg` of its element.
+#[derive(Debug, Clone, PdfFormat)]
+#[rename("MCR")]
+pub struct MarkedContentReference {
+    pub pg: IndirectReference<Page>,
+    #[rename("MCID")]
+    pub mcid: i64,
+}
+
+/// The mark information dictionary, which declares a document as tagged PDF.
+#[derive(Debug, PdfFormat)]
+#[omit_type(true)]
+pub(crate) struct MarkInfo {
+    pub marked: bool,
+}
+
+/// An element of the logical structure of the document, see
+/// `DocumentContext::structure_tree`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureElement {
+    pub structure_type: StandardStructureType,
+    /// A description for users who cannot perceive the element, e.g. of a figure.
+    pub alt: Option<String>,
+    /// The text that the content of the element represents, e.g. if it is drawn as paths.
+    pub actual_text: Option<String>,
+    /// The natural language of the element if it differs from the language of the document.
+    pub language: Option<String>,
+    pub children: Vec<StructureChild>,
+}
+
+/// A child of a `StructureElement`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StructureChild {
+    Element(StructureElement),
+    /// A marked-content sequence started with `PageContext::begin_tagged_content`.
+    MarkedContent {
+        page: IndirectReference<Page>,
+        mcid: i64,
+    },
+}
+
+impl StructureElement {
+    pub fn new(structure_type: StandardStructureType) -> Self {
+        StructureElement {
+            structure_type,
+            alt: None,
+            actual_text: None,
+            language: None,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn add_child(&mut self, element: StructureElement) {
+        self.children.push(StructureChild::Element(element))
+    }
+
+    /// Add the marked-content sequence with the identifier `mcid` on `page`.
+    pub fn add_marked_content(&mut self, page: IndirectReference<Page>, mcid: i64) {
+        self.children
+            .push(StructureChild::MarkedContent { page, mcid })
+    }
+
+    /// Whether a descendant of the element has the type `structure_type`.
+    pub(crate) fn contains(&self, structure_type: StandardStructureType) -> bool {
+        self.children.iter().any(|child| match child {
+            StructureChild::Element(element) => {
+                element.structure_type == structure_type || element.contains(structure_type)
+            }
+            StructureChild::MarkedContent { .. } => false,
+        })
+    }
+}
+
+/// The marked-content sequences of the pages, see `PageContext::begin_tagged_content`.
+#[derive(Debug, Default)]
+pub(crate) struct MarkedContentPages {
+    /// The number of marked-content identifiers of the pages by their `/StructParents` key.
+    mcid_counts: Vec<i64>,
+    /// The `/StructParents` keys of the pages that have been written.
+    keys: BTreeMap<RawIndirectReference, i64>,
+}
+
+impl MarkedContentPages {
+    /// The number of marked-content identifiers used by the page with the `/StructParents` key
+    /// `key` so far.
+    pub fn mcid_count(&self, key: Option<i64>) -> i64 {
+        let index = key.and_then(|key| usize::try_from(key).ok());
+        index
+            .and_then(|index| self.mcid_counts.get(index))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Record that a page uses `count` marked-content identifiers and return its
+    /// `/StructParents` key. `key` is the key the page got for its previous content, if any.
+    pub fn set_mcid_count(&mut self, key: Option<i64>, count: i64) -> i64 {
+        let index = key.and_then(|key| usize::try_from(key).ok());
+        if let Some(previous) = index.and_then(|index| self.mcid_counts.get_mut(index)) {
+            *previous = count;
+            return key.unwrap();
+        }
+        self.mcid_counts.push(count);
+        self.mcid_counts.len() as i64 - 1
+    }
+
+    /// Record the reference of a page with the `/StructParents` key `key`. Keys that were not
+    /// returned by `set_mcid_count` are ignored.
+    pub fn add_page(&mut self, page: RawIndirectReference, key: i64) {
+        let index = usize::try_from(key).ok();
+        if index.is_some_and(|index| index < self.mcid_counts.len()) {
+            self.keys.insert(page, key);
+        }
+    }
+}
+
+/// Writes the structure elements and collects the entries of the parent tree.
+struct StructureWriter<'a> {
+    pages: &'a MarkedContentPages,
+    /// The structure elements of the marked-content sequences by the key of their page.
+    parents: BTreeMap<i64, Vec<Option<IndirectReference<StructElem>>>>,
+    /// Checks the elements for PDF/UA conformance if set.
+    checker: Option<StructureChecker>,
+}
+
+impl<'a> StructureWriter<'a> {
+    fn write_element(
+        &mut self,
+        context: &mut DocumentContext,
+        element: StructureElement,
+        parent: StructureParent,
+    ) -> Result<IndirectReference<StructElem>> {
+        let reference = context.reserve_reference()?;
+        if let Some(checker) = &mut self.checker {
+            checker.check(reference.raw(), &element)?;
+        }
+        let pg = element.children.iter().find_map(|child| match child {
+            StructureChild::MarkedContent { page, .. } => Some(*page),
+            StructureChild::Element(_) => None,
+        });
+        let mut kids = Vec::new();
+        for child in element.children {
+            match child {
+                StructureChild::Element(child) => {
+                    let parent = StructureParent::Elem(reference);
+                    kids.push(StructKid::Element(
+                        self.write_element(context, child, parent)?,
+                    ));
+                }
+                StructureChild::MarkedContent { page, mcid } => {
+                    self.set_parent(page, mcid, reference)?;
+                    kids.push(if Some(page) == pg {
+                        StructKid::MarkedContent(mcid)
+                    } else {
+                        StructKid::MarkedContentReference(MarkedContentReference { pg: page, mcid })
+                    });
+                }
+            }
+        }
+        let dictionary = StructElem {
+            s: element.structure_type,
+            p: parent,
+            id: Vec::new(),
+            pg,
+            k: kids,
+            alt: element.alt.as_deref().map(text_string),
+            actual_text: element.actual_text.as_deref().map(text_string),
+            lang: element.language.as_deref().map(text_string),
+        };
+        context.write_indirect_object(dictionary, reference.raw())?;
+        Ok(reference)
+    }
+
+    fn set_parent(
+        &mut self,
+        page: IndirectReference<Page>,
+        mcid: i64,
+        element: IndirectReference<StructElem>,
+    ) -> Result<()> {
+        let RawIndirectReference(number, generation) = page.raw();
+        let parents = self
+            .pages
+            .keys
+            .get(&page.raw())
+            .and_then(|key| self.parents.get_mut(key));
+        let parent = parents
+            .zip(usize::try_from(mcid).ok())
+            .and_then(|(parents, mcid)| parents.get_mut(mcid))
+            .ok_or_else(|| {
+                Error::InvalidInput(format!(
+                    "the page {} {} has no marked content {}",
+                    number, generation, mcid
+                ))
+            })?;
+        if parent.is_some() {
+            return Err(Error::InvalidInput(format!(
+                "the marked content {} of the page {} {} belongs to several structure elements",
+                mcid, number, generation
+            )));
+        }
+        *parent = Some(element);
+        Ok(())
+    }
+}
+
+/// Write the structure tree with the top-level elements `elements` and the parent tree for
+/// the marked-content sequences in `pages`. Returns `None` if there are no elements.
+///
+/// With `checker`, the elements are checked for PDF/UA conformance and every marked-content
+/// sequence has to belong to an element.
+pub(crate) fn write_structure_tree(
+    context: &mut DocumentContext,
+    elements: Vec<StructureElement>,
+    pages: &MarkedContentPages,
+    checker: Option<StructureChecker>,
+) -> Result<Option<IndirectReference<StructTreeRoot>>> {
+    if elements.is_empty() {
+        return Ok(None);
+    }
+    let check_marked_content = checker.is_some();
+    let mut writer = StructureWriter {
+        pages,
+        parents: pages
+            .keys
+            .values()
+            .map(|&key| (key, vec![None; pages.mcid_counts[key as usize] as usize]))
+            .collect(),
+        checker,
+    };
+    let root = context.reserve_reference()?;
+    let mut k = Vec::new();
+    for element in elements {
+        let parent = StructureParent::Root(root);
+        k.push(writer.write_element(context, element, parent)?.into());
+    }
+
+    if check_marked_content {
+        for (&page, key) in &pages.keys {
+            if let Some(mcid) = writer.parents[key].iter().position(Option::is_none) {
+                return Err(Error::Conformance {
+                    object: Some(page),
+                    message: format!(
+                        "the marked content {} of the page is not part of the structure tree",
+                        mcid
+                    ),
+                });
+            }
+        }
+    }
+    let mut parent_tree = NumberTree::new();
+    for (key, parents) in writer.parents {
+        parent_tree.insert(key, ParentTreeEntry::Page(parents));
+    }
+    let parent_tree = if parent_tree.is_empty() {
+        None
+    } else {
+        Some(parent_tree.write_to_context(context)?)
+    };
+    let next_key = pages.mcid_counts.len() as i64;
+    let root_dictionary = StructTreeRoot {
+        k,
+        parent_tree,
+        parent_tree_next_key: Some(next_key).filter(|&key| key > 0),
+    };
+    context.write_indirect_object(root_dictionary, root.raw())?;
+    Ok(Some(root))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Version;
+
+    #[test]
+    fn test_untagged_marked_content() {
+        let page = RawIndirectReference(3, 0);
+        let mut pages = MarkedContentPages::default();
+        let key = pages.set_mcid_count(None, 2);
+        pages.add_page(page, key);
+        let structure = |mcids: &[i64]| {
+            let mut heading = StructureElement::new(StandardStructureType::H1);
+            for &mcid in mcids {
+                heading.add_marked_content(IndirectReference::new(page.0, page.1), mcid);
+            }
+            vec![heading]
+        };
+
+        let mut context = DocumentContext::with_writer(Vec::new(), Version::Pdf1_7).unwrap();
+        let checker = Some(StructureChecker::default());
+        match write_structure_tree(&mut context, structure(&[0]), &pages, checker) {
+            Err(Error::Conformance { object, message }) => {
+                assert_eq!(object, Some(page));
+                assert_eq!(
+                    message,
+                    "the marked content 1 of the page is not part of the structure tree"
+                );
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        let checker = Some(StructureChecker::default());
+        let result = write_structure_tree(&mut context, structure(&[0, 1]), &pages, checker);
+        assert!(result.unwrap().is_some());
+        // without a checker, marked content may be left out
+        let result = write_structure_tree(&mut context, structure(&[0]), &pages, None);
+        assert!(result.unwrap().is_some());
+    }
+}
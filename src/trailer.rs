@@ -12,60 +12,129 @@
 //    See the License for the specific language governing permissions and
 //    limitations under the License.
 
-use std::io::{Result, Write};
+use std::io::Write;
+use std::ops::Deref;
 use std::time::SystemTime;
 
-use crate::document::{DocumentContext, DocumentCatalog, DocumentInfo};
-use crate::object::{Formatter, IndirectReference, PdfFormat};
+use crate::document::{DocumentCatalog, DocumentContext};
+use crate::info::DocumentInfo;
+use crate::object::{Formatter, HexString, IndirectReference, PdfFormat, RawIndirectReference};
+use crate::reader::RawObject;
+use crate::stream::{StreamEncoder, StreamFilter};
+use crate::Result;
 
 use crypto::digest::Digest;
 use crypto::md5::Md5;
 
-#[derive(Debug, Clone)]
-struct HexString(String);
-
-impl PdfFormat for HexString {
-    fn write(&self, output: &mut Formatter) -> Result<()> {
-        write!(output, "<{}>", self.0)
-    }
-}
-
-fn get_digest(file_len: u64) -> HexString {
+pub(crate) fn get_digest(file_len: u64) -> [u8; 16] {
     let mut md5 = Md5::new();
     let time = SystemTime::now();
     let time = format!("{:?}", time);
     md5.input_str(&time);
     md5.input_str(&format!("{}", file_len));
-    HexString(md5.result_str())
+    let mut digest = [0; 16];
+    md5.result(&mut digest);
+    digest
+}
+
+/// The `/ID` array of the trailer.
+#[derive(Debug)]
+struct FileId {
+    original: Option<RawObject>,
+    current: HexString,
+}
+
+impl PdfFormat for FileId {
+    fn write(&self, f: &mut Formatter) -> std::io::Result<()> {
+        let original: &dyn PdfFormat = match &self.original {
+            Some(original) => original,
+            None => &self.current,
+        };
+        f.format_array().value(original).value(&self.current).finish()
+    }
 }
 
 pub struct Trailer {
-    pub crossref_offset: u32,
+    pub crossref_offset: u64,
     pub(crate) document_catalog: IndirectReference<DocumentCatalog>,
     pub(crate) document_info: Option<IndirectReference<DocumentInfo>>,
+    /// The offset of the previous cross-reference section in an incremental update.
+    pub(crate) prev: Option<u64>,
+    /// The permanent part of the file identifier of the original document in an incremental
+    /// update.
+    pub(crate) original_id: Option<RawObject>,
+    /// The encryption dictionary if the document is encrypted.
+    pub(crate) encrypt: Option<RawIndirectReference>,
+    /// The file identifier.
+    pub(crate) file_id: [u8; 16],
 }
 
 impl Trailer {
+    /// The entries of the trailer dictionary.
+    fn entries(&self, size: i64) -> Vec<(&'static str, Box<dyn PdfFormat>)> {
+        let id = FileId {
+            original: self.original_id.clone(),
+            current: HexString(self.file_id.to_vec()),
+        };
+        let mut entries: Vec<(&'static str, Box<dyn PdfFormat>)> = vec![
+            ("Size", Box::new(size)),
+            ("Root", Box::new(self.document_catalog)),
+            ("ID", Box::new(id)),
+        ];
+        if let Some(document_info) = self.document_info {
+            entries.push(("Info", Box::new(document_info)));
+        }
+        if let Some(encrypt) = self.encrypt {
+            entries.push(("Encrypt", Box::new(encrypt)));
+        }
+        if let Some(prev) = self.prev {
+            entries.push(("Prev", Box::new(prev)));
+        }
+        entries
+    }
+
+    /// Write a classic `trailer` dictionary. The cross-reference table has to be written
+    /// directly before.
     pub(crate) fn write(&mut self, context: &mut DocumentContext) -> Result<()> {
         writeln!(context.output, "trailer")?;
-        let digest = get_digest(context.current_offset());
+        let entries = self.entries(i64::from(context.crossref.len()) + 1);
 
-        let mut formatter = Formatter {
-            writer: &mut context.output,
-        };
-        let mut formatter = formatter
-            .format_dictionary()
-            .key_value(&"Size", &(context.crossref.len() + 1))
-            .key_value(&"Root", &self.document_catalog)
-            .key_value(&"ID", &[digest.clone(), digest]);
-
-        if let Some(document_info) = self.document_info {
-            formatter = formatter.key_value(&"Info", &document_info);
+        let mut formatter = Formatter::new(&mut context.output);
+        let mut formatter = formatter.format_dictionary();
+        for (key, value) in &entries {
+            formatter = formatter.key_value(key, value.deref());
         }
         formatter.finish()?;
+        writeln!(context.output)?;
 
-        write!(context.output, "\nstartxref\n{}\n", self.crossref_offset)?;
+        self.write_startxref(context)
+    }
+
+    /// Write the cross-reference information as a cross-reference stream (PDF 1.5) that also
+    /// carries the trailer entries.
+    pub(crate) fn write_xref_stream(&mut self, context: &mut DocumentContext) -> Result<()> {
+        // the cross-reference stream contains an entry for itself
+        let number = context
+            .crossref
+            .add_entry(self.crossref_offset, 0)?;
+        let (widths, index, data) = context.crossref.stream_data();
+
+        let mut encoder = StreamEncoder::new(Some(StreamFilter::Deflate));
+        encoder.write_all(&data)?;
+        let mut stream = encoder.into_stream()?;
+        stream.add_key_value("Type".to_owned(), Box::new("XRef"));
+        stream.add_key_value("W".to_owned(), Box::new(widths));
+        stream.add_key_value("Index".to_owned(), Box::new(index));
+        for (key, value) in self.entries(i64::from(context.crossref.len()) + 1) {
+            stream.add_key_value(key.to_owned(), value);
+        }
+
+        context.write_unencrypted_object(stream, RawIndirectReference(i64::from(number), 0))?;
+        self.write_startxref(context)
+    }
 
+    fn write_startxref(&mut self, context: &mut DocumentContext) -> Result<()> {
+        write!(context.output, "startxref\n{}\n", self.crossref_offset)?;
         writeln!(context.output, "%%EOF")?;
         Ok(())
     }
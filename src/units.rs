@@ -0,0 +1,93 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! Lengths in units other than points, which convert into and from `Pt`.
+
+use crate::Pt;
+
+/// The number of points per inch.
+const POINTS_PER_INCH: f64 = 72.0;
+
+/// The number of millimetres per inch.
+const MM_PER_INCH: f64 = 25.4;
+
+/// A length in millimetres.
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Mm(pub f64);
+
+/// A length in centimetres.
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Cm(pub f64);
+
+/// A length in inches.
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Inch(pub f64);
+
+impl From<Mm> for Pt {
+    fn from(length: Mm) -> Pt {
+        Pt(length.0 * POINTS_PER_INCH / MM_PER_INCH)
+    }
+}
+
+impl From<Cm> for Pt {
+    fn from(length: Cm) -> Pt {
+        Pt::from(Mm(length.0 * 10.0))
+    }
+}
+
+impl From<Inch> for Pt {
+    fn from(length: Inch) -> Pt {
+        Pt(length.0 * POINTS_PER_INCH)
+    }
+}
+
+impl From<Pt> for Mm {
+    fn from(length: Pt) -> Mm {
+        Mm(length.0 * MM_PER_INCH / POINTS_PER_INCH)
+    }
+}
+
+impl From<Pt> for Cm {
+    fn from(length: Pt) -> Cm {
+        Cm(Mm::from(length).0 / 10.0)
+    }
+}
+
+impl From<Pt> for Inch {
+    fn from(length: Pt) -> Inch {
+        Inch(length.0 / POINTS_PER_INCH)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_units() {
+        assert_eq!(Pt::from(Inch(1.5)), Pt(108.0));
+        assert!((Pt::from(Mm(25.4)) - Pt(72.0)).0.abs() < 1e-9);
+        assert!((Pt::from(Cm(2.54)) - Pt(72.0)).0.abs() < 1e-9);
+        assert!((Mm::from(Pt(72.0)).0 - 25.4).abs() < 1e-9);
+        assert_eq!(Inch::from(Pt(36.0)), Inch(0.5));
+
+        let mut length = Pt(10.0) + Pt(5.0) * 2.0 - Pt(4.0) / 2.0;
+        assert_eq!(length, Pt(18.0));
+        length -= Pt(3.0);
+        length += -Pt(1.0);
+        assert_eq!(length, Pt(14.0));
+        assert_eq!(Pt(7.0) / Pt(14.0), 0.5);
+        assert_eq!(vec![Pt(1.0), Pt(2.0)].into_iter().sum::<Pt>(), Pt(3.0));
+    }
+}
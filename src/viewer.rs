@@ -0,0 +1,257 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! How the document is presented when it is opened.
+
+use crate as lemon_pdf;
+use lemon_pdf_derive::PdfFormat;
+
+use crate::destination::Destination;
+use crate::Version;
+
+/// What is shown next to the document when it is opened.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PdfFormat)]
+pub enum PageMode {
+    /// Neither the outline nor thumbnail images.
+    UseNone,
+    UseOutlines,
+    /// Thumbnail images of the pages.
+    UseThumbs,
+    /// Full-screen mode, without menu bar, window controls or any other window.
+    FullScreen,
+    /// The optional content group panel.
+    UseOC,
+    /// The attachments panel.
+    UseAttachments,
+}
+
+impl PageMode {
+    fn required_version(self) -> Option<(Version, &'static str)> {
+        match self {
+            PageMode::UseOC => Some((Version::Pdf1_5, "the page mode UseOC")),
+            PageMode::UseAttachments => Some((Version::Pdf1_6, "the page mode UseAttachments")),
+            _ => None,
+        }
+    }
+}
+
+/// How pages are arranged when the document is opened.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PdfFormat)]
+pub enum PageLayout {
+    /// One page at a time.
+    SinglePage,
+    /// The pages in one column.
+    OneColumn,
+    /// The pages in two columns, odd-numbered pages on the left.
+    TwoColumnLeft,
+    /// The pages in two columns, odd-numbered pages on the right.
+    TwoColumnRight,
+    /// Two pages at a time, odd-numbered pages on the left.
+    TwoPageLeft,
+    /// Two pages at a time, odd-numbered pages on the right.
+    TwoPageRight,
+}
+
+impl PageLayout {
+    fn required_version(self) -> Option<(Version, &'static str)> {
+        match self {
+            PageLayout::TwoPageLeft | PageLayout::TwoPageRight => {
+                Some((Version::Pdf1_5, "two-page layouts"))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The predominant reading order of the text.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PdfFormat)]
+pub enum Direction {
+    /// Left to right.
+    L2R,
+    /// Right to left, including vertical writing systems.
+    R2L,
+}
+
+/// The page scaling that the print dialog selects initially.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PdfFormat)]
+pub enum PrintScaling {
+    /// No page scaling.
+    None,
+    /// The default of the viewer.
+    AppDefault,
+}
+
+/// The paper handling that the print dialog selects initially.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PdfFormat)]
+pub enum Duplex {
+    /// Print single-sided.
+    Simplex,
+    /// Print double-sided and flip on the short edge of the sheet.
+    DuplexFlipShortEdge,
+    /// Print double-sided and flip on the long edge of the sheet.
+    DuplexFlipLongEdge,
+}
+
+/// How the viewer presents the document. Entries that are `None` are left to the viewer.
+#[derive(Debug, Default, Clone, PartialEq, PdfFormat)]
+#[omit_type(true)]
+pub struct ViewerPreferences {
+    #[skip_if("Option::is_none")]
+    pub hide_toolbar: Option<bool>,
+    #[skip_if("Option::is_none")]
+    pub hide_menubar: Option<bool>,
+    /// Hide user interface elements like scroll bars and navigation controls.
+    #[rename("HideWindowUI")]
+    #[skip_if("Option::is_none")]
+    pub hide_window_ui: Option<bool>,
+    /// Resize the window to fit the first page.
+    #[skip_if("Option::is_none")]
+    pub fit_window: Option<bool>,
+    #[skip_if("Option::is_none")]
+    pub center_window: Option<bool>,
+    /// Show the title of the document information dictionary instead of the file name in
+    /// the title bar.
+    #[skip_if("Option::is_none")]
+    pub display_doc_title: Option<bool>,
+    /// The page mode after leaving full-screen mode, if the page mode is
+    /// `PageMode::FullScreen`.
+    #[skip_if("Option::is_none")]
+    pub non_full_screen_page_mode: Option<PageMode>,
+    #[skip_if("Option::is_none")]
+    pub direction: Option<Direction>,
+    #[skip_if("Option::is_none")]
+    pub print_scaling: Option<PrintScaling>,
+    #[skip_if("Option::is_none")]
+    pub duplex: Option<Duplex>,
+    /// The number of copies that the print dialog selects initially.
+    #[skip_if("Option::is_none")]
+    pub num_copies: Option<u32>,
+}
+
+impl ViewerPreferences {
+    fn required_versions(&self) -> Vec<(Version, &'static str)> {
+        let mut versions = Vec::new();
+        if self.display_doc_title.is_some() {
+            versions.push((Version::Pdf1_4, "the viewer preference DisplayDocTitle"));
+        }
+        if self.print_scaling.is_some() {
+            versions.push((Version::Pdf1_6, "the viewer preference PrintScaling"));
+        }
+        if self.duplex.is_some() {
+            versions.push((Version::Pdf1_7, "the viewer preference Duplex"));
+        }
+        if self.num_copies.is_some() {
+            versions.push((Version::Pdf1_7, "the viewer preference NumCopies"));
+        }
+        versions
+    }
+}
+
+/// A go-to action, which jumps to a destination.
+#[derive(Debug, Clone, PdfFormat)]
+#[rename("Action")]
+pub(crate) struct GoToAction {
+    #[rename("S")]
+    action_type: &'static str,
+    #[rename("D")]
+    destination: Destination,
+}
+
+impl GoToAction {
+    pub fn new(destination: Destination) -> Self {
+        GoToAction {
+            action_type: "GoTo",
+            destination,
+        }
+    }
+}
+
+/// The versions required by the presentation options of the document, with the feature that
+/// requires them.
+pub(crate) fn required_versions(
+    page_mode: Option<PageMode>,
+    page_layout: Option<PageLayout>,
+    viewer_preferences: Option<&ViewerPreferences>,
+) -> Vec<(Version, &'static str)> {
+    let mut versions: Vec<_> = page_mode
+        .and_then(PageMode::required_version)
+        .into_iter()
+        .chain(page_layout.and_then(PageLayout::required_version))
+        .collect();
+    if let Some(viewer_preferences) = viewer_preferences {
+        versions.extend(viewer_preferences.required_versions());
+    }
+    versions
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::ExistingDocument;
+    use crate::{DocumentContext, Page, VersionPolicy};
+
+    #[test]
+    fn test_viewer_options() {
+        let mut output = Vec::new();
+        let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_4).unwrap();
+        let page = context.add_page(Page::new()).unwrap();
+        context.page_mode = Some(PageMode::FullScreen);
+        context.page_layout = Some(PageLayout::TwoColumnLeft);
+        context.viewer_preferences = Some(ViewerPreferences {
+            hide_toolbar: Some(true),
+            non_full_screen_page_mode: Some(PageMode::UseOutlines),
+            print_scaling: Some(PrintScaling::None),
+            duplex: Some(Duplex::DuplexFlipLongEdge),
+            ..Default::default()
+        });
+        context.open_action = Some(Destination::Xyz {
+            page,
+            left: None,
+            top: None,
+            zoom: Some(2.0),
+        });
+        context.finish().unwrap();
+
+        let document = ExistingDocument::parse(&output).unwrap();
+        let catalog = document
+            .resolve_dictionary(document.trailer.get_reference("Root").unwrap())
+            .unwrap();
+        assert_eq!(catalog.get("PageMode").unwrap().0, b"/FullScreen");
+        assert_eq!(catalog.get("PageLayout").unwrap().0, b"/TwoColumnLeft");
+        // Duplex requires PDF 1.7
+        assert_eq!(catalog.get("Version").unwrap().0, b"/1.7");
+        let preferences = catalog
+            .get("ViewerPreferences")
+            .unwrap()
+            .as_dictionary()
+            .unwrap();
+        assert_eq!(preferences.get("HideToolbar").unwrap().0, b"true");
+        assert_eq!(preferences.get("PrintScaling").unwrap().0, b"/None");
+        assert_eq!(
+            preferences.get("NonFullScreenPageMode").unwrap().0,
+            b"/UseOutlines"
+        );
+        assert_eq!(preferences.get("HideMenubar"), None);
+        let action = catalog.get("OpenAction").unwrap().as_dictionary().unwrap();
+        assert_eq!(action.get("S").unwrap().0, b"/GoTo");
+        let destination = action.get("D").unwrap().as_array().unwrap();
+        assert_eq!(destination[0].as_reference(), Some(page.raw()));
+        assert_eq!(destination[4].0, b"2.00");
+
+        let mut context = DocumentContext::with_writer(Vec::new(), Version::Pdf1_4).unwrap();
+        context.version_policy = VersionPolicy::Strict;
+        context.page_layout = Some(PageLayout::TwoPageRight);
+        assert!(context.finish().is_err());
+    }
+}
@@ -0,0 +1,365 @@
+//    Copyright 2018 Manuel Reinhardt
+//
+//    Licensed under the Apache License, Version 2.0 (the "License");
+//    you may not use this file except in compliance with the License.
+//    You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+//    Unless required by applicable law or agreed to in writing, software
+//    distributed under the License is distributed on an "AS IS" BASIS,
+//    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//    See the License for the specific language governing permissions and
+//    limitations under the License.
+
+//! XMP metadata, which is generated from the document information dictionary.
+
+use std::collections::BTreeMap;
+
+use crate::info::{DocumentInfo, Trapped};
+use crate::stream::Stream;
+use crate::{Error, Result};
+
+const RDF_NAMESPACE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+
+const PDFA_EXTENSION_NAMESPACE: &str = "http://www.aiim.org/pdfa/ns/extension/";
+const PDFA_SCHEMA_NAMESPACE: &str = "http://www.aiim.org/pdfa/ns/schema#";
+const PDFA_PROPERTY_NAMESPACE: &str = "http://www.aiim.org/pdfa/ns/property#";
+const PDFUA_ID_NAMESPACE: &str = "http://www.aiim.org/pdfua/ns/id/";
+
+/// The namespaces of the properties written by `XmpMetadata`, by prefix.
+const STANDARD_NAMESPACES: [(&str, &str); 10] = [
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("pdf", "http://ns.adobe.com/pdf/1.3/"),
+    ("pdfaExtension", PDFA_EXTENSION_NAMESPACE),
+    ("pdfaProperty", PDFA_PROPERTY_NAMESPACE),
+    ("pdfaSchema", PDFA_SCHEMA_NAMESPACE),
+    ("pdfaid", "http://www.aiim.org/pdfa/ns/id/"),
+    ("pdfuaid", PDFUA_ID_NAMESPACE),
+    ("rdf", RDF_NAMESPACE),
+    ("x", "adobe:ns:meta/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+];
+
+/// A custom XMP property with a text value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XmpProperty {
+    /// The URI of the namespace of the property, e.g. `http://ns.example.com/dam/1.0/`.
+    pub namespace: String,
+    /// The prefix the namespace is declared with, e.g. `dam`.
+    pub prefix: String,
+    pub name: String,
+    pub value: String,
+}
+
+/// The part and conformance level of PDF/A that the document claims to conform to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdfAIdentification {
+    pub part: u8,
+    /// The conformance level, e.g. `B`.
+    pub conformance: String,
+}
+
+/// The XMP metadata of the document.
+///
+/// When the document is finished, the XMP packet is generated from the document information
+/// dictionary, so both always contain the same values. It is embedded uncompressed as the
+/// `/Metadata` stream of the document catalog.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct XmpMetadata {
+    /// Written to the `pdfaid` schema if set.
+    pub pdfa_identification: Option<PdfAIdentification>,
+    /// The part of PDF/UA that the document claims to conform to, written to the `pdfuaid`
+    /// schema if set.
+    pub pdfua_part: Option<u8>,
+    pub custom_properties: Vec<XmpProperty>,
+}
+
+impl XmpMetadata {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add the custom property `prefix:name` of the namespace `namespace`.
+    pub fn add_property(
+        &mut self,
+        namespace: impl Into<String>,
+        prefix: impl Into<String>,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) {
+        self.custom_properties.push(XmpProperty {
+            namespace: namespace.into(),
+            prefix: prefix.into(),
+            name: name.into(),
+            value: value.into(),
+        });
+    }
+
+    /// The XMP packet with the entries of `info`.
+    pub fn to_packet(&self, info: &DocumentInfo) -> Result<String> {
+        // the properties of each schema by prefix, already serialized
+        let mut schemas: BTreeMap<&str, (&str, String)> = BTreeMap::new();
+        let mut add = |prefix: &'static str, property: String| {
+            let namespace = STANDARD_NAMESPACES
+                .iter()
+                .find(|(standard, _)| *standard == prefix)
+                .map_or("", |(_, namespace)| namespace);
+            schemas
+                .entry(prefix)
+                .or_insert_with(|| (namespace, String::new()))
+                .1
+                .push_str(&property);
+        };
+
+        add("dc", simple_property("dc:format", "application/pdf"));
+        if let Some(title) = &info.title {
+            add("dc", language_alternative("dc:title", title));
+        }
+        if let Some(author) = &info.author {
+            add("dc", sequence("dc:creator", author));
+        }
+        if let Some(subject) = &info.subject {
+            add("dc", language_alternative("dc:description", subject));
+        }
+        if let Some(keywords) = &info.keywords {
+            add("pdf", simple_property("pdf:Keywords", keywords));
+        }
+        if let Some(producer) = &info.producer {
+            add("pdf", simple_property("pdf:Producer", producer));
+        }
+        if let Some(trapped) = info.trapped {
+            let trapped = match trapped {
+                Trapped::True => "True",
+                Trapped::False => "False",
+                Trapped::Unknown => "Unknown",
+            };
+            add("pdf", simple_property("pdf:Trapped", trapped));
+        }
+        if let Some(creator) = &info.creator {
+            add("xmp", simple_property("xmp:CreatorTool", creator));
+        }
+        if let Some(creation_date) = &info.creation_date {
+            add(
+                "xmp",
+                simple_property("xmp:CreateDate", &creation_date.to_iso_8601()),
+            );
+        }
+        if let Some(mod_date) = &info.mod_date {
+            add(
+                "xmp",
+                simple_property("xmp:ModifyDate", &mod_date.to_iso_8601()),
+            );
+        }
+        if let Some(pdfa) = &self.pdfa_identification {
+            add(
+                "pdfaid",
+                simple_property("pdfaid:part", &pdfa.part.to_string()),
+            );
+            add(
+                "pdfaid",
+                simple_property("pdfaid:conformance", &pdfa.conformance),
+            );
+        }
+        if let Some(part) = self.pdfua_part {
+            add(
+                "pdfuaid",
+                simple_property("pdfuaid:part", &part.to_string()),
+            );
+        }
+
+        for property in &self.custom_properties {
+            if !is_xml_name(&property.prefix) || !is_xml_name(&property.name) {
+                return Err(Error::InvalidInput(format!(
+                    "invalid XMP property name {}:{}",
+                    property.prefix, property.name
+                )));
+            }
+            let (namespace, properties) = schemas
+                .entry(&property.prefix)
+                .or_insert_with(|| (&property.namespace, String::new()));
+            let is_reserved = STANDARD_NAMESPACES
+                .iter()
+                .any(|(prefix, _)| *prefix == property.prefix);
+            if *namespace != property.namespace || is_reserved {
+                return Err(Error::InvalidInput(format!(
+                    "the XMP namespace prefix {} is already used for {}",
+                    property.prefix, namespace
+                )));
+            }
+            let name = format!("{}:{}", property.prefix, property.name);
+            properties.push_str(&simple_property(&name, &property.value));
+        }
+
+        let mut packet =
+            String::from("<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n");
+        packet.push_str("<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n");
+        packet.push_str(&format!("<rdf:RDF xmlns:rdf=\"{}\">\n", RDF_NAMESPACE));
+        for (prefix, (namespace, properties)) in schemas {
+            packet.push_str(&format!(
+                "<rdf:Description rdf:about=\"\" xmlns:{}=\"{}\">\n{}</rdf:Description>\n",
+                prefix,
+                escape_xml(namespace),
+                properties
+            ));
+        }
+        // PDF/A requires a description of every schema that is not predefined by XMP
+        let has_extensions = !self.custom_properties.is_empty() || self.pdfua_part.is_some();
+        if self.pdfa_identification.is_some() && has_extensions {
+            packet.push_str(&self.extension_schemas());
+        }
+        packet.push_str("</rdf:RDF>\n</x:xmpmeta>\n<?xpacket end=\"w\"?>");
+        Ok(packet)
+    }
+
+    /// The PDF/A extension schema description of the `pdfuaid` schema and the custom
+    /// properties.
+    fn extension_schemas(&self) -> String {
+        // the name, value type, category and description of the properties by prefix
+        let mut schemas: BTreeMap<&str, (&str, Vec<[&str; 4]>)> = BTreeMap::new();
+        if self.pdfua_part.is_some() {
+            let part = ["part", "Integer", "internal", "The part of ISO 14289"];
+            schemas.insert("pdfuaid", (PDFUA_ID_NAMESPACE, vec![part]));
+        }
+        for property in &self.custom_properties {
+            let (_, properties) = schemas
+                .entry(&property.prefix)
+                .or_insert_with(|| (&property.namespace, Vec::new()));
+            if !properties.iter().any(|[name, ..]| *name == property.name) {
+                let name = property.name.as_str();
+                properties.push([name, "Text", "external", name]);
+            }
+        }
+
+        let mut description = format!(
+            "<rdf:Description rdf:about=\"\" xmlns:pdfaExtension=\"{}\" \
+             xmlns:pdfaSchema=\"{}\" xmlns:pdfaProperty=\"{}\">\n\
+             <pdfaExtension:schemas><rdf:Bag>\n",
+            PDFA_EXTENSION_NAMESPACE, PDFA_SCHEMA_NAMESPACE, PDFA_PROPERTY_NAMESPACE
+        );
+        for (prefix, (namespace, properties)) in schemas {
+            description.push_str("<rdf:li rdf:parseType=\"Resource\">\n");
+            description.push_str(&simple_property("pdfaSchema:schema", prefix));
+            description.push_str(&simple_property("pdfaSchema:namespaceURI", namespace));
+            description.push_str(&simple_property("pdfaSchema:prefix", prefix));
+            description.push_str("<pdfaSchema:property><rdf:Seq>\n");
+            for [name, value_type, category, text] in properties {
+                description.push_str("<rdf:li rdf:parseType=\"Resource\">\n");
+                description.push_str(&simple_property("pdfaProperty:name", name));
+                description.push_str(&simple_property("pdfaProperty:valueType", value_type));
+                description.push_str(&simple_property("pdfaProperty:category", category));
+                description.push_str(&simple_property("pdfaProperty:description", text));
+                description.push_str("</rdf:li>\n");
+            }
+            description.push_str("</rdf:Seq></pdfaSchema:property>\n</rdf:li>\n");
+        }
+        description.push_str("</rdf:Bag></pdfaExtension:schemas>\n</rdf:Description>\n");
+        description
+    }
+
+    /// The uncompressed metadata stream with the XMP packet for `info`.
+    pub(crate) fn to_stream(&self, info: &DocumentInfo) -> Result<Stream> {
+        let mut stream = Stream::with_bytes(self.to_packet(info)?.into_bytes(), None);
+        stream.add_key_value("Type".to_string(), Box::new("Metadata"));
+        stream.add_key_value("Subtype".to_string(), Box::new("XML"));
+        Ok(stream)
+    }
+}
+
+fn simple_property(name: &str, value: &str) -> String {
+    format!("<{0}>{1}</{0}>\n", name, escape_xml(value))
+}
+
+/// A property with alternative values in different languages, of which only the default
+/// is given.
+fn language_alternative(name: &str, value: &str) -> String {
+    format!(
+        "<{0}><rdf:Alt><rdf:li xml:lang=\"x-default\">{1}</rdf:li></rdf:Alt></{0}>\n",
+        name,
+        escape_xml(value)
+    )
+}
+
+/// An ordered array property with the single item `value`.
+fn sequence(name: &str, value: &str) -> String {
+    format!(
+        "<{0}><rdf:Seq><rdf:li>{1}</rdf:li></rdf:Seq></{0}>\n",
+        name,
+        escape_xml(value)
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for chr in text.chars() {
+        match chr {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+/// Whether `name` can be used as a namespace prefix or a local name, i.e. is a valid XML name
+/// without a colon.
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '_')
+        && chars.all(|chr| chr.is_alphanumeric() || chr == '_' || chr == '-' || chr == '.')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::date::PdfDate;
+    use crate::reader::ExistingDocument;
+    use crate::{DocumentContext, Version};
+
+    #[test]
+    fn test_xmp_metadata() {
+        let mut output = Vec::new();
+        let mut context = DocumentContext::with_writer(&mut output, Version::Pdf1_7).unwrap();
+        context.document_info.title = Some("Tom & Jerry".to_string());
+        context.document_info.author = Some("Jörg".to_string());
+        context.document_info.creation_date = Some(PdfDate::new(2020, 5, 17));
+        let mut xmp_metadata = XmpMetadata::new();
+        xmp_metadata.add_property("http://ns.example.com/dam/1.0/", "dam", "AssetId", "A-17");
+        context.xmp_metadata = Some(xmp_metadata);
+        context.finish().unwrap();
+
+        let document = ExistingDocument::parse(&output).unwrap();
+        let catalog = document
+            .resolve_dictionary(document.trailer.get_reference("Root").unwrap())
+            .unwrap();
+        let (dictionary, metadata) = document
+            .resolve_stream(catalog.get_reference("Metadata").unwrap())
+            .unwrap();
+        assert_eq!(dictionary.get("Type").unwrap().0, b"/Metadata");
+        assert_eq!(dictionary.get("Subtype").unwrap().0, b"/XML");
+        assert_eq!(dictionary.get("Filter"), None);
+        let metadata = String::from_utf8(metadata).unwrap();
+        assert!(metadata.contains("<rdf:li xml:lang=\"x-default\">Tom &amp; Jerry</rdf:li>"));
+        assert!(metadata.contains("<rdf:Seq><rdf:li>Jörg</rdf:li></rdf:Seq>"));
+        assert!(metadata.contains("<xmp:CreateDate>2020-05-17T00:00:00Z</xmp:CreateDate>"));
+        assert!(metadata.contains("xmlns:dam=\"http://ns.example.com/dam/1.0/\""));
+        assert!(metadata.contains("<dam:AssetId>A-17</dam:AssetId>"));
+        let info = document
+            .resolve_dictionary(document.trailer.get_reference("Info").unwrap())
+            .unwrap();
+        assert_eq!(info.get("CreationDate").unwrap().0, b"(D:20200517000000Z)");
+
+        let mut xmp_metadata = XmpMetadata::new();
+        xmp_metadata.add_property("http://example.com/", "dc", "Other", "value");
+        let info = DocumentInfo::default();
+        assert!(xmp_metadata.to_packet(&info).is_err());
+        let mut xmp_metadata = XmpMetadata::new();
+        xmp_metadata.add_property("http://example.com/", "ex", "in valid", "value");
+        assert!(xmp_metadata.to_packet(&info).is_err());
+    }
+}